@@ -0,0 +1,468 @@
+//! Combining steady sections into ramp shaped blocks
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::course::{Hint, Kind, Section};
+use crate::parse_duration;
+
+/// Largest deviation from a straight line still accepted as ramp, relative to FTP
+pub const DEFAULT_RAMP_TOLERANCE: f64 = 0.02;
+/// Fewer sections don't make a ramp
+const MIN_RAMP_SECTIONS: usize = 3;
+/// Fewer on/off pairs don't make intervals
+const MIN_INTERVAL_REPEAT: u32 = 2;
+
+/// A linear change of power, e.g. "10m:0.4-0.75"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampSpec {
+    /// duration in seconds
+    pub duration: u32,
+    /// power at the start relative to FTP
+    pub from: f64,
+    /// power at the end relative to FTP
+    pub to: f64,
+}
+
+impl FromStr for RampSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid ramp \"{}\", expected e.g. \"10m:0.4-0.75\"", s);
+
+        let (duration, powers) = s.split_once(':').ok_or_else(invalid)?;
+        let (from, to) = powers.split_once('-').ok_or_else(invalid)?;
+        Ok(RampSpec {
+            duration: parse_duration(duration)?,
+            from: from.trim().parse().map_err(|_| invalid())?,
+            to: to.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Display for RampSpec {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}s:{}-{}", self.duration, self.from, self.to)
+    }
+}
+
+/// Where a warmup or cooldown comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockOption {
+    /// detected from a ramp in the data
+    Detect,
+    /// replaces the data of the given duration
+    Fixed(RampSpec),
+}
+
+impl FromStr for BlockOption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(BlockOption::Detect),
+            _ => s.parse().map(BlockOption::Fixed),
+        }
+    }
+}
+
+impl Display for BlockOption {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BlockOption::Detect => write!(f, "auto"),
+            BlockOption::Fixed(spec) => write!(f, "{}", spec),
+        }
+    }
+}
+
+/// Turns the start of the workout into a warmup block
+pub fn warmup(sections: &mut Vec<Section>, option: BlockOption, tolerance: f64) {
+    let (count, from, to) = match option {
+        BlockOption::Detect => {
+            // the longest linear ramp wins
+            let length = ramp_length(sections.iter(), |last, power| power > last);
+            let Some((count, (from, to))) = (MIN_RAMP_SECTIONS..=length).rev()
+                .find_map(|count| fit_ramp(&sections[..count], tolerance).map(|ramp| (count, ramp))) else {
+                return;
+            };
+            (count, from, to)
+        },
+        BlockOption::Fixed(spec) => {
+            let Some(first) = sections.first() else {
+                return;
+            };
+            let end = first.start + spec.duration;
+            cut(sections, end);
+            (sections.iter().take_while(|sec| sec.start < end).count(), spec.from, spec.to)
+        },
+    };
+
+    if count == 0 {
+        return;
+    }
+    let block = merge(sections.drain(..count), from, Kind::Warmup { end_power: to });
+    sections.insert(0, block);
+}
+
+/// Turns the end of the workout into a cooldown block
+pub fn cooldown(sections: &mut Vec<Section>, option: BlockOption, tolerance: f64) {
+    let (count, from, to) = match option {
+        BlockOption::Detect => {
+            // walking backwards, the power of a cooldown rises
+            let length = ramp_length(sections.iter().rev(), |next, power| power > next);
+            let Some((count, (from, to))) = (MIN_RAMP_SECTIONS..=length).rev()
+                .find_map(|count| fit_ramp(&sections[sections.len() - count..], tolerance).map(|ramp| (count, ramp))) else {
+                return;
+            };
+            (count, from, to)
+        },
+        BlockOption::Fixed(spec) => {
+            let Some(last) = sections.last() else {
+                return;
+            };
+            let start = (last.start + last.duration).saturating_sub(spec.duration);
+            cut(sections, start);
+            (sections.iter().rev().take_while(|sec| sec.start >= start).count(), spec.from, spec.to)
+        },
+    };
+
+    if count == 0 {
+        return;
+    }
+    let block = merge(sections.drain(sections.len() - count..), from, Kind::Cooldown { end_power: to });
+    sections.push(block);
+}
+
+/// Collapses steady sections with a linear progression of power into ramp blocks
+pub fn ramps(sections: &mut Vec<Section>, tolerance: f64) {
+    let mut index = 0;
+    while index < sections.len() {
+        let rest = &sections[index..];
+        let rising = rest.len() > 1 && rest[1].power > rest[0].power;
+        let length = ramp_length(rest.iter(), |last, power| if rising { power > last } else { power < last });
+
+        let ramp = (MIN_RAMP_SECTIONS..=length).rev()
+            .find_map(|count| fit_ramp(&rest[..count], tolerance).map(|ramp| (count, ramp)));
+        if let Some((count, (from, to))) = ramp {
+            let block = merge(sections.drain(index..index + count), from, Kind::Ramp { end_power: to });
+            sections.insert(index, block);
+        }
+        index += 1;
+    }
+}
+
+/// Collapses repeated pairs of a hard and an easy section into interval blocks
+pub fn intervals(sections: &mut Vec<Section>) {
+    let mut index = 0;
+    while index + 1 < sections.len() {
+        let (on, off) = (&sections[index], &sections[index + 1]);
+        let same = |a: &Section, b: &Section| {
+            a.kind == Kind::SteadyState && a.duration == b.duration && a.power == b.power && a.cadence == b.cadence
+        };
+
+        let mut repeat = 0;
+        if same(on, on) && same(off, off) && on.power > off.power {
+            while let [next_on, next_off, ..] = &sections[index + 2 * repeat as usize..] {
+                if !same(next_on, on) || !same(next_off, off) {
+                    break;
+                }
+                repeat += 1;
+            }
+        }
+
+        if repeat >= MIN_INTERVAL_REPEAT {
+            let kind = Kind::IntervalsT {
+                repeat,
+                on_duration: on.duration,
+                off_duration: off.duration,
+                off_power: off.power,
+                cadence_resting: off.cadence,
+            };
+            let power = on.power;
+            let block = merge(sections.drain(index..index + 2 * repeat as usize), power, kind);
+            sections.insert(index, block);
+        }
+        index += 1;
+    }
+}
+
+/// Combines consecutive steady sections of the same cadence whose power differs by at most the tolerance
+/// into one at their average power
+pub fn merge_steady(sections: &mut Vec<Section>, tolerance: f64) {
+    let mut merged: Vec<Section> = Vec::with_capacity(sections.len());
+    for sec in sections.drain(..) {
+        match merged.last() {
+            Some(last) if last.kind == Kind::SteadyState && sec.kind == Kind::SteadyState && last.cadence == sec.cadence
+                && (sec.power - last.power).abs() <= tolerance + f64::EPSILON => {
+                let last = merged.pop().expect("a last section");
+                let power = round_power((last.power * last.duration as f64 + sec.power * sec.duration as f64) / (last.duration + sec.duration) as f64);
+                merged.push(merge([last, sec].into_iter(), power, Kind::SteadyState));
+            },
+            _ => merged.push(sec),
+        }
+    }
+    *sections = merged;
+}
+
+/// Simplifies runs of steady sections of the same cadence in the manner of Ramer-Douglas-Peucker:
+/// a run becomes one section at its average power unless a section deviates by more than the tolerance,
+/// which then stays on its own while the parts before and after it are simplified likewise
+pub fn simplify(sections: &mut Vec<Section>, tolerance: f64) {
+    let mut simplified: Vec<Section> = Vec::with_capacity(sections.len());
+    let mut run: Vec<Section> = vec![];
+    for sec in sections.drain(..) {
+        if sec.kind != Kind::SteadyState || run.last().is_some_and(|last| last.cadence != sec.cadence) {
+            simplify_run(std::mem::take(&mut run), tolerance, &mut simplified);
+        }
+        match sec.kind {
+            Kind::SteadyState => run.push(sec),
+            _ => simplified.push(sec),
+        }
+    }
+    simplify_run(run, tolerance, &mut simplified);
+    // neighbors of a kept section may now be within the tolerance
+    merge_steady(&mut simplified, tolerance);
+    *sections = simplified;
+}
+
+fn simplify_run(mut run: Vec<Section>, tolerance: f64, out: &mut Vec<Section>) {
+    if run.len() < 2 {
+        out.extend(run);
+        return;
+    }
+    let duration: u32 = run.iter().map(|sec| sec.duration).sum();
+    let average = run.iter().map(|sec| sec.power * sec.duration as f64).sum::<f64>() / duration.max(1) as f64;
+    let (farthest, deviation) = run.iter().map(|sec| (sec.power - average).abs()).enumerate()
+        .fold((0, 0.0), |best, (index, deviation)| if deviation > best.1 { (index, deviation) } else { best });
+    if deviation <= tolerance + f64::EPSILON {
+        out.push(merge(run.into_iter(), round_power(average), Kind::SteadyState));
+        return;
+    }
+    let after = run.split_off(farthest + 1);
+    let kept = run.pop().expect("the farthest section");
+    simplify_run(run, tolerance, out);
+    out.push(kept);
+    simplify_run(after, tolerance, out);
+}
+
+/// Splits steady sections longer than the maximum into equally long ones on the raster,
+/// optionally telling the progress at the start of every further one
+pub fn split_long(sections: &mut Vec<Section>, max: u32, raster: u32, progress: bool) {
+    if max == 0 {
+        return;
+    }
+    let long: Vec<(u32, u32)> = sections.iter()
+        .filter(|sec| sec.kind == Kind::SteadyState && sec.duration > max)
+        .map(|sec| (sec.start, sec.duration))
+        .collect();
+    for (start, duration) in long {
+        let count = duration.div_ceil(max);
+        for part in 1..count {
+            let offset = ((part * duration) as f64 / count as f64 / raster.max(1) as f64).round() as u32 * raster.max(1);
+            if offset == 0 || offset >= duration {
+                continue;
+            }
+            cut(sections, start + offset);
+            if progress {
+                let text = if 2 * part == count { "Halfway".to_string() } else { format!("Part {} of {}", part + 1, count) };
+                if let Some(sec) = sections.iter_mut().find(|sec| sec.start == start + offset) {
+                    sec.text.insert(0, Hint { offset: 0, text, duration: None });
+                }
+            }
+        }
+    }
+}
+
+/// Number of steady sections in a row whose power keeps changing in the given direction
+fn ramp_length<'a>(sections: impl Iterator<Item = &'a Section>, direction: impl Fn(f64, f64) -> bool) -> usize {
+    let mut count = 0;
+    let mut last: Option<&Section> = None;
+    for sec in sections {
+        if sec.kind != Kind::SteadyState || last.is_some_and(|last| !direction(last.power, sec.power) || last.cadence != sec.cadence) {
+            break;
+        }
+        last = Some(sec);
+        count += 1;
+    }
+    count
+}
+
+/// Fits a line through the section powers, returning the power at the start and the end.
+/// Each section is expected to hold the power at its midpoint.
+fn fit_ramp(sections: &[Section], tolerance: f64) -> Option<(f64, f64)> {
+    let (first, last) = (sections.first()?, sections.last()?);
+    if sections.len() < MIN_RAMP_SECTIONS {
+        return None;
+    }
+
+    let mid = |sec: &Section| sec.start as f64 + sec.duration as f64 / 2.0;
+    let slope = (last.power - first.power) / (mid(last) - mid(first));
+    let linear = sections.iter()
+        .all(|sec| (first.power + slope * (mid(sec) - mid(first)) - sec.power).abs() <= tolerance + f64::EPSILON);
+    if !linear {
+        return None;
+    }
+
+    let from = first.power - slope * (mid(first) - first.start as f64);
+    let to = last.power + slope * ((last.start + last.duration) as f64 - mid(last));
+    Some((round_power(from), round_power(to)))
+}
+
+/// Splits the section running across the given time, so a section starts there
+fn cut(sections: &mut Vec<Section>, time: u32) {
+    let Some(index) = sections.iter().position(|sec| sec.start < time && time < sec.start + sec.duration) else {
+        return;
+    };
+
+    let sec = &mut sections[index];
+    let offset = time - sec.start;
+    let (before, after): (Vec<_>, Vec<_>) = sec.text.drain(..).partition(|hint| hint.offset < offset);
+    let tail = Section {
+        start: time,
+        duration: sec.duration - offset,
+        power: sec.power,
+        kind: sec.kind,
+        cadence: sec.cadence,
+        text: after.into_iter().map(|mut hint| { hint.offset -= offset; hint }).collect(),
+    };
+    sec.duration = offset;
+    sec.text = before;
+    sections.insert(index + 1, tail);
+}
+
+/// Combines consecutive sections into one block, keeping their texts
+fn merge(sections: impl Iterator<Item = Section>, power: f64, kind: Kind) -> Section {
+    let mut block: Option<Section> = None;
+    for sec in sections {
+        match &mut block {
+            None => block = Some(Section { power, kind, ..sec }),
+            Some(block) => {
+                let shift = sec.start - block.start;
+                block.text.extend(sec.text.into_iter().map(|mut hint| { hint.offset += shift; hint }));
+                block.duration = sec.start + sec.duration - block.start;
+            },
+        }
+    }
+    block.expect("merging at least one section")
+}
+
+fn round_power(power: f64) -> f64 {
+    (power * 100.0).round() / 100.0
+}
+
+#[test]
+fn test_warmup() {
+    use crate::Hint;
+
+    let section = |start, power| Section { start, duration: 60, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut sections = vec![section(0, 0.45), section(60, 0.55), section(120, 0.65), section(180, 1.0), section(240, 0.5)];
+    sections[1].text.push(Hint { offset: 10, text: "Easy".to_string(), duration: None });
+
+    warmup(&mut sections, BlockOption::Detect, DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(3, sections.len());
+    assert_eq!((0, 180, 0.4, Kind::Warmup { end_power: 0.7 }), (sections[0].start, sections[0].duration, sections[0].power, sections[0].kind));
+    assert_eq!(70, sections[0].text[0].offset);
+
+    let mut sections = vec![section(0, 0.5), section(60, 0.6), section(120, 1.0)];
+    warmup(&mut sections, BlockOption::Fixed("90s:0.3-0.6".parse().unwrap()), DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(3, sections.len());
+    assert_eq!((0, 90, 0.3), (sections[0].start, sections[0].duration, sections[0].power));
+    assert_eq!((90, 30, 0.6), (sections[1].start, sections[1].duration, sections[1].power));
+}
+
+#[test]
+fn test_cooldown() {
+    let section = |start, power| Section { start, duration: 60, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+
+    let mut sections = vec![section(0, 1.0), section(60, 0.75), section(120, 0.65), section(180, 0.55)];
+    cooldown(&mut sections, BlockOption::Detect, DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(2, sections.len());
+    assert_eq!((60, 180, 0.8, Kind::Cooldown { end_power: 0.5 }), (sections[1].start, sections[1].duration, sections[1].power, sections[1].kind));
+
+    let mut sections = vec![section(0, 1.0), section(60, 0.6)];
+    cooldown(&mut sections, BlockOption::Fixed("90s:0.6-0.3".parse().unwrap()), DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(2, sections.len());
+    assert_eq!((0, 30, 1.0), (sections[0].start, sections[0].duration, sections[0].power));
+    assert_eq!((30, 90, 0.6), (sections[1].start, sections[1].duration, sections[1].power));
+}
+
+#[test]
+fn test_ramps() {
+    let section = |start, power| Section { start, duration: 60, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+
+    let mut sections = vec![section(0, 0.5), section(60, 0.8), section(120, 0.9), section(180, 1.0), section(240, 0.5)];
+    ramps(&mut sections, DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(3, sections.len());
+    assert_eq!((60, 180, 0.75, Kind::Ramp { end_power: 1.05 }), (sections[1].start, sections[1].duration, sections[1].power, sections[1].kind));
+
+    // too far off a straight line
+    let mut sections = vec![section(0, 0.5), section(60, 0.6), section(120, 0.8)];
+    ramps(&mut sections, DEFAULT_RAMP_TOLERANCE);
+    assert_eq!(3, sections.len());
+    ramps(&mut sections, 0.05);
+    assert_eq!(1, sections.len());
+}
+
+#[test]
+fn test_intervals() {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    for (duration, power) in [(300, 0.5), (30, 1.2), (30, 0.5), (30, 1.2), (30, 0.5), (30, 1.2), (30, 0.5), (30, 1.2), (300, 0.5)] {
+        sections.push(Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] });
+        start += duration;
+    }
+
+    intervals(&mut sections);
+    assert_eq!(4, sections.len());
+    assert_eq!(Kind::IntervalsT { repeat: 3, on_duration: 30, off_duration: 30, off_power: 0.5, cadence_resting: None }, sections[1].kind);
+    assert_eq!((300, 180, 1.2), (sections[1].start, sections[1].duration, sections[1].power));
+    assert_eq!((480, 30, 1.2), (sections[2].start, sections[2].duration, sections[2].power));
+}
+
+#[test]
+fn test_intervals_cadence() {
+    let mut sections = Vec::new();
+    for (index, (power, cadence)) in [(1.2, 110), (0.5, 85), (1.2, 110), (0.5, 85)].into_iter().enumerate() {
+        sections.push(Section { start: index as u32 * 60, duration: 60, power, kind: Kind::SteadyState, cadence: Some(cadence), text: vec![] });
+    }
+
+    intervals(&mut sections);
+    assert_eq!(1, sections.len());
+    assert_eq!(Some(110), sections[0].cadence);
+    assert_eq!(Kind::IntervalsT { repeat: 2, on_duration: 60, off_duration: 60, off_power: 0.5, cadence_resting: Some(85) }, sections[0].kind);
+}
+
+#[test]
+fn test_merge_steady() {
+    use crate::Hint;
+
+    let section = |start, power| Section { start, duration: 60, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut sections = vec![section(0, 0.75), section(60, 0.76), section(120, 0.74), section(180, 1.0), section(240, 1.0)];
+    sections[2].text.push(Hint { offset: 10, text: "Steady".to_string(), duration: None });
+
+    merge_steady(&mut sections, 0.02);
+    assert_eq!(vec![(0, 180, 0.75), (180, 120, 1.0)], sections.iter().map(|sec| (sec.start, sec.duration, sec.power)).collect::<Vec<_>>());
+    assert_eq!(130, sections[0].text[0].offset);
+}
+
+#[test]
+fn test_simplify() {
+    let section = |start, power| Section { start, duration: 60, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let powers = [0.5, 0.52, 0.48, 1.0, 1.03, 0.98, 1.0, 0.5];
+    let mut sections: Vec<Section> = powers.iter().enumerate().map(|(index, power)| section(index as u32 * 60, *power)).collect();
+
+    simplify(&mut sections, 0.05);
+    assert_eq!(vec![(0, 180, 0.5), (180, 240, 1.0), (420, 60, 0.5)], sections.iter().map(|sec| (sec.start, sec.duration, sec.power)).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_split_long() {
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut sections = vec![section(0, 600, 0.5), section(600, 3600, 0.8), section(4200, 2100, 0.9)];
+
+    split_long(&mut sections, 1200, 30, true);
+    let parts: Vec<(u32, u32)> = sections.iter().map(|sec| (sec.start, sec.duration)).collect();
+    assert_eq!(vec![(0, 600), (600, 1200), (1800, 1200), (3000, 1200), (4200, 1050), (5250, 1050)], parts);
+    assert_eq!("Part 2 of 3", sections[2].text[0].text);
+    assert_eq!("Halfway", sections[5].text[0].text);
+}
@@ -0,0 +1,114 @@
+//! Power profile charts of a course, colored by zone
+
+use crate::duration::format_duration;
+use crate::{Course, DurationType, Kind, ZoneModel};
+
+/// Block characters for eighths of a row
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// ANSI 256 colors of the zones from easy to hard, like in Zwift
+const TERMINAL_COLORS: [u8; 7] = [245, 33, 34, 220, 208, 196, 129];
+/// Color of free rides without power target
+const FREE_RIDE_COLOR: u8 = 240;
+/// RGB colors of the zones from easy to hard, like in Zwift
+pub(crate) const ZONE_COLORS: [&str; 7] = ["#7f7f7f", "#338cff", "#59bf59", "#ffcc3f", "#ff8c26", "#f44336", "#9c27b0"];
+/// RGB color of free rides without power target
+pub(crate) const FREE_RIDE_FILL: &str = "#595959";
+/// Least top of the power axis relative to FTP, so easy workouts don't look hard
+pub(crate) const MIN_TOP: f64 = 1.2;
+
+/// Power relative to FTP at the given time, none for free rides and after the end
+pub fn power_at(course: &Course, time: f64) -> Option<f64> {
+    let mut start = 0.0;
+    for sec in &course.sections {
+        for (duration, from, to) in sec.parts() {
+            let end = start + duration as f64;
+            if time < end {
+                if matches!(sec.kind, Kind::FreeRide { .. }) {
+                    return None;
+                }
+                return Some(from + (to - from) * (time - start) / duration as f64);
+            }
+            start = end;
+        }
+    }
+    None
+}
+
+/// Index of the distinct zone the power belongs to, used to pick colors
+pub fn zone_index(zones: &ZoneModel, power: f64) -> usize {
+    let distinct = zones.distinct();
+    zones.classify(power)
+        .and_then(|zone| distinct.iter().position(|known| known.name == zone.name))
+        .unwrap_or(0)
+}
+
+/// Renders the power profile as bar chart of the given size in characters, optionally with ANSI colors
+pub fn render_terminal(course: &Course, zones: &ZoneModel, width: usize, height: usize, color: bool) -> String {
+    let total: u32 = course.sections.iter().map(|sec| sec.duration).sum();
+    let (width, height) = (width.max(1), height.max(1));
+    let top = course.sections.iter().map(|sec| sec.power.max(sec.end_power())).fold(MIN_TOP, f64::max);
+
+    // power in the middle of every column
+    let columns: Vec<Option<f64>> = (0..width)
+        .map(|column| power_at(course, (column as f64 + 0.5) * total as f64 / width as f64))
+        .collect();
+
+    let mut out = String::new();
+    for row in (0..height).rev() {
+        let label = match row {
+            _ if row + 1 == height => format!("{:>4.0}%", top * 100.0),
+            0 => format!("{:>5}", "0%"),
+            _ => " ".repeat(5),
+        };
+        out.push_str(&label);
+        out.push_str(" │");
+
+        for power in &columns {
+            // free rides are drawn as a low bar
+            let (power, code) = match power {
+                Some(power) => (*power, TERMINAL_COLORS[zone_index(zones, *power) % TERMINAL_COLORS.len()]),
+                None => (top / height as f64, FREE_RIDE_COLOR),
+            };
+            let eighths = (power / top * height as f64 * 8.0).round() as usize;
+            let cell = match eighths.saturating_sub(row * 8) {
+                0 => ' ',
+                filled => BLOCKS[filled.min(8) - 1],
+            };
+            if color && cell != ' ' {
+                out.push_str(&format!("\x1b[38;5;{}m{}\x1b[0m", code, cell));
+            } else {
+                out.push(cell);
+            }
+        }
+        out.push('\n');
+    }
+
+    out.push_str(&format!("{} └{}\n", " ".repeat(5), "─".repeat(width)));
+    let end = match course.duration_type {
+        DurationType::Time => format_duration(total),
+        DurationType::Distance => format!("{} m", total),
+    };
+    out.push_str(&format!("{} 0{:>width$}\n", " ".repeat(6), end, width = width.saturating_sub(1)));
+    out
+}
+
+#[test]
+fn test_render_terminal() {
+    use crate::Section;
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![section(0, 60, 0.3), section(60, 60, 1.2)],
+        ..Course::default()
+    };
+
+    assert_eq!(Some(0.3), power_at(&course, 30.0));
+    assert_eq!(None, power_at(&course, 120.0));
+
+    let chart = render_terminal(&course, &ZoneModel::coggan(), 4, 2, false);
+    assert_eq!(" 120% │  ██\n   0% │▄▄██\n      └────\n       0 2m\n", chart);
+    assert!(render_terminal(&course, &ZoneModel::coggan(), 4, 2, true).contains("\x1b[38;5;245m▄"));
+}
@@ -0,0 +1,252 @@
+//! Shell completion scripts generated from the command line definition
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::{Arg, Command};
+
+/// Shells completion scripts can be generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unsupported shell \"{}\", expected \"bash\", \"zsh\" or \"fish\"", s)),
+        }
+    }
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+/// Writes the completion script for the command, its options and visible subcommands
+pub fn generate(shell: Shell, mut command: Command, sink: &mut dyn Write) -> std::io::Result<()> {
+    // adds the help options and propagates global options to the subcommands
+    command.build();
+    match shell {
+        Shell::Bash => bash(&command, sink),
+        Shell::Zsh => zsh(&command, sink),
+        Shell::Fish => fish(&command, sink),
+    }
+}
+
+fn options(command: &Command) -> impl Iterator<Item = &Arg> {
+    command.get_arguments().filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+}
+
+fn subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command.get_subcommands().filter(|sub| !sub.is_hide_set())
+}
+
+/// Whether the option is followed by a value
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_num_args().is_none_or(|range| range.takes_values())
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values().iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_string()).collect()
+}
+
+/// All spellings like "-f" and "--format"
+fn flags(arg: &Arg) -> Vec<String> {
+    let short = arg.get_short().map(|short| format!("-{}", short));
+    let long = arg.get_long().map(|long| format!("--{}", long));
+    short.into_iter().chain(long).collect()
+}
+
+/// First line of the help text, escaped for single quotes of the shell
+fn help(text: Option<String>, shell: Shell) -> String {
+    let text = text.unwrap_or_default();
+    let line = text.lines().next().unwrap_or("");
+    match shell {
+        Shell::Fish => line.replace('\\', "\\\\").replace('\'', "\\'"),
+        // brackets would end the description in _arguments specs
+        _ => line.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]"),
+    }
+}
+
+fn bash(command: &Command, sink: &mut dyn Write) -> std::io::Result<()> {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+
+    writeln!(sink, "{}() {{", function)?;
+    writeln!(sink, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" sub=\"\" word")?;
+    writeln!(sink, "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do")?;
+    writeln!(sink, "        case \"$word\" in")?;
+    let names: Vec<&str> = subcommands(command).map(|sub| sub.get_name()).collect();
+    writeln!(sink, "            {}) sub=\"$word\"; break ;;", names.join("|"))?;
+    writeln!(sink, "        esac")?;
+    writeln!(sink, "    done")?;
+    writeln!(sink)?;
+
+    // values of the previous option
+    writeln!(sink, "    case \"$prev\" in")?;
+    for arg in options(command).filter(|arg| takes_value(arg)) {
+        let values = values(arg);
+        let completion = if values.is_empty() { "compgen -f -- \"$cur\"".to_string() } else { format!("compgen -W \"{}\" -- \"$cur\"", values.join(" ")) };
+        writeln!(sink, "        {}) COMPREPLY=($({})); return ;;", flags(arg).join("|"), completion)?;
+    }
+    writeln!(sink, "    esac")?;
+    writeln!(sink)?;
+
+    writeln!(sink, "    local words")?;
+    writeln!(sink, "    case \"$sub\" in")?;
+    for sub in subcommands(command) {
+        let words: Vec<String> = options(sub).flat_map(flags).collect();
+        writeln!(sink, "        {}) words=\"{}\" ;;", sub.get_name(), words.join(" "))?;
+    }
+    let words: Vec<String> = options(command).flat_map(flags).chain(names.iter().map(|name| name.to_string())).collect();
+    writeln!(sink, "        *) words=\"{}\" ;;", words.join(" "))?;
+    writeln!(sink, "    esac")?;
+    writeln!(sink, "    if [[ \"$cur\" == -* || -z \"$sub\" ]]; then")?;
+    writeln!(sink, "        COMPREPLY=($(compgen -W \"$words\" -- \"$cur\") $(compgen -f -- \"$cur\"))")?;
+    writeln!(sink, "    else")?;
+    writeln!(sink, "        COMPREPLY=($(compgen -f -- \"$cur\"))")?;
+    writeln!(sink, "    fi")?;
+    writeln!(sink, "}}")?;
+    writeln!(sink, "complete -o filenames -F {} {}", function, name)
+}
+
+fn zsh(command: &Command, sink: &mut dyn Write) -> std::io::Result<()> {
+    let name = command.get_name();
+    let spec = |arg: &Arg| -> String {
+        let help = help(arg.get_help().map(|help| help.to_string()), Shell::Zsh);
+        let value = match (takes_value(arg), values(arg)) {
+            (false, _) => String::new(),
+            (true, values) if values.is_empty() => ":value:_files".to_string(),
+            (true, values) => format!(":value:({})", values.join(" ")),
+        };
+        let repeat = matches!(arg.get_action(), clap::ArgAction::Append);
+        match &flags(arg)[..] {
+            [flag] if repeat => format!("'*{}[{}]{}'", flag, help, value),
+            [flag] => format!("'{}[{}]{}'", flag, help, value),
+            flags if repeat => format!("'*'{{{}}}'[{}]{}'", flags.join(","), help, value),
+            flags => format!("'({})'{{{}}}'[{}]{}'", flags.join(" "), flags.join(","), help, value),
+        }
+    };
+
+    writeln!(sink, "#compdef {}", name)?;
+    writeln!(sink)?;
+    writeln!(sink, "_{}() {{", name)?;
+    writeln!(sink, "    local -a commands")?;
+    writeln!(sink, "    commands=(")?;
+    for sub in subcommands(command) {
+        writeln!(sink, "        '{}:{}'", sub.get_name(), help(sub.get_about().map(|about| about.to_string()), Shell::Zsh))?;
+    }
+    writeln!(sink, "    )")?;
+    writeln!(sink)?;
+    writeln!(sink, "    case \"${{words[2]}}\" in")?;
+    for sub in subcommands(command) {
+        writeln!(sink, "        {})", sub.get_name())?;
+        writeln!(sink, "            _arguments -s \\")?;
+        for arg in options(sub) {
+            writeln!(sink, "                {} \\", spec(arg))?;
+        }
+        writeln!(sink, "                '*::file:_files'")?;
+        writeln!(sink, "            ;;")?;
+    }
+    writeln!(sink, "        *)")?;
+    writeln!(sink, "            _arguments -s \\")?;
+    for arg in options(command) {
+        writeln!(sink, "                {} \\", spec(arg))?;
+    }
+    writeln!(sink, "                '1: :{{_describe command commands; _files}}' \\")?;
+    writeln!(sink, "                '*::file:_files'")?;
+    writeln!(sink, "            ;;")?;
+    writeln!(sink, "    esac")?;
+    writeln!(sink, "}}")?;
+    writeln!(sink)?;
+    writeln!(sink, "_{} \"$@\"", name)
+}
+
+fn fish(command: &Command, sink: &mut dyn Write) -> std::io::Result<()> {
+    let name = command.get_name();
+    let complete = |condition: &str, arg: &Arg| -> String {
+        let mut line = format!("complete -c {}", name);
+        if !condition.is_empty() {
+            line.push_str(&format!(" -n '{}'", condition));
+        }
+        if let Some(short) = arg.get_short() {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = arg.get_long() {
+            line.push_str(&format!(" -l {}", long));
+        }
+        if takes_value(arg) {
+            line.push_str(" -r");
+            let values = values(arg);
+            if !values.is_empty() {
+                line.push_str(&format!(" -f -a '{}'", values.join(" ")));
+            }
+        }
+        line.push_str(&format!(" -d '{}'", help(arg.get_help().map(|help| help.to_string()), Shell::Fish)));
+        line
+    };
+
+    for sub in subcommands(command) {
+        writeln!(sink, "complete -c {} -n __fish_use_subcommand -a {} -d '{}'", name, sub.get_name(), help(sub.get_about().map(|about| about.to_string()), Shell::Fish))?;
+    }
+    for arg in options(command) {
+        writeln!(sink, "{}", complete("", arg))?;
+    }
+    // global options were written for the top level already
+    for sub in subcommands(command) {
+        let condition = format!("__fish_seen_subcommand_from {}", sub.get_name());
+        for arg in options(sub).filter(|arg| !arg.is_global_set()) {
+            writeln!(sink, "{}", complete(&condition, arg))?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_generate() {
+    let command = || Command::new("c2z")
+        .arg(Arg::new("format").short('f').long("format").value_parser(["zwo", "fit"]).help("output format [default: \"zwo\"]").global(true))
+        .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue).help("overwrite 'existing' files"))
+        .subcommand(Command::new("stats").about("Print stats").arg(Arg::new("json").long("json").action(clap::ArgAction::SetTrue)))
+        .subcommand(Command::new("completions").hide(true));
+
+    let script = |shell| {
+        let mut out = Vec::new();
+        generate(shell, command(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let bash = script(Shell::Bash);
+    assert!(bash.contains("            stats|help) sub=\"$word\"; break ;;\n"));
+    assert!(bash.contains("        -f|--format) COMPREPLY=($(compgen -W \"zwo fit\" -- \"$cur\")); return ;;\n"));
+    assert!(bash.contains("        stats) words=\"--json -f --format -h --help\" ;;\n"));
+    assert!(bash.ends_with("complete -o filenames -F _c2z c2z\n"));
+
+    let zsh = script(Shell::Zsh);
+    assert!(zsh.starts_with("#compdef c2z\n"));
+    assert!(zsh.contains("'(-f --format)'{-f,--format}'[output format \\[default: \"zwo\"\\]]:value:(zwo fit)'"));
+    assert!(zsh.contains("'--force[overwrite '\\''existing'\\'' files]'"));
+    assert!(zsh.contains("'stats:Print stats'"));
+    assert!(!zsh.contains("completions"));
+
+    let fish = script(Shell::Fish);
+    assert!(fish.contains("complete -c c2z -n __fish_use_subcommand -a stats -d 'Print stats'\n"));
+    assert!(fish.contains("complete -c c2z -s f -l format -r -f -a 'zwo fit' -d 'output format [default: \"zwo\"]'\n"));
+    assert!(fish.contains("complete -c c2z -l force -d 'overwrite \\'existing\\' files'\n"));
+    assert!(fish.contains("complete -c c2z -n '__fish_seen_subcommand_from stats' -l json -d ''\n"));
+}
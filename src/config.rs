@@ -0,0 +1,150 @@
+//! Defaults for frequently used options, read from a TOML file
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Format, Sport};
+
+/// Options which may be given in the config file, command line options take precedence
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub author: Option<String>,
+    /// absolute FTP in watts
+    pub ftp: Option<u16>,
+    pub sport_type: Option<Sport>,
+    /// duration rasterization in seconds
+    pub raster: Option<u32>,
+    /// zone model: "coggan", "seiler" or a YAML/JSON file
+    pub zones: Option<String>,
+    pub format: Option<Format>,
+    pub out_dir: Option<PathBuf>,
+}
+
+/// A TOML value, of the kinds the config needs
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/course2zwift/config.toml`, falling back to `~/.config` or `%APPDATA%`
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(dir.join("course2zwift").join("config.toml"))
+    }
+
+    /// Reads the given file, or the default one if it exists
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let path = match (path, Self::default_path()) {
+            (Some(path), _) => path.to_path_buf(),
+            (None, Some(path)) if path.is_file() => path,
+            (None, _) => return Ok(Config::default()),
+        };
+        let text = fs::read_to_string(&path).map_err(|err| std::io::Error::new(err.kind(), format!("reading config {}: {}", path.display(), err)))?;
+        Self::parse(&text).map_err(|err| Error::Validation(format!("in config {}: {}", path.display(), err)))
+    }
+
+    /// Parses `key = value` lines, values being strings or integers
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+        for (index, line) in text.lines().enumerate() {
+            let fail = |message: String| format!("line {}: {}", index + 1, message);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(fail("tables are not supported".to_string()));
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| fail("expected key = value".to_string()))?;
+            let key = key.trim().trim_matches('"');
+            let value = parse_value(value.trim()).map_err(fail)?;
+            match (key, value) {
+                ("author", Value::String(author)) => config.author = Some(author),
+                ("ftp", Value::Integer(ftp)) => config.ftp = Some(u16::try_from(ftp).map_err(|_| fail(format!("invalid FTP {}", ftp)))?),
+                ("sport_type", Value::String(sport)) => config.sport_type = Some(sport.parse().map_err(fail)?),
+                ("raster", Value::Integer(raster)) => config.raster = Some(u32::try_from(raster).map_err(|_| fail(format!("invalid raster {}", raster)))?),
+                ("zones", Value::String(zones)) => config.zones = Some(zones),
+                ("format", Value::String(format)) => config.format = Some(format.parse().map_err(fail)?),
+                ("out_dir", Value::String(dir)) => config.out_dir = Some(PathBuf::from(dir)),
+                ("author" | "ftp" | "sport_type" | "raster" | "zones" | "format" | "out_dir", value) => {
+                    return Err(fail(format!("unexpected value {:?} for \"{}\"", value, key)));
+                },
+                _ => return Err(fail(format!("unknown key \"{}\"", key))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Removes a comment outside of quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            },
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..index],
+            _ => {},
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(literal) = text.strip_prefix('\'') {
+        let literal = literal.strip_suffix('\'').ok_or("unterminated string")?;
+        return Ok(Value::String(literal.to_string()));
+    }
+    if let Some(basic) = text.strip_prefix('"') {
+        let basic = basic.strip_suffix('"').ok_or("unterminated string")?;
+        let mut value = String::with_capacity(basic.len());
+        let mut chars = basic.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('\\') => value.push('\\'),
+                Some('"') => value.push('"'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                other => return Err(format!("unsupported escape \\{}", other.map(String::from).unwrap_or_default())),
+            }
+        }
+        return Ok(Value::String(value));
+    }
+    text.replace('_', "").parse().map(Value::Integer).map_err(|_| format!("invalid value {}", text))
+}
+
+#[test]
+fn test_parse() {
+    let config = Config::parse("# defaults\nauthor = \"Jane \\\"JD\\\" Doe\" # me\nftp = 250\nsport_type = 'run'\nraster = 15\n\nout_dir = 'C:\\Zwift'\n").unwrap();
+    assert_eq!(Config {
+        author: Some("Jane \"JD\" Doe".to_string()),
+        ftp: Some(250),
+        sport_type: Some(Sport::Run),
+        raster: Some(15),
+        zones: None,
+        format: None,
+        out_dir: Some(PathBuf::from("C:\\Zwift")),
+    }, config);
+
+    assert_eq!(Ok(Config { zones: Some("zones#1.yaml".to_string()), ..Config::default() }), Config::parse("zones = \"zones#1.yaml\""));
+    assert_eq!(Err("line 2: unknown key \"weight\"".to_string()), Config::parse("\nweight = 80"));
+    assert_eq!(Err("line 1: unexpected value Integer(250) for \"author\"".to_string()), Config::parse("author = 250"));
+    assert!(Config::parse("[defaults]").is_err());
+    assert!(Config::parse("ftp = 70000").is_err());
+}
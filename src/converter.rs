@@ -0,0 +1,639 @@
+use std::cmp::max;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::blocks::{cooldown, intervals, merge_steady, ramps, simplify, split_long, warmup, BlockOption, DEFAULT_RAMP_TOLERANCE};
+use crate::course::{Hint, Kind, Section};
+use crate::duration::format_duration;
+use crate::input::{InputReader, Steps};
+use crate::Error;
+
+pub const DEFAULT_DURATION_RASTER: u32 = 30;
+pub const DEFAULT_GAP_AFTER: u32 = 60;
+pub const DEFAULT_GAP_POWER: f64 = 0.5;
+
+/// A single row of input data
+#[derive(Debug, Clone, Default)]
+pub struct Step {
+    /// start since the start of the course, or meters in distance based workouts
+    pub time: Duration,
+    pub watts: Option<u16>,
+    /// target cadence in rpm
+    pub cadence: Option<u16>,
+    pub text: Option<String>,
+    /// how long the text is shown in seconds
+    pub text_duration: Option<u32>,
+    /// further texts with offsets relative to `time`
+    pub hints: Vec<Hint>,
+    /// raster in seconds of the section starting with this step, instead of the one of the converter
+    pub raster: Option<u32>,
+    /// key interval keeping its exact start and duration when anchoring
+    pub key: bool,
+}
+
+/// How a row of the input was turned into sections
+#[derive(Debug, PartialEq)]
+pub struct Explanation {
+    /// number of the row starting at 1, none for the course as a whole
+    pub row: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.row {
+            Some(row) => write!(f, "row {}: {}", row, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// What fills long silences between rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Gap {
+    /// the power of the previous row goes on
+    #[default]
+    Hold,
+    /// a recovery block
+    Rest,
+    /// a free ride block
+    FreeRide,
+}
+
+impl FromStr for Gap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hold" => Ok(Gap::Hold),
+            "rest" => Ok(Gap::Rest),
+            "freeride" => Ok(Gap::FreeRide),
+            _ => Err(format!("unknown gap policy \"{}\", expected \"hold\", \"rest\" or \"freeride\"", s)),
+        }
+    }
+}
+
+impl Display for Gap {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Gap::Hold => write!(f, "hold"),
+            Gap::Rest => write!(f, "rest"),
+            Gap::FreeRide => write!(f, "freeride"),
+        }
+    }
+}
+
+/// How times are rounded to the raster
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    /// never shortens a section
+    Up,
+    /// never lengthens a section
+    Down,
+}
+
+impl Rounding {
+    /// Rounds the offset to a multiple of the step, but at least one step
+    fn round(self, offset: u32, step: u32) -> u32 {
+        let steps = offset as f64 / step as f64;
+        let steps = match self {
+            Rounding::Nearest => steps.round(),
+            Rounding::Up => steps.ceil(),
+            Rounding::Down => steps.floor(),
+        };
+        max(step, (steps as u32).saturating_mul(step))
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "up" => Ok(Rounding::Up),
+            "down" => Ok(Rounding::Down),
+            _ => Err(format!("unknown rounding \"{}\", expected \"nearest\", \"up\" or \"down\"", s)),
+        }
+    }
+}
+
+impl Display for Rounding {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Rounding::Nearest => write!(f, "nearest"),
+            Rounding::Up => write!(f, "up"),
+            Rounding::Down => write!(f, "down"),
+        }
+    }
+}
+
+/// A power bound or tolerance, like "50%" of FTP or "400" watts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerLimit {
+    /// relative to FTP
+    Relative(f64),
+    Watts(u16),
+}
+
+impl PowerLimit {
+    /// The limit relative to the given FTP
+    pub fn relative(self, ftp: u16) -> f64 {
+        match self {
+            PowerLimit::Relative(power) => power,
+            PowerLimit::Watts(watts) => watts as f64 / ftp.max(1) as f64,
+        }
+    }
+}
+
+impl FromStr for PowerLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid power \"{}\", expected percent of FTP like \"50%\" or watts like \"400\"", s);
+        match s.trim().strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f64>().ok().filter(|percent| *percent >= 0.0)
+                .map(|percent| PowerLimit::Relative(percent / 100.0)).ok_or_else(invalid),
+            None => s.trim().trim_end_matches(['W', 'w']).trim().parse().map(PowerLimit::Watts).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Display for PowerLimit {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PowerLimit::Relative(power) => write!(f, "{}%", power * 100.0),
+            PowerLimit::Watts(watts) => write!(f, "{}", watts),
+        }
+    }
+}
+
+/// Converts input data into workout sections
+#[derive(Debug)]
+pub struct Converter {
+    /// absolute FTP in watts
+    pub ftp: u16,
+    /// time shrink factor
+    pub acceleration: f64,
+    /// power scale factor
+    pub scale: f64,
+    /// raise the power of accelerated courses to keep their TSS
+    pub preserve_tss: bool,
+    /// lowest power of sections after scaling, relative to FTP
+    pub min_power: Option<f64>,
+    /// highest power of sections after scaling, relative to FTP
+    pub max_power: Option<f64>,
+    /// duration rasterization in seconds
+    pub raster: u32,
+    /// how starts and durations are rounded to the raster
+    pub rounding: Rounding,
+    /// shortest section in seconds, shorter ones are merged into their neighbor, none keeps them
+    pub min_duration: Option<u32>,
+    /// key intervals keep their exact boundaries, the sections between them end on the raster and absorb the rounding
+    pub anchor: bool,
+    /// turn the start into a warmup block
+    pub warmup: Option<BlockOption>,
+    /// turn the end into a cooldown block
+    pub cooldown: Option<BlockOption>,
+    /// largest deviation from a straight line for ramps relative to FTP, 0 disables ramp detection
+    pub ramp_tolerance: f64,
+    /// collapse repeated on/off pairs into interval blocks
+    pub intervals: bool,
+    /// largest power difference relative to FTP of consecutive steady sections merged into one, none keeps them
+    pub merge_tolerance: Option<f64>,
+    /// largest deviation relative to FTP of steady sections from the average power they are simplified to, none keeps them
+    pub simplify: Option<f64>,
+    /// longest steady section in seconds, longer ones are split into equal parts
+    pub max_section: Option<u32>,
+    /// add texts like "Halfway" to the parts of split sections
+    pub section_progress: bool,
+    /// rows without power start free ride blocks instead of adding text only
+    pub free_ride: bool,
+    /// free ride blocks keep the road flat
+    pub flat_road: bool,
+    /// what fills silences between rows longer than `gap_after`
+    pub gap: Gap,
+    /// seconds without a row after which the gap policy applies
+    pub gap_after: u32,
+    /// power of recovery blocks filling gaps relative to FTP
+    pub gap_power: f64,
+}
+
+impl Converter {
+    pub fn new(ftp: u16) -> Self {
+        Converter {
+            ftp,
+            acceleration: 1.0,
+            scale: 1.0,
+            preserve_tss: false,
+            min_power: None,
+            max_power: None,
+            raster: DEFAULT_DURATION_RASTER,
+            rounding: Rounding::Nearest,
+            min_duration: None,
+            anchor: false,
+            warmup: None,
+            cooldown: None,
+            ramp_tolerance: DEFAULT_RAMP_TOLERANCE,
+            intervals: true,
+            merge_tolerance: None,
+            simplify: None,
+            max_section: None,
+            section_progress: false,
+            free_ride: false,
+            flat_road: false,
+            gap: Gap::Hold,
+            gap_after: DEFAULT_GAP_AFTER,
+            gap_power: DEFAULT_GAP_POWER,
+        }
+    }
+
+    /// Reads steps from the source with the given reader and converts them into sections
+    pub fn convert(&self, input: &dyn InputReader, source: &mut dyn Read) -> Result<Vec<Section>, Error> {
+        let (steps, _) = input.stream(source)?;
+        self.translate_stream(steps)
+    }
+
+    /// Translates steps into rasterized sections of constant power
+    pub fn translate(&self, steps: Vec<Step>) -> Vec<Section> {
+        let Ok((sections, _)) = self.rasterize(steps.into_iter().map(Ok::<_, Infallible>), false);
+        self.detect_blocks(sections)
+    }
+
+    /// Translates steps while they are read, failing on the first step that can't be read
+    pub fn translate_stream(&self, steps: Steps) -> Result<Vec<Section>, Error> {
+        let (sections, _) = self.rasterize(steps, false)?;
+        Ok(self.detect_blocks(sections))
+    }
+
+    /// Tells for every step which section it opened or extended, and how its time was rounded
+    pub fn explain(&self, steps: Vec<Step>) -> Vec<Explanation> {
+        let Ok((sections, mut explanations)) = self.rasterize(steps.into_iter().map(Ok::<_, Infallible>), true);
+        let count = sections.len();
+        let blocks = self.detect_blocks(sections).len();
+        if blocks < count {
+            explanations.push(Explanation { row: None, message: format!("{} sections were combined into {} ramps, intervals and other blocks", count, blocks) });
+        } else if blocks > count {
+            explanations.push(Explanation { row: None, message: format!("{} sections were split into {} blocks of at most {}", count, blocks, format_duration(self.max_section.unwrap_or_default())) });
+        }
+        explanations
+    }
+
+    /// Turns steps into sections starting and ending on the raster, with explanations if asked for
+    fn rasterize<E>(&self, steps: impl Iterator<Item = Result<Step, E>>, explaining: bool) -> Result<(Vec<Section>, Vec<Explanation>), E> {
+        let mut out = Vec::<Section>::new();
+        let mut cur_sec: Option<Section> = None;
+        // raster of the current section, rows may give their own
+        let mut cur_raster = self.raster;
+        let mut cur_key = false;
+        let mut explanations = Vec::new();
+
+        let mut steps = steps.enumerate().peekable();
+        // a row held back until the gap before it is filled
+        let mut held = None;
+        let mut last_time = None;
+        loop {
+            let (row, step) = match held.take() {
+                Some((index, step)) => (Some(index + 1), step),
+                None => match steps.next() {
+                    Some((index, step)) => {
+                        let step = step?;
+                        match self.gap_before(last_time, &step) {
+                            Some(gap) if cur_sec.is_some() => {
+                                held = Some((index, step));
+                                (None, gap)
+                            },
+                            _ => (Some(index + 1), step),
+                        }
+                    },
+                    None => break,
+                },
+            };
+            let gap = row.is_none();
+            last_time = Some(step.time);
+            let last = held.is_none() && steps.peek().is_none();
+            // Use acceleration factor
+            let local_time = (step.time.as_secs_f64() / self.acceleration).round() as u32;
+            let mut explain = |message: String| if explaining {
+                explanations.push(Explanation { row, message: format!("at {}, {}", format_duration(local_time), message) });
+            };
+
+            // Scale power
+            let scaled = step.watts.map(|watts| ((watts as f64 * self.power_factor() / self.ftp as f64 * 100.0).round() as u32) as f64 / 100.0);
+            let power = scaled.map(|power| self.limit(power));
+
+            // in free ride mode, rows without power start a free ride, except the last one marking the end
+            let block = match power {
+                _ if gap => Some(match self.gap {
+                    Gap::FreeRide => (0.0, Kind::FreeRide { flat_road: self.flat_road }),
+                    _ => (self.limit(self.gap_power), Kind::SteadyState),
+                }),
+                Some(power) => Some((power, Kind::SteadyState)),
+                None if self.free_ride && !last => Some((0.0, Kind::FreeRide { flat_road: self.flat_road })),
+                None => None,
+            };
+
+            let mut offset = 0;
+            let mut lasts = None;
+            if let Some(sec) = &mut cur_sec {
+                if local_time > sec.start {
+                    offset = local_time - sec.start;
+                    sec.duration = match self.anchor {
+                        // key intervals start and end exactly
+                        true if cur_key || (step.key && block.is_some()) => offset,
+                        // the sections between them end on the raster of the whole workout
+                        true => self.rounding.round(local_time, cur_raster).checked_sub(sec.start).filter(|duration| *duration > 0).unwrap_or(offset),
+                        false => self.rounding.round(offset, cur_raster),
+                    };
+                }
+                lasts = Some(match sec.duration == offset {
+                    true => format!("section {} lasts {} until this row", out.len() + 1, format_duration(sec.duration)),
+                    false => format!("section {} lasts {}, rounded from {} until this row", out.len() + 1, format_duration(sec.duration), format_duration(offset)),
+                });
+            }
+
+            match (&mut cur_sec, block, &step.text) {
+                (Some(sec), None, text) if text.is_some() || !step.hints.is_empty() => {
+                    // add text to existing node
+                    let rounded_offset = round(offset, 5);
+                    let mut message = format!("text added to section {} at +{}", out.len() + 1, format_duration(rounded_offset));
+                    if rounded_offset != offset {
+                        message += &format!(", rounded from +{} to 5s", format_duration(offset));
+                    }
+                    if let Some(text) = text {
+                        sec.text.push(Hint {offset: rounded_offset, text: text.clone(), duration: step.text_duration});
+                        if sec.duration < rounded_offset {
+                            sec.duration += cur_raster;
+                            message += &format!(", which lengthens the section by one raster to {} to show it", format_duration(sec.duration));
+                        }
+                    }
+                    sec.text.extend(step.hints.iter().map(|hint| Hint { offset: rounded_offset + hint.offset, ..hint.clone() }));
+                    explain(message);
+                },
+                (section, Some((power, kind)), _) => {
+                    let raster = step.raster.unwrap_or(self.raster);
+                    let key = self.anchor && step.key;
+                    let mut new_start_time = match key {
+                        true => local_time,
+                        false => self.rounding.round(local_time, raster),
+                    };
+                    let number = out.len() + if section.is_some() { 2 } else { 1 };
+                    let opened = match kind {
+                        Kind::FreeRide { .. } => format!("free ride section {}", number),
+                        _ => match scaled.filter(|scaled| *scaled != power) {
+                            Some(scaled) => format!("section {} at {:.0}% (limited from {:.0}%)", number, power * 100.0, scaled * 100.0),
+                            None => format!("section {} at {:.0}%", number, power * 100.0),
+                        },
+                    };
+                    let mut message = match key {
+                        true => format!("key {} starts at {} exactly", opened, format_duration(new_start_time)),
+                        false => format!("{} starts at {}, rounded to the {}s raster", opened, format_duration(new_start_time), raster),
+                    };
+                    // close existing node
+                    if let Some(sec) = &section {
+                        new_start_time = sec.start + sec.duration;
+                        message = format!("{}, and ends, {} starts at its end {}", lasts.take().unwrap_or_default(), opened, format_duration(new_start_time));
+                        out.push(cur_sec.unwrap());
+                    }
+                    if gap {
+                        message = format!("no row for more than {}, {}", format_duration(self.gap_after), message);
+                    }
+                    explain(message);
+
+                    // start new node
+                    let mut sec = Section{ start: new_start_time, duration: raster, power, kind, cadence: step.cadence, text: vec!()};
+                    if let Some(text) = &step.text {
+                        sec.text.push(Hint{offset: 0, text: text.clone(), duration: step.text_duration})
+                    }
+                    sec.text.extend(step.hints.iter().cloned());
+                    cur_sec = Some(sec);
+                    cur_raster = raster;
+                    cur_key = key;
+                },
+                (None, None, _) => explain("ignored, as no section has started yet".to_string()),
+                (Some(_), None, _) => explain(lasts.unwrap_or_default()),
+            }
+        }
+
+        if let Some(sec) = cur_sec {
+            out.push(sec);
+        }
+        let merged = self.merge_short(&mut out);
+        if explaining {
+            explanations.extend(merged.into_iter().map(|message| Explanation { row: None, message }));
+        }
+        Ok((out, explanations))
+    }
+
+    /// Merges sections shorter than the minimum duration into the one before, or the first one into the one after
+    fn merge_short(&self, sections: &mut Vec<Section>) -> Vec<String> {
+        let mut messages = Vec::new();
+        let Some(min_duration) = self.min_duration else {
+            return messages;
+        };
+        let mut index = 0;
+        while index < sections.len() && sections.len() > 1 {
+            if sections[index].duration >= min_duration {
+                index += 1;
+                continue;
+            }
+            let short = sections.remove(index);
+            let neighbor = match index {
+                0 => {
+                    let next = &mut sections[0];
+                    for hint in &mut next.text {
+                        hint.offset += short.duration;
+                    }
+                    next.text.splice(0..0, short.text);
+                    next.start = short.start;
+                    next.duration += short.duration;
+                    "after"
+                },
+                _ => {
+                    let previous = &mut sections[index - 1];
+                    previous.text.extend(short.text.into_iter().map(|hint| Hint { offset: hint.offset + previous.duration, ..hint }));
+                    previous.duration += short.duration;
+                    "before"
+                },
+            };
+            messages.push(format!("the section at {} lasting {} is shorter than {} and merged into the one {}",
+                format_duration(short.start), format_duration(short.duration), format_duration(min_duration), neighbor));
+        }
+        messages
+    }
+
+    /// Factor applied to the power of rows: the scale, and with `preserve_tss` the square root
+    /// of the acceleration, as TSS grows with the duration and the square of the intensity
+    pub fn power_factor(&self) -> f64 {
+        match self.preserve_tss {
+            true => self.scale * self.acceleration.sqrt(),
+            false => self.scale,
+        }
+    }
+
+    /// Keeps the power within the minimum and maximum power
+    fn limit(&self, power: f64) -> f64 {
+        let power = self.min_power.map_or(power, |min| power.max(min));
+        self.max_power.map_or(power, |max| power.min(max))
+    }
+
+    /// The step starting to fill the silence before the step, if it is too long and the policy fills it
+    fn gap_before(&self, last_time: Option<Duration>, step: &Step) -> Option<Step> {
+        let start = last_time? + Duration::from_secs(self.gap_after as u64);
+        (self.gap != Gap::Hold && step.time > start)
+            .then(|| Step { time: start, ..Step::default() })
+    }
+
+    /// Detects warmups, cooldowns, ramps and intervals in rasterized sections
+    fn detect_blocks(&self, mut out: Vec<Section>) -> Vec<Section> {
+        if let Some(option) = self.warmup {
+            warmup(&mut out, option, self.ramp_tolerance);
+        }
+        if let Some(option) = self.cooldown {
+            cooldown(&mut out, option, self.ramp_tolerance);
+        }
+        if self.ramp_tolerance > 0.0 {
+            ramps(&mut out, self.ramp_tolerance);
+        }
+        if self.intervals {
+            intervals(&mut out);
+        }
+        if let Some(tolerance) = self.merge_tolerance {
+            merge_steady(&mut out, tolerance);
+        }
+        if let Some(tolerance) = self.simplify {
+            simplify(&mut out, tolerance);
+        }
+        if let Some(max) = self.max_section {
+            split_long(&mut out, max, self.raster, self.section_progress);
+        }
+
+        out
+    }
+}
+
+fn round(offset: u32, step: u32) -> u32 {
+    Rounding::Nearest.round(offset, step)
+}
+
+#[test]
+fn test_round() {
+    assert_eq!(30, round(0, 30));
+    assert_eq!(30, round(10,30));
+    assert_eq!(10, round(12,5));
+    assert_eq!(30, round(20, 30));
+    assert_eq!(30, round(30, 30));
+    assert_eq!(30, round(40, 30));
+    assert_eq!(60, round(50, 30));
+    assert_eq!(60, Rounding::Up.round(40, 30));
+    assert_eq!(30, Rounding::Down.round(50, 30));
+    assert_eq!(30, Rounding::Down.round(10, 30));
+}
+
+#[test]
+fn test_free_ride() {
+    let step = |minutes: u64, watts| Step { time: Duration::from_secs(minutes * 60), watts, ..Step::default() };
+    let steps = vec![step(0, Some(200)), step(2, None), step(5, Some(250)), step(6, None)];
+
+    let converter = Converter { free_ride: true, flat_road: true, ..Converter::new(250) };
+    let sections = converter.translate(steps);
+    assert_eq!(3, sections.len());
+    assert_eq!((Kind::FreeRide { flat_road: true }, 180), (sections[1].kind, sections[1].duration));
+    assert_eq!(1.0, sections[2].power);
+}
+
+#[test]
+fn test_explain() {
+    let step = |seconds, watts, text: Option<&str>| Step { time: Duration::from_secs(seconds), watts, text: text.map(str::to_string), ..Step::default() };
+    let steps = vec![step(0, Some(100), None), step(100, Some(200), None), step(190, None, Some("Go")), step(250, None, None)];
+
+    let converter = Converter { raster: 30, ..Converter::new(200) };
+    let explanations: Vec<String> = converter.explain(steps).iter().map(|explanation| explanation.to_string()).collect();
+    assert_eq!(vec![
+        "row 1: at 0s, section 1 at 50% starts at 30s, rounded to the 30s raster",
+        "row 2: at 1m40s, section 1 lasts 1m, rounded from 1m10s until this row, and ends, section 2 at 100% starts at its end 1m30s",
+        "row 3: at 3m10s, text added to section 2 at +1m40s, which lengthens the section by one raster to 2m to show it",
+        "row 4: at 4m10s, section 2 lasts 2m30s, rounded from 2m40s until this row",
+    ], explanations);
+}
+
+#[test]
+fn test_gap() {
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = || vec![step(0, Some(200)), step(30, Some(250)), step(600, Some(200)), step(660, None)];
+
+    let converter = Converter { intervals: false, ramp_tolerance: 0.0, ..Converter::new(250) };
+    assert_eq!(3, converter.translate(steps()).len());
+
+    let rest = Converter { gap: Gap::Rest, ..converter };
+    let sections = rest.translate(steps());
+    assert_eq!(4, sections.len());
+    assert_eq!((90, 0.5, 510), (sections[2].start, sections[2].power, sections[2].duration));
+
+    let free_ride = Converter { gap: Gap::FreeRide, ..rest };
+    assert_eq!(Kind::FreeRide { flat_road: false }, free_ride.translate(steps())[2].kind);
+}
+
+#[test]
+fn test_min_duration() {
+    let step = |seconds, watts, text: Option<&str>| Step { time: Duration::from_secs(seconds), watts, text: text.map(str::to_string), ..Step::default() };
+    let steps = || vec![step(0, Some(100), None), step(10, Some(200), None), step(120, Some(250), Some("Go")), step(125, Some(200), None), step(240, None, None)];
+
+    let converter = Converter { raster: 5, ramp_tolerance: 0.0, intervals: false, ..Converter::new(200) };
+    assert_eq!(4, converter.translate(steps()).len());
+
+    let converter = Converter { min_duration: Some(30), ..converter };
+    let sections = converter.translate(steps());
+    assert_eq!(vec![(5, 120, 1.0), (125, 115, 1.0)], sections.iter().map(|sec| (sec.start, sec.duration, sec.power)).collect::<Vec<_>>());
+    assert_eq!(115, sections[0].text[0].offset);
+}
+
+#[test]
+fn test_step_raster() {
+    let step = |seconds, watts, raster| Step { time: Duration::from_secs(seconds), watts, raster, ..Step::default() };
+    let steps = vec![step(0, Some(150), None), step(600, Some(400), Some(5)), step(615, Some(150), None), step(900, None, None)];
+
+    let converter = Converter { ramp_tolerance: 0.0, intervals: false, ..Converter::new(250) };
+    let durations: Vec<u32> = converter.translate(steps).iter().map(|sec| sec.duration).collect();
+    assert_eq!(vec![570, 15, 300], durations);
+}
+
+#[test]
+fn test_anchor() {
+    let step = |seconds, watts, key| Step { time: Duration::from_secs(seconds), watts, key, ..Step::default() };
+    let steps = || vec![step(0, Some(150), false), step(607, Some(400), true), step(647, Some(150), false), step(1210, None, false)];
+    let sections = |converter: &Converter| converter.translate(steps()).iter().map(|sec| (sec.start, sec.duration)).collect::<Vec<_>>();
+
+    let converter = Converter { ramp_tolerance: 0.0, intervals: false, ..Converter::new(250) };
+    assert_eq!(vec![(30, 570), (600, 60), (660, 540)], sections(&converter));
+
+    let converter = Converter { anchor: true, ..converter };
+    assert_eq!(vec![(30, 577), (607, 40), (647, 553)], sections(&converter));
+}
+
+#[test]
+fn test_power_limit() {
+    assert_eq!(Ok(PowerLimit::Relative(0.5)), "50%".parse());
+    assert_eq!(Ok(PowerLimit::Watts(400)), "400W".parse());
+    assert!("fast".parse::<PowerLimit>().is_err());
+    assert_eq!(1.6, PowerLimit::Watts(400).relative(250));
+
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts: Some(watts), ..Step::default() };
+    let converter = Converter { min_power: Some(0.5), max_power: Some(1.5), ramp_tolerance: 0.0, ..Converter::new(250) };
+    let sections = converter.translate(vec![step(0, 50), step(60, 1000), step(120, 200)]);
+    assert_eq!(vec![0.5, 1.5, 0.8], sections.iter().map(|sec| sec.power).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_preserve_tss() {
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts: Some(watts), ..Step::default() };
+    let converter = Converter { acceleration: 2.0, preserve_tss: true, ramp_tolerance: 0.0, ..Converter::new(250) };
+    assert_eq!(2f64.sqrt(), converter.power_factor());
+    let sections = converter.translate(vec![step(0, 150), step(240, 200), step(480, 200)]);
+    assert_eq!(vec![(30, 0.85), (120, 1.13), (240, 1.13)], sections.iter().map(|sec| (sec.start, sec.power)).collect::<Vec<_>>());
+}
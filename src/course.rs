@@ -0,0 +1,144 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::pace::RunPace;
+
+/// A complete workout, ready to be written out
+#[derive(Debug, Default, Serialize)]
+pub struct Course {
+    pub name: String,
+    pub description: Option<String>,
+    pub author: String,
+    pub sport_type: Sport,
+    /// tags to filter workouts by in Zwift
+    pub tags: Vec<String>,
+    /// group in Zwift's workout picker
+    pub category: Option<String>,
+    pub subcategory: Option<String>,
+    /// reference pace of run workouts, power is the speed relative to it then
+    pub pace: Option<RunPace>,
+    /// whether section durations are seconds or meters
+    pub duration_type: DurationType,
+    /// absolute FTP in watts the course was computed for
+    pub ftp: u16,
+    pub sections: Vec<Section>,
+}
+
+/// A block of the workout
+#[derive(Debug, Serialize)]
+pub struct Section {
+    /// start time in seconds
+    pub start: u32,
+    /// duration in seconds, or meters for distance based courses
+    pub duration: u32,
+    /// power relative to FTP (1.0 = 100%), at the start of ramps
+    pub power: f64,
+    pub kind: Kind,
+    /// target cadence in rpm
+    pub cadence: Option<u16>,
+    pub text: Vec<Hint>,
+}
+
+impl Section {
+    /// Power at the end of the section, relative to FTP
+    pub fn end_power(&self) -> f64 {
+        match self.kind {
+            Kind::SteadyState => self.power,
+            Kind::Warmup { end_power } | Kind::Cooldown { end_power } | Kind::Ramp { end_power } => end_power,
+            Kind::IntervalsT { off_power, .. } => off_power,
+            Kind::FreeRide { .. } => self.power,
+        }
+    }
+
+    /// Pieces of constant or linearly changing power the section consists of,
+    /// as duration in seconds with start and end power
+    pub fn parts(&self) -> Vec<(u32, f64, f64)> {
+        match self.kind {
+            Kind::IntervalsT { repeat, on_duration, off_duration, off_power, .. } => (0..repeat)
+                .flat_map(|_| [(on_duration, self.power, self.power), (off_duration, off_power, off_power)])
+                .collect(),
+            _ => vec![(self.duration, self.power, self.end_power())],
+        }
+    }
+}
+
+/// The sport a workout is made for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sport {
+    #[default]
+    Bike,
+    /// targets are paces, free rides aren't available
+    Run,
+}
+
+impl FromStr for Sport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            // "ride" was the default before sports were checked
+            "bike" | "ride" => Ok(Sport::Bike),
+            "run" => Ok(Sport::Run),
+            _ => Err(format!("unsupported sport \"{}\", expected \"bike\" or \"run\"", s)),
+        }
+    }
+}
+
+impl Display for Sport {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Sport::Bike => write!(f, "bike"),
+            Sport::Run => write!(f, "run"),
+        }
+    }
+}
+
+/// What section durations measure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationType {
+    /// seconds
+    #[default]
+    Time,
+    /// meters, e.g. for run workouts like 6x800m
+    Distance,
+}
+
+/// How the power develops over a section
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Kind {
+    /// constant power
+    #[default]
+    SteadyState,
+    /// linear change of power at the start of the workout
+    Warmup { end_power: f64 },
+    /// linear change of power at the end of the workout
+    Cooldown { end_power: f64 },
+    /// linear change of power within the workout
+    Ramp { end_power: f64 },
+    /// repeated pairs of an on part with `power` and `cadence` and an off part
+    IntervalsT {
+        repeat: u32,
+        on_duration: u32,
+        off_duration: u32,
+        off_power: f64,
+        /// target cadence of the off parts in rpm
+        cadence_resting: Option<u16>,
+    },
+    /// no power target, `power` is 0
+    FreeRide { flat_road: bool },
+}
+
+/// A text message shown during a section
+#[derive(Debug, Clone, Serialize)]
+pub struct Hint {
+    /// offset in seconds relative to the section start
+    pub offset: u32,
+    pub text: String,
+    /// how long the text is shown in seconds [default: up to Zwift]
+    pub duration: Option<u32>,
+}
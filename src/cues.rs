@@ -0,0 +1,255 @@
+//! Text events added to a whole course, like countdowns
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::{parse_duration, Course, DurationType, Error, Hint, Step};
+
+/// How long countdown texts are shown at most, in seconds
+const COUNTDOWN_DISPLAY: u32 = 5;
+/// Least gap between a reminder and other texts, in seconds
+const REMINDER_GAP: u32 = 10;
+
+/// A text repeated at a fixed interval, like "Drink!@15m"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub text: String,
+    /// in seconds, or meters for distance based courses
+    pub interval: u32,
+}
+
+impl FromStr for Reminder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid reminder \"{}\", expected e.g. \"Drink!@15m\"", s);
+        let (text, interval) = s.rsplit_once('@').ok_or_else(invalid)?;
+        let interval = parse_duration(interval).map_err(|_| invalid())?;
+        if interval == 0 || text.trim().is_empty() {
+            return Err(invalid());
+        }
+        Ok(Reminder { text: text.trim().to_string(), interval })
+    }
+}
+
+/// Adds texts like "30 seconds to go" the given times before every effort, which is a section
+/// with power above the threshold relative to FTP following an easier one.
+/// Times longer than the preceding section are left out.
+pub fn countdown(course: &mut Course, marks: &[u32], threshold: f64) {
+    let hard: Vec<bool> = course.sections.iter().map(|sec| sec.power.max(sec.end_power()) > threshold).collect();
+
+    for index in 1..course.sections.len() {
+        if !hard[index] || hard[index - 1] {
+            continue;
+        }
+
+        let sec = &mut course.sections[index - 1];
+        for &mark in marks {
+            if mark == 0 || mark > sec.duration {
+                continue;
+            }
+            let text = match course.duration_type {
+                DurationType::Time => match mark {
+                    60 => "1 minute to go".to_string(),
+                    _ if mark % 60 == 0 => format!("{} minutes to go", mark / 60),
+                    _ => format!("{} seconds to go", mark),
+                },
+                DurationType::Distance => format!("{} m to go", mark),
+            };
+            sec.text.push(Hint { offset: sec.duration - mark, text, duration: Some(mark.min(COUNTDOWN_DISPLAY)) });
+        }
+        sec.text.sort_by_key(|hint| hint.offset);
+    }
+}
+
+/// Translations of texts into one language
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    entries: HashMap<String, String>,
+}
+
+impl Dictionary {
+    /// Reads a CSV table with the original texts in the first column and one column per language,
+    /// named like "de" in the header
+    pub fn read(source: &mut dyn Read, lang: &str) -> Result<Self, Error> {
+        let mut reader = csv::Reader::from_reader(source);
+        let column = reader.headers()?.iter()
+            .skip(1)
+            .position(|header| header.trim().eq_ignore_ascii_case(lang))
+            .map(|position| position + 1)
+            .ok_or_else(|| Error::Parse(format!("dictionary has no column \"{}\"", lang)))?;
+
+        let mut entries = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            match (record.get(0).map(str::trim), record.get(column).map(str::trim)) {
+                (Some(text), Some(translation)) if !text.is_empty() && !translation.is_empty() => {
+                    entries.insert(text.to_string(), translation.to_string());
+                },
+                _ => {},
+            }
+        }
+        Ok(Dictionary { entries })
+    }
+
+    /// Replaces all texts of the course with their translation, returning those without one
+    pub fn translate(&self, course: &mut Course) -> Vec<String> {
+        let mut missing = Vec::new();
+        for hint in course.sections.iter_mut().flat_map(|sec| sec.text.iter_mut()) {
+            match self.entries.get(hint.text.trim()) {
+                Some(translation) => hint.text = translation.clone(),
+                None if !missing.contains(&hint.text) => missing.push(hint.text.clone()),
+                None => {},
+            }
+        }
+        missing
+    }
+}
+
+/// Adds the texts of separately maintained steps to the sections at their time, shrunk by the
+/// acceleration factor like the course itself. Power of the steps is ignored.
+pub fn add_cues(course: &mut Course, cues: &[Step], acceleration: f64) {
+    let mut hints: Vec<(f64, Hint)> = Vec::new();
+    for step in cues {
+        let time = step.time.as_secs_f64();
+        if let Some(text) = &step.text {
+            hints.push((time, Hint { offset: 0, text: text.clone(), duration: step.text_duration }));
+        }
+        hints.extend(step.hints.iter().map(|hint| (time + hint.offset as f64, Hint { offset: 0, ..hint.clone() })));
+    }
+
+    for (time, mut hint) in hints {
+        // placed like texts of the course itself, relative to the start of the last section before
+        let time = (time / acceleration).round() as u32;
+        let index = course.sections.iter().rposition(|sec| sec.start <= time).unwrap_or(0);
+        if let Some(sec) = course.sections.get_mut(index) {
+            hint.offset = time.saturating_sub(sec.start);
+            sec.text.push(hint);
+            sec.text.sort_by_key(|hint| hint.offset);
+        }
+    }
+}
+
+/// Adds the reminder text at every multiple of its interval, except at the very end.
+/// Reminders too close to other texts are moved behind them, as long as the section lasts.
+pub fn remind(course: &mut Course, reminder: &Reminder) {
+    let total: u32 = course.sections.iter().map(|sec| sec.duration).sum();
+
+    let mut start = 0;
+    let mut at = reminder.interval;
+    for sec in &mut course.sections {
+        while at < start + sec.duration && at < total {
+            let mut offset = at - start;
+            while let Some(hint) = sec.text.iter().find(|hint| hint.offset <= offset && offset < hint.offset + REMINDER_GAP) {
+                offset = hint.offset + REMINDER_GAP;
+            }
+            if offset >= sec.duration {
+                offset = at - start;
+            }
+
+            sec.text.push(Hint { offset, text: reminder.text.clone(), duration: None });
+            at += reminder.interval;
+        }
+        sec.text.sort_by_key(|hint| hint.offset);
+        start += sec.duration;
+    }
+}
+
+#[test]
+fn test_countdown() {
+    use crate::{Kind, Section};
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![section(0, 120, 0.6), section(120, 60, 1.2), section(180, 60, 1.1), section(240, 20, 0.5), section(260, 60, 1.2)],
+        ..Course::default()
+    };
+
+    countdown(&mut course, &[60, 10, 30], 1.0);
+    let texts = |index: usize| course.sections[index].text.iter().map(|hint| (hint.offset, hint.text.as_str())).collect::<Vec<_>>();
+    assert_eq!(vec![(60, "1 minute to go"), (90, "30 seconds to go"), (110, "10 seconds to go")], texts(0));
+    assert!(texts(1).is_empty());
+    assert_eq!(vec![(10, "10 seconds to go")], texts(3));
+}
+
+#[test]
+fn test_remind() {
+    use crate::{Kind, Section};
+
+    let section = |start, duration| Section { start, duration, power: 0.6, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![section(0, 1000), section(1000, 800)],
+        ..Course::default()
+    };
+    course.sections[1].text.push(Hint { offset: 795, text: "Done".to_string(), duration: None });
+    course.sections[0].text.push(Hint { offset: 595, text: "Go".to_string(), duration: None });
+
+    let reminder: Reminder = "Drink!@10m".parse().unwrap();
+    assert_eq!(600, reminder.interval);
+    remind(&mut course, &reminder);
+
+    let texts = |index: usize| course.sections[index].text.iter().map(|hint| (hint.offset, hint.text.as_str())).collect::<Vec<_>>();
+    assert_eq!(vec![(595, "Go"), (605, "Drink!")], texts(0));
+    assert_eq!(vec![(200, "Drink!"), (795, "Done")], texts(1));
+    assert!("Drink!".parse::<Reminder>().is_err());
+}
+
+#[test]
+fn test_add_cues() {
+    use std::time::Duration;
+
+    use crate::{Kind, Section};
+
+    let section = |start, duration| Section { start, duration, power: 0.6, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![section(0, 120), section(120, 60)],
+        ..Course::default()
+    };
+    course.sections[0].text.push(Hint { offset: 60, text: "Existing".to_string(), duration: None });
+
+    let cue = |seconds, text: &str| Step {
+        time: Duration::from_secs(seconds),
+        watts: Some(100),
+        text: Some(text.to_string()),
+        hints: vec![Hint { offset: 20, text: format!("{} again", text), duration: None }],
+        ..Step::default()
+    };
+    add_cues(&mut course, &[cue(60, "Early"), cue(300, "Late")], 2.0);
+
+    let texts = |index: usize| course.sections[index].text.iter().map(|hint| (hint.offset, hint.text.as_str())).collect::<Vec<_>>();
+    assert_eq!(vec![(30, "Early"), (40, "Early again"), (60, "Existing")], texts(0));
+    assert_eq!(vec![(30, "Late"), (40, "Late again")], texts(1));
+}
+
+#[test]
+fn test_dictionary() {
+    use crate::{Kind, Section};
+
+    let csv = "en,fr,de\nGo!,Allez !,Los!\n30 seconds to go,,Noch 30 Sekunden\n";
+    let dictionary = Dictionary::read(&mut csv.as_bytes(), "DE").unwrap();
+    assert!(Dictionary::read(&mut csv.as_bytes(), "it").is_err());
+    assert!(Dictionary::read(&mut csv.as_bytes(), "en").is_err());
+
+    let hint = |text: &str| Hint { offset: 0, text: text.to_string(), duration: None };
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![Section { start: 0, duration: 60, power: 0.6, kind: Kind::SteadyState, cadence: None, text: vec![hint("Go! "), hint("30 seconds to go"), hint("Unknown"), hint("Unknown")] }],
+        ..Course::default()
+    };
+
+    assert_eq!(vec!["Unknown".to_string()], dictionary.translate(&mut course));
+    let texts: Vec<&str> = course.sections[0].text.iter().map(|hint| hint.text.as_str()).collect();
+    assert_eq!(vec!["Los!", "Noch 30 Sekunden", "Unknown", "Unknown"], texts);
+}
@@ -0,0 +1,141 @@
+//! Differences between two courses, e.g. after tweaking the raster
+
+use std::fmt::{Display, Formatter};
+
+use crate::duration::format_duration;
+use crate::stats::Stats;
+use crate::{Course, DurationType, Kind, Section, ZoneModel};
+
+/// Power differences below this are rounding noise, relative to FTP
+const POWER_TOLERANCE: f64 = 0.005;
+
+/// A difference between two courses
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    /// index of the section, if the difference is about a single one
+    pub section: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.section {
+            Some(index) => write!(f, "section {}: {}", index + 1, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Compares the courses section by section, followed by the total duration and TSS
+pub fn compare(a: &Course, b: &Course) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let mut differ = |section: Option<usize>, message: String| differences.push(Difference { section, message });
+
+    for index in 0..a.sections.len().max(b.sections.len()) {
+        let (old, new) = match (a.sections.get(index), b.sections.get(index)) {
+            (Some(old), Some(new)) => (old, new),
+            (Some(old), None) => {
+                differ(Some(index), format!("removed {}", describe(a, old)));
+                continue;
+            },
+            (None, Some(new)) => {
+                differ(Some(index), format!("added {}", describe(b, new)));
+                continue;
+            },
+            (None, None) => unreachable!(),
+        };
+
+        if kind_name(&old.kind) != kind_name(&new.kind) {
+            differ(Some(index), format!("{} -> {}", kind_name(&old.kind), kind_name(&new.kind)));
+        }
+        if old.duration != new.duration {
+            differ(Some(index), format!("duration {} -> {}", length(a, old.duration), length(b, new.duration)));
+        }
+        if (old.power - new.power).abs() >= POWER_TOLERANCE {
+            differ(Some(index), format!("power {:.0}% -> {:.0}% ({:+.0}%)", old.power * 100.0, new.power * 100.0, (new.power - old.power) * 100.0));
+        }
+        if (old.end_power() - new.end_power()).abs() >= POWER_TOLERANCE && (old.end_power() != old.power || new.end_power() != new.power) {
+            differ(Some(index), format!("end power {:.0}% -> {:.0}%", old.end_power() * 100.0, new.end_power() * 100.0));
+        }
+        let (old_text, new_text) = (texts(old), texts(new));
+        if old_text != new_text {
+            differ(Some(index), format!("text \"{}\" -> \"{}\"", old_text, new_text));
+        }
+    }
+
+    let (old_total, new_total) = (total(a), total(b));
+    let delta = match new_total.checked_sub(old_total) {
+        Some(more) => format!("+{}", length(b, more)),
+        None => format!("-{}", length(b, old_total - new_total)),
+    };
+    differ(None, format!("duration {} -> {} ({})", length(a, old_total), length(b, new_total), delta));
+
+    // TSS is based on time
+    if a.duration_type == DurationType::Time && b.duration_type == DurationType::Time {
+        let zones = ZoneModel::default();
+        let (old_tss, new_tss) = (Stats::of(a, &zones).tss, Stats::of(b, &zones).tss);
+        differ(None, format!("TSS {:.0} -> {:.0} ({:+.0})", old_tss, new_tss, new_tss - old_tss));
+    }
+
+    differences
+}
+
+fn total(course: &Course) -> u32 {
+    course.sections.iter().map(|sec| sec.duration).sum()
+}
+
+fn length(course: &Course, duration: u32) -> String {
+    match course.duration_type {
+        DurationType::Time => format_duration(duration),
+        DurationType::Distance => format!("{} m", duration),
+    }
+}
+
+fn kind_name(kind: &Kind) -> &'static str {
+    match kind {
+        Kind::SteadyState => "steady state",
+        Kind::Warmup { .. } => "warmup",
+        Kind::Cooldown { .. } => "cooldown",
+        Kind::Ramp { .. } => "ramp",
+        Kind::IntervalsT { .. } => "intervals",
+        Kind::FreeRide { .. } => "free ride",
+    }
+}
+
+fn texts(sec: &Section) -> String {
+    sec.text.iter().map(|hint| hint.text.as_str()).collect::<Vec<_>>().join(" | ")
+}
+
+fn describe(course: &Course, sec: &Section) -> String {
+    format!("{} of {} at {:.0}%", kind_name(&sec.kind), length(course, sec.duration), sec.power * 100.0)
+}
+
+#[test]
+fn test_compare() {
+    use crate::Hint;
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let course = |sections| Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections,
+        ..Course::default()
+    };
+
+    let a = course(vec![section(0, 1800, 0.5), section(1800, 1800, 1.0)]);
+    let mut b = course(vec![section(0, 1800, 0.5), section(1800, 1770, 1.05), section(3570, 600, 0.5)]);
+    b.sections[1].text.push(Hint { offset: 0, text: "Go".to_string(), duration: None });
+
+    let report: Vec<String> = compare(&a, &b).iter().map(|difference| difference.to_string()).collect();
+    assert_eq!(vec![
+        "section 2: duration 30m -> 29m30s",
+        "section 2: power 100% -> 105% (+5%)",
+        "section 2: text \"\" -> \"Go\"",
+        "section 3: added steady state of 10m at 50%",
+        "duration 1h -> 1h9m30s (+9m30s)",
+        "TSS 73 -> 86 (+13)",
+    ], report);
+
+    assert_eq!(2, compare(&a, &a).len());
+}
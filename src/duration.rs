@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// Parses a duration into seconds.
+/// Accepts plain seconds ("90"), units ("90s", "5m", "1h30m") and clock notation ("1:30", "01:05:00").
+pub fn parse_duration(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let invalid = || format!("invalid duration \"{}\"", text);
+    let too_long = || format!("duration \"{}\" is too long", text);
+
+    if text.is_empty() {
+        return Err(invalid());
+    }
+
+    if text.contains(':') {
+        let mut seconds: u32 = 0;
+        for part in text.split(':') {
+            let value: u32 = part.parse().map_err(|_| invalid())?;
+            seconds = seconds.checked_mul(60).and_then(|seconds| seconds.checked_add(value)).ok_or_else(too_long)?;
+        }
+        return Ok(seconds);
+    }
+
+    if let Ok(seconds) = text.parse() {
+        return Ok(seconds);
+    }
+
+    let mut seconds: u32 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' => {
+                let value: u32 = number.parse().map_err(|_| invalid())?;
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                seconds = value.checked_mul(unit).and_then(|value| seconds.checked_add(value)).ok_or_else(too_long)?;
+                number.clear();
+            },
+            _ => return Err(invalid()),
+        }
+    }
+
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(seconds)
+}
+
+/// Formats seconds like "1h30m", "5m" or "45s", readable by [`parse_duration`]
+pub fn format_duration(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    let mut text = String::new();
+    if hours > 0 {
+        text += &format!("{}h", hours);
+    }
+    if minutes > 0 {
+        text += &format!("{}m", minutes);
+    }
+    if seconds > 0 || text.is_empty() {
+        text += &format!("{}s", seconds);
+    }
+    text
+}
+
+/// Formats a time since the start like "01:05:00" or "01:05:00.25", hours may exceed a day
+pub(crate) fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let mut text = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    let millis = time.subsec_millis();
+    if millis > 0 {
+        text += format!(".{:03}", millis).trim_end_matches('0');
+    }
+    text
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(Ok(90), parse_duration("90"));
+    assert_eq!(Ok(90), parse_duration("90s"));
+    assert_eq!(Ok(300), parse_duration("5m"));
+    assert_eq!(Ok(5400), parse_duration("1h30m"));
+    assert_eq!(Ok(90), parse_duration("1:30"));
+    assert_eq!(Ok(3900), parse_duration("01:05:00"));
+    assert!(parse_duration("5x").is_err());
+    assert!(parse_duration("m").is_err());
+    assert!(parse_duration("").is_err());
+    assert_eq!(Err("duration \"9999999:00:00\" is too long".to_string()), parse_duration("9999999:00:00"));
+    assert!(parse_duration("9999999h").is_err());
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!("1h30m", format_duration(5400));
+    assert_eq!("1m30s", format_duration(90));
+    assert_eq!("0s", format_duration(0));
+    assert_eq!(Ok(3725), parse_duration(&format_duration(3725)));
+}
+
+#[test]
+fn test_format_time() {
+    assert_eq!("00:01:30", format_time(Duration::from_secs(90)));
+    assert_eq!("26:00:05", format_time(Duration::from_secs(93605)));
+    assert_eq!("00:01:02.25", format_time(Duration::from_millis(62250)));
+}
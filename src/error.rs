@@ -0,0 +1,96 @@
+//! Errors of reading, converting and writing workouts, with an exit code per kind
+
+use crate::input::InputError;
+
+/// Input data could not be parsed
+pub const EXIT_PARSE: u8 = 65;
+/// Options or values are invalid or don't fit together
+pub const EXIT_VALIDATION: u8 = 64;
+/// An input file does not exist
+pub const EXIT_NOT_FOUND: u8 = 66;
+/// Reading or writing failed otherwise
+pub const EXIT_IO: u8 = 74;
+
+/// Everything that can go wrong, distinguishing bad input from failing files
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// a line of the input is malformed
+    #[error("{0}")]
+    Input(#[from] InputError),
+    /// the input is malformed as a whole, like broken XML
+    #[error("Error: {0}")]
+    Parse(String),
+    /// options or values are invalid or don't fit together
+    #[error("Error: {0}")]
+    Validation(String),
+    /// reading or writing a file failed
+    #[error("Error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Process exit code following the BSD sysexits conventions
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Input(_) | Error::Parse(_) => EXIT_PARSE,
+            Error::Validation(_) => EXIT_VALIDATION,
+            Error::Io(err) if err.kind() == std::io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+            Error::Io(_) => EXIT_IO,
+        }
+    }
+
+    /// The message without the "Error: " prefix and line number
+    pub fn message(&self) -> String {
+        match self {
+            Error::Input(err) => err.message.clone(),
+            Error::Parse(message) | Error::Validation(message) => message.clone(),
+            Error::Io(err) => err.to_string(),
+        }
+    }
+}
+
+/// Failing to read a CSV file is no parse error
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        match err.is_io_error() {
+            true => match err.into_kind() {
+                csv::ErrorKind::Io(err) => Error::Io(err),
+                _ => unreachable!("an io error"),
+            },
+            false => Error::Parse(err.to_string()),
+        }
+    }
+}
+
+/// Errors of the parsers used for the input formats
+macro_rules! parse_errors {
+    ($($error:ty),*) => {
+        $(impl From<$error> for Error {
+            fn from(err: $error) -> Self {
+                Error::Parse(err.to_string())
+            }
+        })*
+    };
+}
+
+parse_errors!(
+    std::num::ParseIntError,
+    std::num::ParseFloatError,
+    chrono::ParseError,
+    quick_xml::Error,
+    quick_xml::events::attributes::AttrError,
+    serde_json::Error,
+    serde_yaml::Error,
+    calamine::XlsxError
+);
+
+#[test]
+fn test_exit_code() {
+    assert_eq!(EXIT_PARSE, Error::from(InputError::new(2, Some("time"), "invalid time")).exit_code());
+    assert_eq!(EXIT_VALIDATION, Error::Validation("no input files given".to_string()).exit_code());
+    assert_eq!(EXIT_NOT_FOUND, Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).exit_code());
+    assert_eq!(EXIT_IO, Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied)).exit_code());
+    assert_eq!(EXIT_IO, Error::from(csv::Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied))).exit_code());
+    assert_eq!("Error: no input files given", Error::Validation("no input files given".to_string()).to_string());
+    assert_eq!("Error in line 2: invalid time", Error::from(InputError::new(2, None, "invalid time")).to_string());
+}
@@ -0,0 +1,149 @@
+//! How much rasterization and rounding changed a plan, to choose a fitting raster
+
+use std::fmt::{Display, Formatter};
+
+use crate::duration::format_duration;
+use crate::{Converter, Kind, Section, Step};
+
+/// Number of intervals listed as most affected
+const WORST: usize = 3;
+
+/// Differences between the input steps and the converted sections
+#[derive(Debug)]
+pub struct Fidelity {
+    /// sum of the distances between planned and converted power changes, in seconds
+    pub shifted: u32,
+    /// in kJ
+    pub planned_work: f64,
+    /// in kJ
+    pub converted_work: f64,
+    /// the planned intervals whose work changed most
+    pub worst: Vec<Deviation>,
+}
+
+/// Change of a planned interval
+#[derive(Debug, PartialEq)]
+pub struct Deviation {
+    /// in seconds
+    pub start: u32,
+    /// in seconds
+    pub duration: u32,
+    /// planned power in watts
+    pub watts: f64,
+    /// work converted minus planned in the time of the interval, in kJ
+    pub work: f64,
+}
+
+/// An interval of the input with constant power, none for free rides
+struct Planned {
+    start: u32,
+    end: u32,
+    watts: Option<f64>,
+}
+
+impl Fidelity {
+    /// Compares the steps as the converter sees them, accelerated and scaled, with the resulting sections
+    pub fn of(converter: &Converter, steps: &[Step], sections: &[Section]) -> Self {
+        let planned = plan(converter, steps);
+        let ftp = converter.ftp as f64;
+
+        // converted power for every second, sections follow each other without gaps
+        let mut converted: Vec<Option<f64>> = Vec::new();
+        let mut boundaries = vec![0];
+        for sec in sections {
+            for (duration, from, to) in sec.parts() {
+                converted.extend((0..duration).map(|second| match sec.kind {
+                    Kind::FreeRide { .. } => None,
+                    _ => Some((from + (to - from) * (second as f64 + 0.5) / duration as f64) * ftp),
+                }));
+                boundaries.push(converted.len() as u32);
+            }
+        }
+        let work = |start: u32, end: u32| -> f64 {
+            (start..end).filter_map(|second| converted.get(second as usize).copied().flatten()).sum::<f64>() / 1000.0
+        };
+
+        let mut shifted = 0;
+        let mut deviations = Vec::new();
+        for (index, interval) in planned.iter().enumerate() {
+            if index > 0 {
+                shifted += nearest(&boundaries, interval.start);
+            }
+            if let Some(watts) = interval.watts {
+                let delta = work(interval.start, interval.end) - watts * (interval.end - interval.start) as f64 / 1000.0;
+                deviations.push(Deviation { start: interval.start, duration: interval.end - interval.start, watts, work: delta });
+            }
+        }
+        if let Some(last) = planned.last() {
+            shifted += nearest(&boundaries, last.end);
+        }
+
+        let planned_work = planned.iter()
+            .filter_map(|interval| interval.watts.map(|watts| watts * (interval.end - interval.start) as f64 / 1000.0))
+            .sum();
+        let converted_work = work(0, converted.len() as u32);
+
+        deviations.retain(|deviation| deviation.work.abs() >= 0.05);
+        deviations.sort_by(|a, b| b.work.abs().total_cmp(&a.work.abs()));
+        deviations.truncate(WORST);
+
+        Fidelity { shifted, planned_work, converted_work, worst: deviations }
+    }
+}
+
+impl Display for Fidelity {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "seconds shifted    {}", self.shifted)?;
+        writeln!(f, "planned work       {:.1} kJ", self.planned_work)?;
+        writeln!(f, "converted work     {:.1} kJ ({:+.1} kJ)", self.converted_work, self.converted_work - self.planned_work)?;
+        for deviation in &self.worst {
+            let start = format!("at {}", format_duration(deviation.start));
+            writeln!(f, "{:<18} {:>8} of {:>4.0} W {:+6.1} kJ", start, format_duration(deviation.duration), deviation.watts, deviation.work)?;
+        }
+        Ok(())
+    }
+}
+
+/// Intervals between steps with power, texts only don't end them
+fn plan(converter: &Converter, steps: &[Step]) -> Vec<Planned> {
+    let mut planned: Vec<Planned> = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        let time = (step.time.as_secs_f64() / converter.acceleration).round() as u32;
+        let starts = step.watts.is_some() || (converter.free_ride && index + 1 < steps.len());
+        if let Some(last) = planned.last_mut() {
+            last.end = time.max(last.start);
+        }
+        if starts {
+            let watts = step.watts.map(|watts| watts as f64 * converter.power_factor());
+            planned.push(Planned { start: time, end: time, watts });
+        } else if step.text.is_none() && step.hints.is_empty() {
+            // rows with neither power nor text mark the end
+            break;
+        }
+    }
+    planned
+}
+
+/// Distance to the closest boundary
+fn nearest(boundaries: &[u32], time: u32) -> u32 {
+    boundaries.iter().map(|boundary| boundary.abs_diff(time)).min().unwrap_or(0)
+}
+
+#[test]
+fn test_fidelity() {
+    use std::time::Duration;
+
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = vec![step(0, Some(100)), step(100, Some(200)), step(200, None)];
+
+    let converter = Converter { raster: 30, intervals: false, ramp_tolerance: 0.0, ..Converter::new(200) };
+    let sections = converter.translate(vec![step(0, Some(100)), step(100, Some(200)), step(200, None)]);
+    let fidelity = Fidelity::of(&converter, &steps, &sections);
+
+    // the first section starts at the raster, so the change moves to 60s and the end to 180s
+    assert_eq!(60, fidelity.shifted);
+    assert_eq!(30.0, fidelity.planned_work);
+    assert_eq!(30.0, fidelity.converted_work);
+    assert_eq!(Deviation { start: 0, duration: 100, watts: 100.0, work: 4.0 }, fidelity.worst[0]);
+    assert_eq!(-4.0, fidelity.worst[1].work);
+}
@@ -0,0 +1,299 @@
+//! Minimal support for the Garmin FIT binary format
+
+use crate::Error;
+
+const HEADER_SIZE: u8 = 14;
+const PROTOCOL_VERSION: u8 = 0x20;
+const PROFILE_VERSION: u16 = 2132;
+
+/// Global message numbers
+pub const MESG_FILE_ID: u16 = 0;
+pub const MESG_RECORD: u16 = 20;
+pub const MESG_WORKOUT: u16 = 26;
+pub const MESG_WORKOUT_STEP: u16 = 27;
+
+/// Field numbers shared by all messages
+pub const FIELD_TIMESTAMP: u8 = 253;
+
+/// A typed field value
+#[derive(Debug)]
+pub enum Value {
+    Enum(u8),
+    UInt16(u16),
+    UInt32(u32),
+    /// null terminated string, padded to the given size
+    String(String, u8),
+}
+
+impl Value {
+    fn base_type(&self) -> u8 {
+        match self {
+            Value::Enum(_) => 0x00,
+            Value::UInt16(_) => 0x84,
+            Value::UInt32(_) => 0x86,
+            Value::String(_, _) => 0x07,
+        }
+    }
+
+    fn size(&self) -> u8 {
+        match self {
+            Value::Enum(_) => 1,
+            Value::UInt16(_) => 2,
+            Value::UInt32(_) => 4,
+            Value::String(_, size) => *size,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Enum(v) => out.push(*v),
+            Value::UInt16(v) => out.extend_from_slice(&v.to_le_bytes()),
+            Value::UInt32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            Value::String(s, size) => {
+                // keep room for the terminating null byte
+                let mut bytes: Vec<u8> = s.bytes().take(*size as usize - 1).collect();
+                bytes.resize(*size as usize, 0);
+                out.extend_from_slice(&bytes);
+            },
+        }
+    }
+}
+
+/// Assembles FIT messages into a complete file
+#[derive(Debug, Default)]
+pub struct Encoder {
+    data: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder::default()
+    }
+
+    /// Writes a definition message followed by a data message for the given fields.
+    /// Every message gets its own definition, so layouts may differ between messages.
+    pub fn message(&mut self, global: u16, fields: &[(u8, Value)]) {
+        // definition message using local message type 0
+        self.data.push(0x40);
+        self.data.push(0);
+        self.data.push(0); // little endian
+        self.data.extend_from_slice(&global.to_le_bytes());
+        self.data.push(fields.len() as u8);
+        for (num, value) in fields {
+            self.data.extend_from_slice(&[*num, value.size(), value.base_type()]);
+        }
+
+        // data message
+        self.data.push(0x00);
+        for (_, value) in fields {
+            value.encode(&mut self.data);
+        }
+    }
+
+    /// Returns the complete file including header and checksums
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.data.len() + HEADER_SIZE as usize + 2);
+        out.push(HEADER_SIZE);
+        out.push(PROTOCOL_VERSION);
+        out.extend_from_slice(&PROFILE_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(b".FIT");
+        let header_crc = crc(0, &out);
+        out.extend_from_slice(&header_crc.to_le_bytes());
+
+        out.extend_from_slice(&self.data);
+        let file_crc = crc(0, &out);
+        out.extend_from_slice(&file_crc.to_le_bytes());
+        out
+    }
+}
+
+/// A decoded data message
+#[derive(Debug)]
+pub struct Message {
+    pub global: u16,
+    pub fields: Vec<Field>,
+}
+
+/// A raw field of a decoded data message
+#[derive(Debug)]
+pub struct Field {
+    pub num: u8,
+    big_endian: bool,
+    bytes: Vec<u8>,
+}
+
+impl Message {
+    fn field(&self, num: u8) -> Option<&Field> {
+        self.fields.iter().find(|field| field.num == num)
+    }
+
+    /// Returns an enum or uint8 field, unless missing or invalid
+    #[cfg(test)]
+    pub fn u8(&self, num: u8) -> Option<u8> {
+        let value = *self.field(num)?.bytes.first()?;
+        (value != u8::MAX).then_some(value)
+    }
+
+    /// Returns a uint16 field, unless missing or invalid
+    pub fn u16(&self, num: u8) -> Option<u16> {
+        let field = self.field(num)?;
+        let bytes: [u8; 2] = field.bytes.get(..2)?.try_into().ok()?;
+        let value = if field.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) };
+        (value != u16::MAX).then_some(value)
+    }
+
+    /// Returns a uint32 field, unless missing or invalid
+    pub fn u32(&self, num: u8) -> Option<u32> {
+        let field = self.field(num)?;
+        let bytes: [u8; 4] = field.bytes.get(..4)?.try_into().ok()?;
+        let value = if field.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) };
+        (value != u32::MAX).then_some(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    global: u16,
+    big_endian: bool,
+    /// field number and size
+    fields: Vec<(u8, u8)>,
+    /// total size of all developer fields, which are skipped
+    developer_size: usize,
+}
+
+/// Decodes all data messages of a FIT file
+pub fn decode(data: &[u8]) -> Result<Vec<Message>, Error> {
+    if data.len() < 12 || &data[8..12] != b".FIT" {
+        return Err(Error::Parse("not a FIT file".to_string()));
+    }
+
+    let header_size = data[0] as usize;
+    let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let end = header_size + data_size;
+    if data.len() < end {
+        return Err(Error::Parse("FIT file is truncated".to_string()));
+    }
+
+    let mut definitions: [Option<Definition>; 16] = Default::default();
+    let mut messages = Vec::new();
+    let mut last_timestamp = 0u32;
+    let mut pos = header_size;
+
+    let take = |pos: &mut usize, len: usize| -> Result<&[u8], Error> {
+        if *pos + len > end {
+            return Err(Error::Parse(format!("unexpected end of FIT data at byte {}", *pos)));
+        }
+        let slice = &data[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    };
+
+    while pos < end {
+        let header = take(&mut pos, 1)?[0];
+
+        if header & 0x80 == 0 && header & 0x40 != 0 {
+            // definition message
+            let local = (header & 0x0F) as usize;
+            let fixed = take(&mut pos, 5)?;
+            let big_endian = fixed[1] == 1;
+            let global = if big_endian { u16::from_be_bytes([fixed[2], fixed[3]]) } else { u16::from_le_bytes([fixed[2], fixed[3]]) };
+            let count = fixed[4] as usize;
+
+            let fields = take(&mut pos, count * 3)?
+                .chunks(3)
+                .map(|field| (field[0], field[1]))
+                .collect();
+
+            let mut developer_size = 0;
+            if header & 0x20 != 0 {
+                let count = take(&mut pos, 1)?[0] as usize;
+                developer_size = take(&mut pos, count * 3)?
+                    .chunks(3)
+                    .map(|field| field[1] as usize)
+                    .sum();
+            }
+
+            definitions[local] = Some(Definition { global, big_endian, fields, developer_size });
+            continue;
+        }
+
+        // data message, possibly with a compressed timestamp
+        let (local, timestamp) = if header & 0x80 != 0 {
+            let offset = (header & 0x1F) as u32;
+            let mut timestamp = (last_timestamp & !0x1F) + offset;
+            if offset < last_timestamp & 0x1F {
+                timestamp += 0x20;
+            }
+            (((header >> 5) & 0x03) as usize, Some(timestamp))
+        } else {
+            ((header & 0x0F) as usize, None)
+        };
+
+        let definition = definitions[local].clone()
+            .ok_or_else(|| Error::Parse(format!("missing definition for local message {} at byte {}", local, pos - 1)))?;
+
+        let mut fields = Vec::with_capacity(definition.fields.len() + 1);
+        for (num, size) in &definition.fields {
+            let bytes = take(&mut pos, *size as usize)?.to_vec();
+            fields.push(Field { num: *num, big_endian: definition.big_endian, bytes });
+        }
+        take(&mut pos, definition.developer_size)?;
+
+        if let Some(timestamp) = timestamp {
+            fields.push(Field { num: FIELD_TIMESTAMP, big_endian: false, bytes: timestamp.to_le_bytes().to_vec() });
+        }
+
+        let message = Message { global: definition.global, fields };
+        if let Some(timestamp) = message.u32(FIELD_TIMESTAMP) {
+            last_timestamp = timestamp;
+        }
+        messages.push(message);
+    }
+
+    Ok(messages)
+}
+
+/// Computes the FIT CRC-16 over the given bytes
+pub fn crc(mut crc: u16, bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401,
+        0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+
+    for byte in bytes {
+        let mut tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ TABLE[(byte & 0xF) as usize];
+
+        tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ TABLE[((byte >> 4) & 0xF) as usize];
+    }
+
+    crc
+}
+
+#[test]
+fn test_crc() {
+    // a valid file including its checksum has a CRC of zero
+    let file = Encoder::new().finish();
+    assert_eq!(16, file.len());
+    assert_eq!(0, crc(0, &file));
+    assert_eq!(0, crc(0, &file[..14]));
+}
+
+#[test]
+fn test_decode() {
+    let mut encoder = Encoder::new();
+    encoder.message(MESG_RECORD, &[(FIELD_TIMESTAMP, Value::UInt32(1000)), (7, Value::UInt16(250))]);
+    encoder.message(MESG_RECORD, &[(FIELD_TIMESTAMP, Value::UInt32(1001)), (7, Value::UInt16(u16::MAX))]);
+
+    let messages = decode(&encoder.finish()).unwrap();
+    assert_eq!(2, messages.len());
+    assert_eq!(MESG_RECORD, messages[0].global);
+    assert_eq!(Some(1000), messages[0].u32(FIELD_TIMESTAMP));
+    assert_eq!(Some(250), messages[0].u16(7));
+    assert_eq!(Some(1001), messages[1].u32(FIELD_TIMESTAMP));
+    assert_eq!(None, messages[1].u16(7));
+}
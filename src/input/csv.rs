@@ -0,0 +1,765 @@
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use csv::StringRecord;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+
+use super::encoding::Decode;
+use super::{parse_messages, parse_power, watts, InputError, InputReader, Metadata, PowerUnit, Steps, TimeFormat, TimeMode, Timeline};
+use crate::duration::format_time;
+use crate::{parse_duration, parse_pace, DurationType, Error, PaceUnit, Step, ZoneModel};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Record {
+    /// may be left out in favor of distances
+    #[serde(default)]
+    time: String,
+    power: Option<String>,
+    text: Option<String>,
+    /// optional column, not written back
+    #[serde(default, deserialize_with = "csv::invalid_option", skip_serializing)]
+    cadence: Option<u16>,
+    /// optional column with heart rate in bpm, used in rows without power
+    #[serde(default, deserialize_with = "csv::invalid_option", skip_serializing)]
+    hr: Option<u16>,
+    /// optional column with distance in meters instead of time
+    #[serde(default, deserialize_with = "csv::invalid_option", skip_serializing)]
+    distance: Option<u32>,
+    /// optional column with the time the text is shown, like "15s"
+    #[serde(default, skip_serializing)]
+    text_duration: Option<String>,
+    /// optional column with the end of the row, the one of the last row ends the workout
+    #[serde(default, skip_serializing)]
+    end_time: Option<String>,
+    /// optional column with the raster of the section starting in the row, like "5s"
+    #[serde(default, skip_serializing)]
+    raster: Option<String>,
+    /// optional column marking key intervals like "x", which keep their exact times with --anchor
+    #[serde(default, skip_serializing)]
+    key: Option<String>,
+}
+
+/// Reads CSV data with a `time,power,text` header and optional `cadence`, `hr`, `distance`, `text_duration`, `end_time`, `raster` and `key` columns.
+/// Texts like "Go!@15s" are shown for the given duration, several ones are separated by "|" like "Ready|+10s Go!".
+/// With distances given, the steps are measured in meters instead of seconds.
+#[derive(Debug)]
+pub struct CsvReader {
+    pub time_mode: TimeMode,
+    pub time_format: TimeFormat,
+    pub power_unit: PowerUnit,
+    /// absolute FTP in watts, used to convert relative power
+    pub ftp: u16,
+    /// zones to resolve labels like "Z2" in the power column
+    pub zones: ZoneModel,
+    /// lactate threshold heart rate in bpm, to convert the `hr` column into power
+    pub lthr: Option<u16>,
+    /// threshold pace of run workouts in seconds per km, paces like "4:30" are relative to it
+    pub threshold_pace: Option<f64>,
+    /// unit of paces without an explicit one
+    pub pace_unit: PaceUnit,
+    /// names of columns differing from the field names
+    pub columns: Columns,
+    /// whether the first row names the columns, otherwise the columns are time, power and text
+    pub has_header: bool,
+    pub delimiter: Delimiter,
+    /// encoding of files not saved as UTF-8, like "windows-1252" of older Excel versions
+    pub encoding: Option<&'static Encoding>,
+    /// fail on unknown columns, a missing power column and rows with fewer or more filled fields than the header,
+    /// instead of ignoring them
+    pub strict: bool,
+    /// sort rows by time instead of failing on rows before the previous one, in time mode
+    pub sort: bool,
+}
+
+/// Separator of the columns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delimiter {
+    /// the most frequent of comma, semicolon and tab in the first line, like in exports of European Excel locales
+    #[default]
+    Auto,
+    Char(u8),
+}
+
+impl Delimiter {
+    /// Counts the candidates outside of quotes, comma wins a tie
+    fn sniff(line: &[u8]) -> u8 {
+        let mut quoted = false;
+        let mut counts = [(b',', 0), (b';', 0), (b'\t', 0)];
+        for c in line {
+            match c {
+                b'"' => quoted = !quoted,
+                c if !quoted => counts.iter_mut().filter(|(delimiter, _)| delimiter == c).for_each(|(_, count)| *count += 1),
+                _ => {},
+            }
+        }
+        counts.iter().fold((b',', 0), |best, &(delimiter, count)| if count > best.1 { (delimiter, count) } else { best }).0
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Delimiter::Auto),
+            "tab" | "\\t" | "\t" => Ok(Delimiter::Char(b'\t')),
+            _ if s.len() == 1 && s.is_ascii() => Ok(Delimiter::Char(s.as_bytes()[0])),
+            _ => Err(format!("invalid delimiter \"{}\", expected a single character, \"tab\" or \"auto\"", s)),
+        }
+    }
+}
+
+impl Display for Delimiter {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Delimiter::Auto => write!(f, "auto"),
+            Delimiter::Char(b'\t') => write!(f, "tab"),
+            Delimiter::Char(c) => write!(f, "{}", *c as char),
+        }
+    }
+}
+
+/// Names of the input columns, for CSVs exported by other tools with headers like "Watts" instead of "power"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Columns {
+    pub time: Option<String>,
+    pub power: Option<String>,
+    pub text: Option<String>,
+    pub cadence: Option<String>,
+    pub hr: Option<String>,
+    pub distance: Option<String>,
+    pub text_duration: Option<String>,
+}
+
+impl Columns {
+    /// The fields with the columns renamed to them
+    fn renames(&self) -> [(&'static str, Option<&str>); 7] {
+        [
+            ("time", self.time.as_deref()),
+            ("power", self.power.as_deref()),
+            ("text", self.text.as_deref()),
+            ("cadence", self.cadence.as_deref()),
+            ("hr", self.hr.as_deref()),
+            ("distance", self.distance.as_deref()),
+            ("text_duration", self.text_duration.as_deref()),
+        ]
+    }
+
+    /// Renames the columns of the header to the fields, by the given names or common spellings.
+    /// Columns spelled like a field with a given name, and repeated columns are ignored, unless strict.
+    fn rename(&self, header: &StringRecord, strict: bool) -> Result<StringRecord, Error> {
+        let renames = self.renames();
+        for (field, name) in renames {
+            if let Some(name) = name.filter(|name| !header.iter().any(|column| column == *name)) {
+                return Err(Error::Validation(format!("the header has no column \"{}\" for the {}", name, field)));
+            }
+        }
+
+        let mut fields = Vec::new();
+        for column in header {
+            let field = match renames.iter().find(|(_, name)| *name == Some(column)) {
+                Some((field, _)) => Some(*field),
+                None => alias(column).filter(|field| !renames.iter().any(|(renamed, name)| name.is_some() && renamed == field)),
+            };
+            let field = field.filter(|field| !fields.contains(&Some(*field)));
+            // unnamed columns come from trailing delimiters
+            if strict && field.is_none() && !column.is_empty() {
+                return Err(Error::Validation(format!("unknown column \"{}\" in the header", column)));
+            }
+            fields.push(field);
+        }
+        if strict && !fields.contains(&Some("power")) {
+            return Err(Error::Validation("the header has no power column".to_string()));
+        }
+        // most likely the first row holds data already
+        if !fields.iter().any(|field| matches!(field, Some("time" | "duration" | "distance"))) {
+            return Err(Error::Validation(format!("the header \"{}\" has no time, duration or distance column, use --no-header for files without one", header.iter().collect::<Vec<_>>().join(","))));
+        }
+        Ok(fields.into_iter().map(Option::unwrap_or_default).collect())
+    }
+}
+
+/// Common spellings of the columns, after lowercasing
+const ALIASES: &[(&str, &[&str])] = &[
+    ("time", &["time", "timestamp", "start", "elapsed"]),
+    ("power", &["power", "watts", "watt", "pwr"]),
+    ("text", &["text", "message", "note", "notes", "comment", "cue"]),
+    ("cadence", &["cadence", "cad", "rpm"]),
+    ("hr", &["hr", "heart_rate", "heartrate", "bpm"]),
+    ("duration", &["duration", "length"]),
+    ("distance", &["distance", "dist"]),
+    ("text_duration", &["text_duration"]),
+    ("end_time", &["end_time", "end", "stop"]),
+    ("raster", &["raster"]),
+    ("key", &["key", "anchor"]),
+];
+
+/// The field a column like "Power (W)" or "Heart Rate" is meant for
+fn alias(column: &str) -> Option<&'static str> {
+    // leaves out units like "(W)" or "[bpm]"
+    let name = column.split(['(', '[']).next().unwrap_or_default().trim().to_lowercase().replace([' ', '-'], "_");
+    ALIASES.iter().find(|(_, aliases)| aliases.contains(&name.as_str())).map(|(field, _)| *field)
+}
+
+impl InputReader for CsvReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        Ok(self.read_with_metadata(source)?.0)
+    }
+
+    fn read_with_metadata(&self, source: &mut dyn Read) -> Result<(Vec<Step>, Metadata), Error> {
+        let (steps, metadata) = self.stream(source)?;
+        Ok((steps.collect::<Result<_, _>>()?, metadata))
+    }
+
+    /// Parses one record after the other, so huge logs are never held in memory as a whole
+    fn stream<'a>(&'a self, source: &'a mut dyn Read) -> Result<(Steps<'a>, Metadata), Error> {
+        let source: Box<dyn Read + 'a> = match self.encoding {
+            Some(encoding) => Box::new(Decode::new(source, encoding)),
+            None => Box::new(source),
+        };
+        let mut source = BufReader::new(source);
+        // byte order mark of UTF-8 files saved by Excel
+        if source.fill_buf()?.starts_with(b"\xef\xbb\xbf") {
+            source.consume(3);
+        }
+        let mut metadata = read_comments(&mut source)?;
+        let delimiter = match self.delimiter {
+            Delimiter::Auto => {
+                let buffer = source.fill_buf()?;
+                Delimiter::sniff(buffer.split(|c| *c == b'\n').next().unwrap_or_default())
+            },
+            Delimiter::Char(c) => c,
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(self.has_header)
+            .delimiter(delimiter)
+            .comment(Some(b'#'))
+            .flexible(true)
+            // stray spaces of hand edited spreadsheets
+            .trim(csv::Trim::All)
+            .from_reader(source);
+        let mut header = match self.has_header {
+            true => self.columns.rename(reader.headers()?, self.strict)?,
+            false => StringRecord::from(vec!["time", "power", "text"]),
+        };
+        // a duration column holds the length of every row, like the time column in duration mode
+        let durations = !header.iter().any(|column| column == "time") && header.iter().any(|column| column == "duration");
+        if durations {
+            header = header.iter().map(|column| if column == "duration" { "time" } else { column }).collect();
+        }
+
+        let strict = self.strict;
+        let mut records = reader.into_records()
+            .map(move |record| {
+                let mut record = record?;
+                let trailing = record.iter().skip(header.len()).all(str::is_empty);
+                if strict && (record.len() < header.len() || !trailing) {
+                    return Err(csv::Error::from(std::io::Error::new(std::io::ErrorKind::InvalidData,
+                        format!("found {} fields, but the header has {}", record.len(), header.len()))));
+                }
+                // extra fields are ignored, missing ones empty
+                record.truncate(header.len());
+                while record.len() < header.len() {
+                    record.push_field("");
+                }
+                record.deserialize::<Record>(Some(&header))
+            })
+            .peekable();
+        metadata.duration_type = match records.peek() {
+            Some(Ok(Record { distance: Some(_), .. })) => DurationType::Distance,
+            _ => DurationType::Time,
+        };
+
+        let duration_type = metadata.duration_type;
+        let time_mode = if durations { TimeMode::Duration } else { self.time_mode };
+        let mut timeline = Timeline::new(time_mode);
+        let sorting = self.sort && time_mode == TimeMode::Time;
+        let rows = records.enumerate().map(move |(index, record)| {
+            let line = index + 1;
+            let record = record.map_err(|err| match err.kind() {
+                csv::ErrorKind::Utf8 { .. } => InputError::new(line, None, format!("{}, use --encoding for files not saved as UTF-8", err)),
+                _ => InputError::new(line, None, err.to_string()),
+            })?;
+            if duration_type == DurationType::Distance && record.distance.is_none() {
+                return Err(InputError::new(line, Some("distance"), "missing distance").into());
+            }
+            let mut step = self.parse_step(line, &record)?;
+            match timeline.place(&mut step) {
+                Ok(()) => {},
+                // sorted once all rows are read
+                Err(_) if sorting => {},
+                Err(last_time) => {
+                    let msg = format!("time {} is before last time {}, use --sort-input to sort rows by time", format_time(step.time), format_time(last_time));
+                    return Err(InputError::new(line, Some("time"), msg).into());
+                },
+            }
+            let end = self.parse_end(line, &record, &step, time_mode, duration_type)?;
+            Ok((step, end))
+        });
+        if sorting {
+            // stable, so texts stay behind the power of the same time
+            let mut rows = rows.collect::<Result<Vec<_>, Error>>()?;
+            rows.sort_by_key(|(step, _)| step.time);
+            // the latest end time ends the last section, whichever row it is given in
+            let latest = rows.iter().filter_map(|(_, end)| *end).max();
+            if let Some((step, end)) = rows.last_mut() {
+                *end = latest.filter(|latest| *latest >= step.time);
+            }
+            return Ok((end_rows(rows.into_iter().map(Ok)), metadata));
+        }
+        Ok((end_rows(rows), metadata))
+    }
+}
+
+/// Adds a step without power at the end time of rows ending before the next row starts, and after the last row
+fn end_rows<'a>(rows: impl Iterator<Item = Result<(Step, Option<Duration>), Error>> + 'a) -> Steps<'a> {
+    let mut rows = rows.peekable();
+    let mut ended: Option<Step> = None;
+    Box::new(std::iter::from_fn(move || {
+        if let Some(step) = ended.take() {
+            return Some(Ok(step));
+        }
+        let (step, end) = match rows.next()? {
+            Ok(row) => row,
+            Err(err) => return Some(Err(err)),
+        };
+        if let Some(end) = end {
+            let before_next = match rows.peek() {
+                Some(Ok((next, _))) => end < next.time,
+                Some(Err(_)) => false,
+                None => true,
+            };
+            if before_next {
+                ended = Some(Step { time: end, ..Step::default() });
+            }
+        }
+        Some(Ok(step))
+    }))
+}
+
+impl CsvReader {
+    pub fn new(time_mode: TimeMode, ftp: u16) -> Self {
+        CsvReader {
+            time_mode,
+            time_format: TimeFormat::Auto,
+            power_unit: PowerUnit::Watts,
+            ftp,
+            zones: ZoneModel::default(),
+            lthr: None,
+            threshold_pace: None,
+            pace_unit: PaceUnit::Km,
+            columns: Columns::default(),
+            has_header: true,
+            delimiter: Delimiter::Auto,
+            encoding: None,
+            strict: false,
+            sort: false,
+        }
+    }
+
+    /// The end time of the row, if given
+    fn parse_end(&self, line: usize, record: &Record, step: &Step, time_mode: TimeMode, duration_type: DurationType) -> Result<Option<Duration>, InputError> {
+        let fail = |message: String| InputError::new(line, Some("end_time"), message);
+        let Some(text) = record.end_time.as_deref().filter(|text| !text.is_empty()) else {
+            return Ok(None);
+        };
+        if time_mode == TimeMode::Duration || duration_type == DurationType::Distance {
+            return Err(fail("end times need rows with start times".to_string()));
+        }
+        let end = self.time_format.parse(text).map_err(|err| fail(format!("invalid end time \"{}\": {}", text, err)))?;
+        if end < step.time {
+            return Err(fail(format!("end time {} is before time {}", format_time(end), format_time(step.time))));
+        }
+        Ok(Some(end))
+    }
+
+    fn parse_step(&self, line: usize, record: &Record) -> Result<Step, InputError> {
+        let fail = |column: &str, message: String| InputError::new(line, Some(column), message);
+
+        // distances are handled like seconds
+        let time = match record.distance {
+            Some(distance) => Duration::from_secs(distance as u64),
+            None => self.time_format.parse(&record.time)
+                .map_err(|err| fail("time", format!("invalid time \"{}\": {}", record.time, err)))?,
+        };
+        let power = record.power.as_deref().map(|power| self.parse_power(power)).transpose()
+            .map_err(|err| fail("power", err))?;
+        let watts = match (power.flatten(), record.hr) {
+            (None, Some(hr)) => Some(self.heart_rate_power(hr).map_err(|err| fail("hr", err.message()))?),
+            (watts, _) => watts,
+        };
+
+        // the first message belongs to the row itself, unless it has an offset
+        let mut hints = record.text.as_deref().map(parse_messages).unwrap_or_default();
+        let first = match hints.first() {
+            Some(hint) if hint.offset == 0 => Some(hints.remove(0)),
+            _ => None,
+        };
+        // "END" in capitals marks the end of the last section only, it isn't shown
+        let first = first.filter(|hint| watts.is_some() || hint.text != "END");
+        let text_duration = match record.text_duration.as_deref().map(str::trim).filter(|duration| !duration.is_empty()) {
+            Some(duration) => Some(parse_duration(duration).map_err(|err| fail("text_duration", err))?),
+            None => first.as_ref().and_then(|hint| hint.duration),
+        };
+        let raster = match record.raster.as_deref().map(str::trim).filter(|raster| !raster.is_empty()) {
+            Some(raster) => Some(parse_duration(raster).ok().filter(|raster| *raster > 0)
+                .ok_or_else(|| fail("raster", format!("invalid raster \"{}\"", raster)))?),
+            None => None,
+        };
+        // any mark but "no" makes a key interval
+        let key = record.key.as_deref().map(str::trim)
+            .is_some_and(|key| !key.is_empty() && !["0", "no", "false"].contains(&key.to_lowercase().as_str()));
+        let step = Step{time, watts, cadence: record.cadence, text: first.map(|hint| hint.text), text_duration, hints, raster, key};
+        Ok(step)
+    }
+}
+
+impl CsvReader {
+    /// Parses power into watts, paces are turned into speed relative to the threshold pace
+    fn parse_power(&self, power: &str) -> Result<Option<u16>, String> {
+        let pace = self.threshold_pace.and_then(|threshold| parse_pace(power, self.pace_unit).map(|pace| threshold / pace));
+        match pace {
+            Some(speed) => watts(speed * self.ftp as f64, power).map(Some),
+            None => parse_power(power, self.power_unit, self.ftp, &self.zones),
+        }
+    }
+
+    /// Converts heart rate into watts by mapping heart rate zones onto the power zones of the same name
+    fn heart_rate_power(&self, hr: u16) -> Result<u16, Error> {
+        let lthr = self.lthr.ok_or_else(|| Error::Validation("the hr column needs --lthr".to_string()))?;
+        let power = ZoneModel::coggan_heart_rate().translate(hr as f64 / lthr as f64, &self.zones)
+            .ok_or_else(|| Error::Parse(format!("no power zone matches heart rate {} bpm in zone model \"{}\"", hr, self.zones)))?;
+        Ok((power * self.ftp as f64).round() as u16)
+    }
+}
+
+/// Skips the blank and comment lines in front of the header, comments like "# author: Jane" give metadata
+fn read_comments(source: &mut dyn BufRead) -> Result<Metadata, Error> {
+    let mut metadata = Metadata::default();
+    while let Some(b'#' | b'\r' | b'\n') = source.fill_buf()?.first() {
+        let mut line = String::new();
+        source.read_line(&mut line)?;
+        let Some((key, value)) = line.trim().trim_start_matches('#').split_once(':') else {
+            continue;
+        };
+        let value = Some(value.trim().to_string());
+        match key.trim().to_lowercase().as_str() {
+            "name" => metadata.name = value,
+            "description" => metadata.description = value,
+            "author" => metadata.author = value,
+            _ => {},
+        }
+    }
+    Ok(metadata)
+}
+
+/// Writes steps as CSV data with a `time,power,text` header, readable by [`CsvReader`]
+pub fn write_csv(steps: &[Step], sink: &mut dyn Write) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(sink);
+    for step in steps {
+        writer.serialize(Record {
+            time: format_time(step.time),
+            power: step.watts.map(|watts| watts.to_string()),
+            // keep the display duration as suffix of the text
+            text: match (&step.text, step.text_duration) {
+                (Some(text), Some(duration)) => Some(format!("{}@{}s", text, duration)),
+                (text, _) => text.clone(),
+            },
+            cadence: step.cadence,
+            hr: None,
+            distance: None,
+            text_duration: None,
+            end_time: None,
+            raster: None,
+            key: None,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_csv() {
+    let steps = vec![
+        Step { watts: Some(180), ..Step::default() },
+        Step { time: Duration::from_secs(90), text: Some("Turn right".to_string()), ..Step::default() },
+    ];
+
+    let mut out = Vec::new();
+    write_csv(&steps, &mut out).unwrap();
+    assert_eq!("time,power,text\n00:00:00,180,\n00:01:30,,Turn right\n", String::from_utf8(out.clone()).unwrap());
+
+    let read = CsvReader::new(TimeMode::Time, 250).read(&mut &out[..]).unwrap();
+    assert_eq!(2, read.len());
+    assert_eq!(Some("Turn right".to_string()), read[1].text);
+}
+
+#[test]
+fn test_cadence() {
+    let csv = "time,power,text,cadence\n00:00:00,200,,60\n00:05:00,250,Spin up,\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(Some(60), steps[0].cadence);
+    assert_eq!(None, steps[1].cadence);
+}
+
+#[test]
+fn test_heart_rate() {
+    let csv = "time,power,text,hr\n00:00:00,,,150\n00:05:00,300,,170\n00:10:00,,End,\n";
+    let reader = CsvReader { lthr: Some(170), ..CsvReader::new(TimeMode::Time, 200) };
+    let steps = reader.read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(Some(164), steps[0].watts);
+    assert_eq!(Some(300), steps[1].watts);
+    assert_eq!(None, steps[2].watts);
+
+    assert!(CsvReader::new(TimeMode::Time, 200).read(&mut csv.as_bytes()).is_err());
+}
+
+#[test]
+fn test_pace() {
+    let csv = "time,power,text\n00:00:00,5:00,\n00:10:00,4:00,\n00:15:00,6:26/mi,\n00:20:00,,End\n";
+    let reader = CsvReader { threshold_pace: Some(240.0), ..CsvReader::new(TimeMode::Time, 100) };
+    let steps = reader.read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(Some(80), steps[0].watts);
+    assert_eq!(Some(100), steps[1].watts);
+    assert_eq!(Some(100), steps[2].watts);
+}
+
+#[test]
+fn test_distance() {
+    let csv = "distance,power,text\n800,300,Fast\n400,150,\n0,,End\n";
+    let reader = CsvReader::new(TimeMode::Duration, 250);
+    let (steps, metadata) = reader.read_with_metadata(&mut csv.as_bytes()).unwrap();
+    assert_eq!(DurationType::Distance, metadata.duration_type);
+    assert_eq!(Duration::from_secs(1200), steps[2].time);
+
+    let mixed = "time,distance,power,text\n,800,300,\n00:05:00,,150,\n";
+    assert!(reader.read(&mut mixed.as_bytes()).is_err());
+}
+
+#[test]
+fn test_text_duration() {
+    let csv = "time,power,text,text_duration\n00:00:00,200,Long instruction,30s\n00:01:00,,Go!@5s,\n00:02:00,,Plain,\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(Some(30), steps[0].text_duration);
+    assert_eq!((Some("Go!".to_string()), Some(5)), (steps[1].text.clone(), steps[1].text_duration));
+    assert_eq!(None, steps[2].text_duration);
+
+    let mut out = Vec::new();
+    write_csv(&steps, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("00:01:00,,Go!@5s\n"));
+}
+
+#[test]
+fn test_messages() {
+    let csv = "time,power,text\n00:00:00,200,Ready|+10s Go!|+20s Faster\n00:01:00,,+15s Later\n00:02:00,,End\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(Some("Ready".to_string()), steps[0].text);
+    assert_eq!(vec![10, 20], steps[0].hints.iter().map(|hint| hint.offset).collect::<Vec<_>>());
+    assert_eq!((None, 15), (steps[1].text.clone(), steps[1].hints[0].offset));
+}
+
+#[test]
+fn test_errors() {
+    let reader = CsvReader::new(TimeMode::Time, 250);
+    let error = |csv: &str| match reader.read(&mut csv.as_bytes()).unwrap_err() {
+        Error::Input(err) => err,
+        err => panic!("unexpected error {}", err),
+    };
+
+    assert_eq!(InputError::new(1, Some("time"), "invalid time \"1:xx\": input contains invalid characters"), error("time,power,text\n1:xx,200,\n"));
+    assert_eq!(InputError::new(1, Some("time"), "invalid time \"99999999:00\": input is out of range"), error("time,power,text\n99999999:00,200,\n"));
+    assert_eq!(InputError::new(2, Some("text_duration"), "invalid duration \"soon\""), error("time,power,text,text_duration\n00:00:00,200,,\n00:01:00,,Go,soon\n"));
+    assert_eq!("Error in line 2: missing distance", error("distance,power,text\n0,200,\n,100,\n").to_string());
+
+    assert_eq!(InputError::new(2, Some("power"), "power \"1e9\" is more than 65535 watts"), error("time,power\n0:00,200\n1:00,1e9\n"));
+}
+
+#[test]
+fn test_stream() {
+    let csv = "time,power,text\n00:00:00,200,\n00:01:00,250,\n00:02:00,xx:yy,\nbroken,,\n";
+    let reader = CsvReader::new(TimeMode::Time, 250);
+    let mut source = csv.as_bytes();
+    let (mut steps, metadata) = reader.stream(&mut source).unwrap();
+    assert_eq!(DurationType::Time, metadata.duration_type);
+    assert_eq!(Some(200), steps.next().unwrap().unwrap().watts);
+    assert_eq!(Some(250), steps.next().unwrap().unwrap().watts);
+    assert_eq!(None, steps.next().unwrap().unwrap().watts);
+    assert!(matches!(steps.next(), Some(Err(Error::Input(InputError { line: 4, .. })))));
+}
+
+#[test]
+fn test_columns() {
+    let csv = "Timestamp,Watts,power,Note\n00:00:00,200,1,Go\n00:01:00,250,2,\n";
+    let columns = Columns { time: Some("Timestamp".to_string()), power: Some("Watts".to_string()), text: Some("Note".to_string()), ..Columns::default() };
+    let reader = CsvReader { columns, ..CsvReader::new(TimeMode::Time, 250) };
+    let steps = reader.read(&mut csv.as_bytes()).unwrap();
+    assert_eq!((Some(200), Some("Go".to_string())), (steps[0].watts, steps[0].text.clone()));
+    assert_eq!(Some(250), steps[1].watts);
+
+    let reader = CsvReader { columns: Columns { power: Some("Power".to_string()), ..Columns::default() }, ..CsvReader::new(TimeMode::Time, 250) };
+    assert_eq!("Error: the header has no column \"Power\" for the power", reader.read(&mut csv.as_bytes()).unwrap_err().to_string());
+}
+
+#[test]
+fn test_no_header() {
+    let csv = "00:00:00,180,\n00:01:30,,Turn right\n00:02:00,210,Up that hill\n";
+    assert!(CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).is_err());
+    let reader = CsvReader { has_header: false, ..CsvReader::new(TimeMode::Time, 250) };
+    let steps = reader.read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(3, steps.len());
+    assert_eq!(Some(180), steps[0].watts);
+    assert_eq!(Some("Turn right".to_string()), steps[1].text);
+
+    let steps = reader.read(&mut "00:00:00,180\n00:05:00,200\n".as_bytes()).unwrap();
+    assert_eq!(Some(200), steps[1].watts);
+}
+
+#[test]
+fn test_delimiter() {
+    let reader = CsvReader::new(TimeMode::Time, 250);
+    let steps = reader.read(&mut "time;power;text\n00:00:00;180;\"Go, go\"\n00:01:00;200;\n".as_bytes()).unwrap();
+    assert_eq!((Some(180), Some("Go, go".to_string())), (steps[0].watts, steps[0].text.clone()));
+    let steps = reader.read(&mut "time\tpower\ttext\n00:00:00\t180\t\n".as_bytes()).unwrap();
+    assert_eq!(Some(180), steps[0].watts);
+
+    let reader = CsvReader { delimiter: Delimiter::Char(b'|'), ..CsvReader::new(TimeMode::Time, 250) };
+    assert_eq!(Some(180), reader.read(&mut "time|power|text\n00:00:00|180|\n".as_bytes()).unwrap()[0].watts);
+
+    assert_eq!(b',', Delimiter::sniff(b"\"time;a\",power"));
+    assert_eq!(Ok(Delimiter::Char(b'\t')), "tab".parse());
+    assert!("::".parse::<Delimiter>().is_err());
+}
+
+#[test]
+fn test_comments() {
+    let csv = "# name: Sweet Spot\n# Author: Jane Doe\n# just a note\n\ntime,power,text\n# warmup\n00:00:00,180,\n\n00:05:00,250,# not a comment\n";
+    let (steps, metadata) = CsvReader::new(TimeMode::Time, 250).read_with_metadata(&mut csv.as_bytes()).unwrap();
+    assert_eq!(2, steps.len());
+    assert_eq!(Some("# not a comment".to_string()), steps[1].text);
+    assert_eq!((Some("Sweet Spot".to_string()), Some("Jane Doe".to_string()), None), (metadata.name, metadata.author, metadata.description));
+}
+
+#[test]
+fn test_aliases() {
+    let csv = "TIME,Power (W),Heart Rate [bpm],watts,Note\n00:00:00,200,150,1,Go\n00:01:00,250,160,2,\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!((Some(200), Some("Go".to_string())), (steps[0].watts, steps[0].text.clone()));
+    assert_eq!(Some("hr"), alias("Heart-Rate"));
+    assert_eq!(None, alias("speed"));
+}
+
+#[test]
+fn test_strict() {
+    let csv = "time,power,text,speed\n00:00:00,200,Go,30\n00:01:00,250\n00:02:00,,End,,\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!((3, Some(250), None), (steps.len(), steps[1].watts, steps[1].text.clone()));
+
+    let reader = CsvReader { strict: true, ..CsvReader::new(TimeMode::Time, 250) };
+    assert_eq!("Error: unknown column \"speed\" in the header", reader.read(&mut csv.as_bytes()).unwrap_err().to_string());
+    assert_eq!("Error: the header has no power column", reader.read(&mut "time,text\n00:00:00,Go\n".as_bytes()).unwrap_err().to_string());
+    let err = reader.read(&mut "time,power,text\n00:00:00,200,Go\n00:01:00,250\n".as_bytes()).unwrap_err();
+    assert!(matches!(err, Error::Input(InputError { line: 2, .. })));
+}
+
+#[test]
+fn test_encoding() {
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut "\u{feff}time,power,text\n00:00:00,200,Grüße\n".as_bytes()).unwrap();
+    assert_eq!(Some("Grüße".to_string()), steps[0].text);
+
+    let csv = b"time;power;text\n00:00:00;200;Gr\xfc\xdfe\n";
+    assert!(CsvReader::new(TimeMode::Time, 250).read(&mut &csv[..]).is_err());
+    let reader = CsvReader { encoding: Some(encoding_rs::WINDOWS_1252), ..CsvReader::new(TimeMode::Time, 250) };
+    assert_eq!(Some("Grüße".to_string()), reader.read(&mut &csv[..]).unwrap()[0].text);
+}
+
+#[test]
+fn test_whitespace() {
+    let csv = "time, power ,text,\r\n 00:00:00 , 200 , Go ,\r\n00:05:00,,End,,\r\n";
+    for strict in [false, true] {
+        let reader = CsvReader { strict, ..CsvReader::new(TimeMode::Time, 250) };
+        let steps = reader.read(&mut csv.as_bytes()).unwrap();
+        assert_eq!((Some(200), Some("Go".to_string())), (steps[0].watts, steps[0].text.clone()));
+        assert_eq!(Some("End".to_string()), steps[1].text);
+    }
+}
+
+#[test]
+fn test_duration_column() {
+    let csv = "duration,power,text\n300,180,\n5:00,250,Go\n0,,End\n";
+    let steps = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(vec![0, 300, 600], steps.iter().map(|step| step.time.as_secs()).collect::<Vec<_>>());
+
+    let steps = CsvReader::new(TimeMode::Duration, 250).read(&mut "time,power\n300,180\n0,\n".as_bytes()).unwrap();
+    assert_eq!(300, steps[1].time.as_secs());
+
+    let steps = CsvReader::new(TimeMode::Duration, 250).read(&mut "time,power
+20:00:00,180
+10:00:00,150
+0,
+".as_bytes()).unwrap();
+    assert_eq!(108000, steps[2].time.as_secs());
+}
+
+#[test]
+fn test_end() {
+    let reader = CsvReader::new(TimeMode::Time, 250);
+    let steps = reader.read(&mut "time,power,text\n00:00:00,200,\n00:10:00,,END\n".as_bytes()).unwrap();
+    assert_eq!((600, None), (steps[1].time.as_secs(), steps[1].text.clone()));
+
+    let steps = reader.read(&mut "time,power,end_time\n0:00,200,9:00\n10:00,250,12:30\n".as_bytes()).unwrap();
+    let times: Vec<(u64, Option<u16>)> = steps.iter().map(|step| (step.time.as_secs(), step.watts)).collect();
+    assert_eq!(vec![(0, Some(200)), (540, None), (600, Some(250)), (750, None)], times);
+
+    // ending right at the next row adds nothing, and sorted rows end at the latest end time
+    let reader = CsvReader { sort: true, ..CsvReader::new(TimeMode::Time, 250) };
+    let steps = reader.read(&mut "time,power,end_time\n10:00,250,\n0:00,200,10:00\n5:00,180,12:00\n".as_bytes()).unwrap();
+    let times: Vec<(u64, Option<u16>)> = steps.iter().map(|step| (step.time.as_secs(), step.watts)).collect();
+    assert_eq!(vec![(0, Some(200)), (300, Some(180)), (600, Some(250)), (720, None)], times);
+
+    assert!(reader.read(&mut "time,power,end_time\n10:00,250,9:00\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_subsecond() {
+    let reader = CsvReader::new(TimeMode::Duration, 250);
+    let steps = reader.read(&mut "time,power
+0.25,200
+0.25,300
+0.5,200
+0,
+".as_bytes()).unwrap();
+    assert_eq!(vec![0, 250, 500, 1000], steps.iter().map(|step| step.time.as_millis()).collect::<Vec<_>>());
+
+    let mut out = Vec::new();
+    write_csv(&steps[1..2], &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("00:00:00.25,"));
+}
+
+#[test]
+fn test_sort() {
+    let csv = "time,power,text\n0:00,200,\n10:00,250,\n5:00,300,\n5:00,,Go\n15:00,,\n";
+    let error = CsvReader::new(TimeMode::Time, 250).read(&mut csv.as_bytes()).unwrap_err();
+    assert_eq!("Error in line 3: time 00:05:00 is before last time 00:10:00, use --sort-input to sort rows by time", error.to_string());
+
+    let reader = CsvReader { sort: true, ..CsvReader::new(TimeMode::Time, 250) };
+    let steps = reader.read(&mut csv.as_bytes()).unwrap();
+    assert_eq!(vec![0, 300, 300, 600, 900], steps.iter().map(|step| step.time.as_secs()).collect::<Vec<_>>());
+    assert_eq!((Some(300), Some("Go".to_string())), (steps[1].watts, steps[2].text.clone()));
+}
+
+#[test]
+fn test_raster_column() {
+    let reader = CsvReader::new(TimeMode::Time, 250);
+    let steps = reader.read(&mut "time,power,raster
+0:00,150,
+10:00,400,5s
+10:15,150,
+".as_bytes()).unwrap();
+    assert_eq!(vec![None, Some(5), None], steps.iter().map(|step| step.raster).collect::<Vec<_>>());
+    assert!(reader.read(&mut "time,power,raster
+0:00,150,0
+".as_bytes()).is_err());
+}
@@ -0,0 +1,56 @@
+//! Transcoding of text inputs saved in other encodings than UTF-8
+
+use std::io::{BufRead, BufReader, Read};
+
+use encoding_rs::{CoderResult, Decoder, Encoding};
+
+/// Looks up an encoding by a label like "latin1" or "windows-1252"
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("unknown encoding \"{}\", expected a label like \"latin1\" or \"windows-1252\"", label))
+}
+
+/// Decodes the source into UTF-8 while it is read, a byte order mark takes precedence over the encoding
+pub(crate) struct Decode<R> {
+    source: BufReader<R>,
+    decoder: Decoder,
+    finished: bool,
+}
+
+impl<R: Read> Decode<R> {
+    pub fn new(source: R, encoding: &'static Encoding) -> Self {
+        Decode { source: BufReader::new(source), decoder: encoding.new_decoder(), finished: false }
+    }
+}
+
+impl<R: Read> Read for Decode<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while !self.finished {
+            let input = self.source.fill_buf()?;
+            let last = input.is_empty();
+            let (result, read, written, _) = self.decoder.decode_to_utf8(input, buf, last);
+            self.source.consume(read);
+            self.finished = last && result == CoderResult::InputEmpty;
+            if written > 0 {
+                return Ok(written);
+            }
+            if result == CoderResult::OutputFull {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "buffer too small to decode a character"));
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[test]
+fn test_decode() {
+    let decode = |bytes: &[u8], label| {
+        let mut text = String::new();
+        Decode::new(bytes, parse_encoding(label).unwrap()).read_to_string(&mut text).unwrap();
+        text
+    };
+    assert_eq!("Straße bergauf – 5€", decode(b"Stra\xdfe bergauf \x96 5\x80", "windows-1252"));
+    assert_eq!("Grüße", decode(b"Gr\xfc\xdfe", "latin1"));
+    assert_eq!("Grüße", decode("\u{feff}Grüße".as_bytes(), "latin1"));
+    assert!(parse_encoding("klingon").is_err());
+}
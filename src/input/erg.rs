@@ -0,0 +1,140 @@
+use std::io::{BufRead, BufReader, Read};
+use std::time::Duration;
+
+use super::{InputError, InputReader};
+use crate::{Error, Step};
+
+/// Reads ERG (absolute watts) and MRC (percent of FTP) files
+#[derive(Debug)]
+pub struct ErgReader {
+    /// absolute FTP in watts, used to convert MRC percentages
+    pub ftp: u16,
+}
+
+#[derive(Debug, PartialEq)]
+enum Block {
+    None,
+    Header,
+    Data,
+    Text,
+}
+
+impl InputReader for ErgReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let mut block = Block::None;
+        let mut percent = false;
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        let mut steps = Vec::new();
+
+        for (line, text) in BufReader::new(source).lines().enumerate() {
+            let text = text?;
+            let text = text.trim();
+            let line = line + 1;
+
+            match text.to_uppercase().as_str() {
+                "[COURSE HEADER]" => block = Block::Header,
+                "[COURSE DATA]" => block = Block::Data,
+                "[COURSE TEXT]" => block = Block::Text,
+                end if end.starts_with("[END") => block = Block::None,
+                _ if text.is_empty() || text.starts_with(';') => {},
+                header if block == Block::Header && header.starts_with("MINUTES") => {
+                    percent = header.ends_with("PERCENT");
+                },
+                _ if block == Block::Data => {
+                    let mut columns = text.split_whitespace();
+                    let (Some(minutes), Some(value)) = (columns.next(), columns.next()) else {
+                        return Err(InputError::new(line, None, "expected minutes and power").into());
+                    };
+                    let minutes = minutes.parse::<f64>().map_err(|err| InputError::new(line, Some("minutes"), err.to_string()))?;
+                    let value = value.parse::<f64>().map_err(|err| InputError::new(line, Some("power"), err.to_string()))?;
+                    let watts = if percent { value * self.ftp as f64 / 100.0 } else { value };
+                    points.push((minutes * 60.0, watts));
+                },
+                _ if block == Block::Text => {
+                    let mut columns = text.split('\t');
+                    let (Some(seconds), Some(message)) = (columns.next(), columns.next()) else {
+                        return Err(InputError::new(line, None, "expected seconds and text").into());
+                    };
+                    let seconds = seconds.trim().parse::<f64>().map_err(|err| InputError::new(line, Some("seconds"), err.to_string()))?;
+                    let text_duration = match columns.next().map(str::trim).filter(|duration| !duration.is_empty()) {
+                        Some(duration) => Some(duration.parse::<f64>().ok().filter(|duration| duration.is_finite() && *duration >= 0.0)
+                            .ok_or_else(|| InputError::new(line, Some("duration"), format!("invalid duration \"{}\"", duration)))?.round() as u32),
+                        None => None,
+                    };
+                    steps.push(Step { time: time(seconds), text: Some(message.trim().to_string()), text_duration, ..Step::default() });
+                },
+                _ => {},
+            }
+        }
+
+        // Every segment between two points becomes a step with its average power
+        let mut last_watts = None;
+        for pair in points.windows(2) {
+            let ((start, from), (end, to)) = (pair[0], pair[1]);
+            if end <= start {
+                continue;
+            }
+
+            let watts = ((from + to) / 2.0).round() as u16;
+            if last_watts != Some(watts) {
+                steps.push(Step { time: time(start), watts: Some(watts), ..Step::default() });
+                last_watts = Some(watts);
+            }
+        }
+
+        if last_watts.is_none() {
+            return Err(Error::Parse("no course data found".to_string()));
+        }
+        // the end of the last segment
+        if let Some((end, _)) = points.last() {
+            steps.push(Step { time: time(*end), ..Step::default() });
+        }
+
+        // stable, so power steps stay in front of hints at the same time
+        steps.sort_by_key(|step| step.time);
+        Ok(steps)
+    }
+}
+
+impl ErgReader {
+    pub fn new(ftp: u16) -> Self {
+        ErgReader { ftp }
+    }
+}
+
+fn time(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds).unwrap_or_default()
+}
+
+#[test]
+fn test_read() {
+    let mrc = "\
+[COURSE HEADER]
+VERSION = 2
+UNITS = ENGLISH
+MINUTES PERCENT
+[END COURSE HEADER]
+[COURSE DATA]
+0.00\t50
+1.50\t50
+1.50\t100
+2.00\t100
+2.00\t100
+3.00\t100
+[END COURSE DATA]
+[COURSE TEXT]
+100\tGo!\t10
+[END COURSE TEXT]
+";
+
+    let steps = ErgReader::new(200).read(&mut mrc.as_bytes()).unwrap();
+    assert_eq!(4, steps.len());
+    assert_eq!(Some(100), steps[0].watts);
+    assert_eq!(Some(200), steps[1].watts);
+    assert_eq!(Duration::from_secs(90), steps[1].time);
+    assert_eq!((Some("Go!".to_string()), Some(10)), (steps[2].text.clone(), steps[2].text_duration));
+    assert_eq!((Duration::from_secs(180), None), (steps[3].time, steps[3].watts));
+
+    let sections = crate::Converter::new(200).translate(steps);
+    assert_eq!(180, sections.iter().map(|sec| sec.start + sec.duration).max().unwrap());
+}
@@ -0,0 +1,59 @@
+use std::io::Read;
+
+use super::{average, InputReader};
+use crate::fit::{decode, FIELD_TIMESTAMP, MESG_RECORD};
+use crate::{Error, Step};
+
+const FIELD_POWER: u8 = 7;
+
+/// Reads the power stream of recorded FIT activities
+#[derive(Debug)]
+pub struct FitReader {
+    /// averaging interval in seconds
+    pub interval: u32,
+}
+
+impl InputReader for FitReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        let samples: Vec<(u32, u16)> = decode(&data)?
+            .iter()
+            .filter(|message| message.global == MESG_RECORD)
+            .filter_map(|message| Some((message.u32(FIELD_TIMESTAMP)?, message.u16(FIELD_POWER)?)))
+            .collect();
+
+        let Some(&(start, _)) = samples.first() else {
+            return Err(Error::Parse("no power data found in FIT file".to_string()));
+        };
+
+        let samples: Vec<(u32, u16)> = samples.into_iter()
+            .map(|(timestamp, watts)| (timestamp.saturating_sub(start), watts))
+            .collect();
+
+        Ok(average(&samples, self.interval))
+    }
+}
+
+impl FitReader {
+    pub fn new(interval: u32) -> Self {
+        FitReader { interval }
+    }
+}
+
+#[test]
+fn test_read() {
+    use crate::fit::{Encoder, Value};
+
+    let mut encoder = Encoder::new();
+    for (second, watts) in [(0, 100), (1, 200), (2, 300), (3, 310), (6, 50)] {
+        encoder.message(MESG_RECORD, &[(FIELD_TIMESTAMP, Value::UInt32(1000 + second)), (FIELD_POWER, Value::UInt16(watts))]);
+    }
+    let data = encoder.finish();
+
+    let steps = FitReader::new(2).read(&mut &data[..]).unwrap();
+    let watts: Vec<Option<u16>> = steps.iter().map(|step| step.watts).collect();
+    assert_eq!(vec![Some(150), Some(305), Some(50)], watts);
+    assert_eq!(6, steps[2].time.as_secs());
+}
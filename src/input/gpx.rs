@@ -0,0 +1,170 @@
+use std::io::{BufReader, Read};
+
+use chrono::DateTime;
+use quick_xml::events::Event;
+
+use super::{average, InputReader};
+use crate::{Error, Step};
+
+pub const DEFAULT_WEIGHT: f64 = 80.0;
+pub const DEFAULT_CDA: f64 = 0.32;
+pub const DEFAULT_CRR: f64 = 0.005;
+
+const GRAVITY: f64 = 9.81;
+const AIR_DENSITY: f64 = 1.225;
+const DRIVETRAIN_EFFICIENCY: f64 = 0.975;
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Reads GPX tracks and derives the power needed to ride them from gradient and speed
+#[derive(Debug)]
+pub struct GpxReader {
+    /// averaging interval in seconds
+    pub interval: u32,
+    /// total mass of rider and bike in kg
+    pub weight: f64,
+    /// aerodynamic drag area in m²
+    pub cda: f64,
+    /// rolling resistance coefficient
+    pub crr: f64,
+}
+
+#[derive(Debug, Default)]
+struct Point {
+    lat: f64,
+    lon: f64,
+    ele: Option<f64>,
+    /// milliseconds since the epoch
+    time: Option<i64>,
+}
+
+impl InputReader for GpxReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let points = self.read_points(source)?;
+
+        let Some(start) = points.first().and_then(|point| point.time) else {
+            return Err(Error::Parse("GPX track needs points with timestamps".to_string()));
+        };
+
+        let mut samples = Vec::with_capacity(points.len());
+        for pair in points.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            let (Some(from_time), Some(to_time)) = (from.time, to.time) else {
+                continue;
+            };
+            if to_time <= from_time {
+                continue;
+            }
+
+            let distance = distance(from, to);
+            let climb = match (from.ele, to.ele) {
+                (Some(from), Some(to)) => to - from,
+                _ => 0.0,
+            };
+            let speed = distance / ((to_time - from_time) as f64 / 1000.0);
+            let gradient = if distance > 0.0 { climb / distance } else { 0.0 };
+
+            let watts = self.power(speed, gradient).round() as u16;
+            samples.push((((to_time - start) / 1000) as u32, watts));
+        }
+
+        if samples.is_empty() {
+            return Err(Error::Parse("GPX track has too few points".to_string()));
+        }
+
+        Ok(average(&samples, self.interval))
+    }
+}
+
+impl GpxReader {
+    pub fn new(interval: u32) -> Self {
+        GpxReader { interval, weight: DEFAULT_WEIGHT, cda: DEFAULT_CDA, crr: DEFAULT_CRR }
+    }
+
+    /// Power in watts needed to hold the speed (m/s) on the gradient, never negative
+    fn power(&self, speed: f64, gradient: f64) -> f64 {
+        let angle = gradient.atan();
+        let gravity = self.weight * GRAVITY * angle.sin();
+        let rolling = self.weight * GRAVITY * angle.cos() * self.crr;
+        let drag = 0.5 * AIR_DENSITY * self.cda * speed * speed;
+
+        ((gravity + rolling + drag) * speed / DRIVETRAIN_EFFICIENCY).max(0.0)
+    }
+
+    fn read_points(&self, source: &mut dyn Read) -> Result<Vec<Point>, Error> {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(source));
+        reader.config_mut().trim_text(true);
+
+        let mut points = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+        let mut current: Option<Point> = None;
+
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                    if name == "trkpt" {
+                        let mut point = Point::default();
+                        for attr in e.attributes() {
+                            let attr = attr?;
+                            let value = attr.unescape_value()?;
+                            match attr.key.local_name().as_ref() {
+                                b"lat" => point.lat = value.parse()?,
+                                b"lon" => point.lon = value.parse()?,
+                                _ => {},
+                            }
+                        }
+                        current = Some(point);
+                    }
+                    path.push(name);
+                },
+                Event::End(_) => {
+                    let name = path.pop();
+                    if name.as_deref() == Some("trkpt") {
+                        points.extend(current.take());
+                    }
+                },
+                Event::Text(t) => {
+                    let (Some(point), Some(name)) = (&mut current, path.last()) else {
+                        continue;
+                    };
+                    let text = t.unescape()?.into_owned();
+                    match name as &str {
+                        "ele" => point.ele = Some(text.parse()
+                            .map_err(|err| Error::Parse(format!("invalid elevation \"{}\" at byte {}: {}", text, reader.buffer_position(), err)))?),
+                        "time" => point.time = Some(DateTime::parse_from_rfc3339(&text)
+                            .map_err(|err| Error::Parse(format!("invalid time \"{}\" at byte {}: {}", text, reader.buffer_position(), err)))?
+                            .timestamp_millis()),
+                        _ => {},
+                    }
+                },
+                Event::Eof => break,
+                _ => {},
+            }
+            buf.clear();
+        }
+
+        Ok(points)
+    }
+}
+
+/// Great circle distance in meters
+fn distance(from: &Point, to: &Point) -> f64 {
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (to.lon - from.lon).to_radians();
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+#[test]
+fn test_power() {
+    let reader = GpxReader::new(30);
+    // flat at 36 km/h
+    assert_eq!(241, reader.power(10.0, 0.0).round() as u32);
+    // 5% climb at 18 km/h
+    assert_eq!(246, reader.power(5.0, 0.05).round() as u32);
+    // steep descent doesn't produce negative power
+    assert_eq!(0.0, reader.power(15.0, -0.1));
+}
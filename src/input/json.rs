@@ -0,0 +1,119 @@
+use std::io::Read;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{apply_time_mode, InputError, InputReader, Metadata, TimeFormat, TimeMode};
+use crate::duration::format_time;
+use crate::{Error, Step};
+
+/// Reads a JSON array of `{time, power, text}` steps,
+/// or an object with metadata and such an array as `steps`
+#[derive(Debug)]
+pub struct JsonReader {
+    pub time_mode: TimeMode,
+    /// absolute FTP in watts, to adapt power of documents written for a different FTP
+    pub ftp: u16,
+    /// sort steps by time instead of failing on steps before the previous one, in time mode
+    pub sort: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Document {
+    Steps(Vec<Record>),
+    Workout {
+        name: Option<String>,
+        description: Option<String>,
+        author: Option<String>,
+        /// absolute FTP in watts the power values are based on
+        ftp: Option<u16>,
+        steps: Vec<Record>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    time: Time,
+    power: Option<u16>,
+    text: Option<String>,
+}
+
+/// Time as "hh:mm:ss" or in seconds
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Time {
+    Seconds(f64),
+    Text(String),
+}
+
+impl InputReader for JsonReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        Ok(self.read_with_metadata(source)?.0)
+    }
+
+    fn read_with_metadata(&self, source: &mut dyn Read) -> Result<(Vec<Step>, Metadata), Error> {
+        let document: Document = serde_json::from_reader(source)?;
+
+        let (records, metadata, ftp) = match document {
+            Document::Steps(records) => (records, Metadata::default(), None),
+            Document::Workout { name, description, author, ftp, steps } => (steps, Metadata { name, description, author, ..Metadata::default() }, ftp),
+        };
+
+        let mut steps = Vec::with_capacity(records.len());
+        for (index, record) in records.into_iter().enumerate() {
+            let time = match &record.time {
+                Time::Seconds(seconds) => Duration::try_from_secs_f64(*seconds).ok(),
+                Time::Text(text) => TimeFormat::Auto.parse(text).ok(),
+            }.ok_or_else(|| InputError::new(index + 1, Some("time"), format!("invalid time {:?}", record.time)))?;
+
+            let watts = match (record.power, ftp) {
+                (Some(watts), Some(ftp)) if ftp > 0 => Some((watts as f64 * self.ftp as f64 / ftp as f64).round() as u16),
+                (watts, _) => watts,
+            };
+
+            steps.push(Step { time, watts, text: record.text, ..Step::default() });
+        }
+
+        if self.sort && self.time_mode == TimeMode::Time {
+            // stable, so texts stay behind the power of the same time
+            steps.sort_by_key(|step| step.time);
+        }
+        if let Err((index, last_time)) = apply_time_mode(&mut steps, self.time_mode) {
+            let msg = format!("time {} is before last time {}, use --sort-input to sort rows by time", format_time(steps[index].time), format_time(last_time));
+            return Err(InputError::new(index + 1, Some("time"), msg).into());
+        }
+
+        Ok((steps, metadata))
+    }
+}
+
+impl JsonReader {
+    pub fn new(time_mode: TimeMode, ftp: u16) -> Self {
+        JsonReader { time_mode, ftp, sort: false }
+    }
+}
+
+#[test]
+fn test_read() {
+    let json = r#"{
+        "name": "Test",
+        "ftp": 200,
+        "steps": [
+            {"time": "00:00:00", "power": 100},
+            {"time": 90, "text": "Turn right"},
+            {"time": 120, "power": 200, "text": "Up that hill"}
+        ]
+    }"#;
+
+    let (steps, metadata) = JsonReader::new(TimeMode::Time, 300).read_with_metadata(&mut json.as_bytes()).unwrap();
+    assert_eq!(Some("Test".to_string()), metadata.name);
+    assert_eq!(3, steps.len());
+    assert_eq!(Some(150), steps[0].watts);
+    assert_eq!(Duration::from_secs(90), steps[1].time);
+    assert_eq!(Some(300), steps[2].watts);
+
+    let steps = JsonReader::new(TimeMode::Duration, 300).read(&mut r#"[{"time": 60, "power": 100}, {"time": 60, "power": 200}]"#.as_bytes()).unwrap();
+    assert_eq!(Duration::from_secs(60), steps[1].time);
+    assert_eq!(Some(100), steps[0].watts);
+}
@@ -0,0 +1,851 @@
+//! Readers turning various input formats into [`Step`]s
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{naive::NaiveTime, Timelike};
+
+use crate::duration::{format_duration, format_time};
+use crate::{parse_duration, DurationType, Error, Hint, Step, ZoneModel};
+
+mod csv;
+mod encoding;
+mod erg;
+mod fit;
+mod gpx;
+mod json;
+mod tcx;
+mod xlsx;
+mod yaml;
+mod zwo;
+
+pub use self::csv::{write_csv, Columns, CsvReader, Delimiter};
+pub use self::encoding::parse_encoding;
+pub use self::erg::ErgReader;
+pub use self::fit::FitReader;
+pub use self::gpx::{GpxReader, DEFAULT_CDA, DEFAULT_CRR, DEFAULT_WEIGHT};
+pub use self::json::JsonReader;
+pub use self::tcx::TcxReader;
+pub use self::xlsx::XlsxReader;
+pub use self::yaml::YamlReader;
+pub use self::zwo::ZwoReader;
+
+/// How to interpret the time column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeMode {
+    /// absolute time since the start of the course
+    Time,
+    /// duration of the step
+    Duration,
+}
+
+/// How the time column is written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// detected per value: "1:02:03", "5:00" as minutes and seconds, or plain seconds like "300"
+    #[default]
+    Auto,
+    /// "h:mm:ss"
+    Hms,
+    /// "m:ss", minutes may exceed 59
+    Ms,
+    /// plain seconds
+    Seconds,
+    /// chrono format like "%H:%M:%S%.f"
+    Custom(String),
+}
+
+impl TimeFormat {
+    /// Parses the time, seconds may be fractional like "1:02.5"
+    pub fn parse(&self, text: &str) -> Result<Duration, String> {
+        let parts = match self {
+            TimeFormat::Custom(format) => {
+                let time = NaiveTime::parse_from_str(text, format).map_err(|err| err.to_string())?;
+                return Ok(Duration::new(time.num_seconds_from_midnight() as u64, time.nanosecond()));
+            },
+            TimeFormat::Auto => 1..=3,
+            TimeFormat::Hms => 3..=3,
+            TimeFormat::Ms => 2..=2,
+            TimeFormat::Seconds => 1..=1,
+        };
+
+        let fields: Vec<&str> = text.split(':').collect();
+        if fields.len() < *parts.start() {
+            return Err("premature end of input".to_string());
+        }
+        if fields.len() > *parts.end() {
+            return Err("trailing input".to_string());
+        }
+        let (last, leading) = fields.split_last().unwrap_or((&"", &[]));
+        let invalid = || "input contains invalid characters".to_string();
+        let out_of_range = || "input is out of range".to_string();
+        let digits = |field: &str| !field.is_empty() && field.bytes().all(|c| c.is_ascii_digit());
+
+        let mut seconds: u64 = 0;
+        for (index, field) in leading.iter().enumerate() {
+            let value: u64 = field.parse().ok().filter(|_| digits(field)).ok_or_else(invalid)?;
+            // only the first field may exceed the range of its unit
+            if index > 0 && value >= 60 {
+                return Err(out_of_range());
+            }
+            seconds = seconds.checked_mul(60).and_then(|seconds| seconds.checked_add(value)).ok_or_else(out_of_range)?;
+        }
+        let (whole, fraction) = last.split_once('.').unwrap_or((last, ""));
+        if !digits(whole) || !(fraction.is_empty() || digits(fraction)) || last.ends_with('.') {
+            return Err(invalid());
+        }
+        let value: u64 = whole.parse().map_err(|_| invalid())?;
+        if !leading.is_empty() && value >= 60 {
+            return Err(out_of_range());
+        }
+        let nanos = format!("{:0<9}", fraction).get(..9).unwrap_or_default().parse().unwrap_or(0);
+        // section offsets are held in u32 seconds
+        let seconds = seconds.checked_mul(60).and_then(|seconds| seconds.checked_add(value))
+            .filter(|seconds| *seconds <= u32::MAX as u64).ok_or_else(out_of_range)?;
+        Ok(Duration::new(seconds, nanos))
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(TimeFormat::Auto),
+            "hms" | "h:mm:ss" => Ok(TimeFormat::Hms),
+            "ms" | "m:ss" => Ok(TimeFormat::Ms),
+            "seconds" => Ok(TimeFormat::Seconds),
+            _ if s.contains('%') => Ok(TimeFormat::Custom(s.to_string())),
+            _ => Err(format!("unknown time format \"{}\", expected \"auto\", \"hms\", \"ms\", \"seconds\" or a format like \"%H:%M:%S\"", s)),
+        }
+    }
+}
+
+impl Display for TimeFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TimeFormat::Auto => write!(f, "auto"),
+            TimeFormat::Hms => write!(f, "hms"),
+            TimeFormat::Ms => write!(f, "ms"),
+            TimeFormat::Seconds => write!(f, "seconds"),
+            TimeFormat::Custom(format) => write!(f, "{}", format),
+        }
+    }
+}
+
+/// How to interpret the power column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PowerUnit {
+    /// absolute watts, values like "75%" are relative to FTP nonetheless
+    #[default]
+    Watts,
+    /// percent of FTP
+    Percent,
+}
+
+impl FromStr for PowerUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "watts" => Ok(PowerUnit::Watts),
+            "percent" => Ok(PowerUnit::Percent),
+            _ => Err(format!("unknown power unit \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for PowerUnit {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PowerUnit::Watts => write!(f, "watts"),
+            PowerUnit::Percent => write!(f, "percent"),
+        }
+    }
+}
+
+/// Parses a power value into watts, converting percentages and zone labels with the given FTP.
+/// Values which are no number or zone mean there is no power, e.g. for text only rows.
+/// Fails on more watts than a workout can hold.
+fn parse_power(text: &str, unit: PowerUnit, ftp: u16, zones: &ZoneModel) -> Result<Option<u16>, String> {
+    let text = text.trim();
+    if let Some(zone) = zones.find(text) {
+        return watts(zone.target() * ftp as f64, text).map(Some);
+    }
+
+    let (number, percent) = match text.strip_suffix('%') {
+        Some(number) => (number.trim(), true),
+        None => (text, unit == PowerUnit::Percent),
+    };
+
+    let Some(value) = number.parse().ok().filter(|value: &f64| value.is_finite() && *value >= 0.0) else {
+        return Ok(None);
+    };
+    watts(if percent { value / 100.0 * ftp as f64 } else { value }, text).map(Some)
+}
+
+/// Rounds power to watts, failing instead of saturating on too much power
+fn watts(power: f64, text: &str) -> Result<u16, String> {
+    let watts = power.round();
+    match watts <= u16::MAX as f64 {
+        true => Ok(watts as u16),
+        false => Err(format!("power \"{}\" is more than {} watts", text, u16::MAX)),
+    }
+}
+
+/// Splits a display duration like "@15s" off the end of a text
+fn split_text_duration(text: &str) -> (String, Option<u32>) {
+    match text.rsplit_once('@').map(|(message, duration)| (message, parse_duration(duration))) {
+        Some((message, Ok(duration))) => (message.trim_end().to_string(), Some(duration)),
+        _ => (text.to_string(), None),
+    }
+}
+
+/// Parses the messages of a text cell separated by "|", each with an optional offset relative
+/// to the row in front like "+10s Go!" and an optional display duration like "Go!@5s"
+fn parse_messages(cell: &str) -> Vec<Hint> {
+    cell.split('|')
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .map(|message| {
+            let offset = message.strip_prefix('+')
+                .and_then(|rest| rest.split_once(' '))
+                .and_then(|(offset, rest)| parse_duration(offset).ok().map(|offset| (offset, rest.trim_start())));
+            let (offset, message) = offset.unwrap_or((0, message));
+            let (text, duration) = split_text_duration(message);
+            Hint { offset, text, duration }
+        })
+        .collect()
+}
+
+/// A problem in a line of the input, with the column if known
+#[derive(Debug, PartialEq)]
+pub struct InputError {
+    pub line: usize,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl InputError {
+    pub fn new(line: usize, column: Option<&str>, message: impl Into<String>) -> Self {
+        InputError { line, column: column.map(str::to_string), message: message.into() }
+    }
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Error in line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Workout details some input formats carry along with their steps
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// whether the step times are seconds or meters
+    pub duration_type: DurationType,
+}
+
+/// Steps read one by one, ordered by time
+pub type Steps<'a> = Box<dyn Iterator<Item = Result<Step, Error>> + 'a>;
+
+/// A source format that can be translated into a course
+pub trait InputReader {
+    /// Reads all steps from the given source, ordered by time
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error>;
+
+    /// Reads all steps like [`InputReader::read`], plus the metadata the format provides
+    fn read_with_metadata(&self, source: &mut dyn Read) -> Result<(Vec<Step>, Metadata), Error> {
+        Ok((self.read(source)?, Metadata::default()))
+    }
+
+    /// Reads the steps one by one, formats which can't stream read them all upfront
+    fn stream<'a>(&'a self, source: &'a mut dyn Read) -> Result<(Steps<'a>, Metadata), Error> {
+        let (steps, metadata) = self.read_with_metadata(source)?;
+        Ok((Box::new(steps.into_iter().map(Ok)), metadata))
+    }
+}
+
+/// Available input formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// table with time, power and text columns
+    Csv,
+    /// recorded FIT activity
+    Fit,
+    /// TCX activity or course
+    Tcx,
+    /// GPX track, converted with a physics model
+    Gpx,
+    /// ERG file with absolute watts
+    Erg,
+    /// MRC file with power relative to FTP
+    Mrc,
+    /// Zwift workout file
+    Zwo,
+    /// JSON array of steps, optionally with metadata
+    Json,
+    /// hand-written YAML workout
+    Yaml,
+    /// Excel workbook with the same columns as CSV
+    Xlsx,
+}
+
+impl InputFormat {
+    /// All formats, e.g. to list them
+    pub fn all() -> &'static [InputFormat] {
+        &[InputFormat::Csv, InputFormat::Fit, InputFormat::Tcx, InputFormat::Gpx, InputFormat::Erg, InputFormat::Mrc, InputFormat::Zwo, InputFormat::Json, InputFormat::Yaml, InputFormat::Xlsx]
+    }
+
+    /// Name as given on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputFormat::Csv => "csv",
+            InputFormat::Fit => "fit",
+            InputFormat::Tcx => "tcx",
+            InputFormat::Gpx => "gpx",
+            InputFormat::Erg => "erg",
+            InputFormat::Mrc => "mrc",
+            InputFormat::Zwo => "zwo",
+            InputFormat::Json => "json",
+            InputFormat::Yaml => "yaml",
+            InputFormat::Xlsx => "xlsx",
+        }
+    }
+
+    /// Detects the format by file extension, falling back to CSV
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match &extension as &str {
+            "fit" => InputFormat::Fit,
+            "tcx" => InputFormat::Tcx,
+            "gpx" => InputFormat::Gpx,
+            "erg" => InputFormat::Erg,
+            "mrc" => InputFormat::Mrc,
+            "zwo" => InputFormat::Zwo,
+            "json" => InputFormat::Json,
+            "yaml" | "yml" => InputFormat::Yaml,
+            "xlsx" => InputFormat::Xlsx,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "fit" => Ok(InputFormat::Fit),
+            "tcx" => Ok(InputFormat::Tcx),
+            "gpx" => Ok(InputFormat::Gpx),
+            "erg" => Ok(InputFormat::Erg),
+            "mrc" => Ok(InputFormat::Mrc),
+            "zwo" => Ok(InputFormat::Zwo),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            "xlsx" => Ok(InputFormat::Xlsx),
+            _ => Err(format!("unknown input format \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Places steps on the timeline according to the time mode.
+/// Fails with the index of the first step going back in time and the time before it.
+fn apply_time_mode(steps: &mut [Step], time_mode: TimeMode) -> Result<(), (usize, Duration)> {
+    let mut timeline = Timeline::new(time_mode);
+    for (index, step) in steps.iter_mut().enumerate() {
+        timeline.place(step).map_err(|last_time| (index, last_time))?;
+    }
+    Ok(())
+}
+
+/// Applies the time mode to steps one at a time, for readers streaming their rows
+struct Timeline {
+    time_mode: TimeMode,
+    last_time: Duration,
+}
+
+impl Timeline {
+    fn new(time_mode: TimeMode) -> Self {
+        Timeline { time_mode, last_time: Duration::ZERO }
+    }
+
+    /// Turns a duration into the start time of the step, fails with the last time if the step is before it
+    fn place(&mut self, step: &mut Step) -> Result<(), Duration> {
+        if self.time_mode == TimeMode::Duration {
+            let new_end = self.last_time + step.time;
+
+            step.time = self.last_time;
+            self.last_time = new_end;
+        } else {
+            // check if time is monotonic ascending
+            if step.time < self.last_time {
+                return Err(self.last_time);
+            }
+            self.last_time = step.time;
+        }
+        Ok(())
+    }
+}
+
+/// What to do with rows sharing the time of the previous row
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// later rows override the fields they fill in
+    Last,
+    /// the first row wins, later rows only fill in fields it leaves empty
+    First,
+    /// like last, but the texts of all rows are shown
+    #[default]
+    MergeText,
+    /// fail on rows sharing a time
+    Error,
+}
+
+impl Duplicates {
+    /// Combines a step with the following one of the same time
+    fn merge(self, step: &mut Step, next: Step) {
+        let later = self != Duplicates::First;
+        pick(&mut step.watts, next.watts, later);
+        pick(&mut step.cadence, next.cadence, later);
+        pick(&mut step.raster, next.raster, later);
+        step.key |= next.key;
+        match next.text {
+            Some(text) if self == Duplicates::MergeText && step.text.is_some() => {
+                step.hints.push(Hint { offset: 0, text, duration: next.text_duration });
+            },
+            Some(text) if later || step.text.is_none() => {
+                step.text = Some(text);
+                step.text_duration = next.text_duration;
+            },
+            _ => {},
+        }
+        match self {
+            Duplicates::MergeText => step.hints.extend(next.hints),
+            _ if !next.hints.is_empty() && (later || step.hints.is_empty()) => step.hints = next.hints,
+            _ => {},
+        }
+    }
+}
+
+/// Sets the field to the other value if given, replacing a value of its own only if preferred
+fn pick<T>(field: &mut Option<T>, other: Option<T>, prefer_other: bool) {
+    if other.is_some() && (prefer_other || field.is_none()) {
+        *field = other;
+    }
+}
+
+impl FromStr for Duplicates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(Duplicates::Last),
+            "first" => Ok(Duplicates::First),
+            "merge-text" => Ok(Duplicates::MergeText),
+            "error" => Ok(Duplicates::Error),
+            _ => Err(format!("unknown duplicate policy \"{}\", expected \"last\", \"first\", \"merge-text\" or \"error\"", s)),
+        }
+    }
+}
+
+impl Display for Duplicates {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Duplicates::Last => write!(f, "last"),
+            Duplicates::First => write!(f, "first"),
+            Duplicates::MergeText => write!(f, "merge-text"),
+            Duplicates::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Combines consecutive steps sharing a time into one by the policy, like a row with power and
+/// another one with text
+pub fn merge_duplicates(steps: Steps<'_>, policy: Duplicates) -> Steps<'_> {
+    let mut steps = steps.enumerate().peekable();
+    Box::new(std::iter::from_fn(move || {
+        let mut step = match steps.next()? {
+            (_, Ok(step)) => step,
+            (_, Err(err)) => return Some(Err(err)),
+        };
+        while let Some((index, Ok(next))) = steps.next_if(|(_, next)| matches!(next, Ok(next) if next.time == step.time)) {
+            if policy == Duplicates::Error {
+                let msg = format!("time {} is the time of the previous row as well", format_time(next.time));
+                return Some(Err(InputError::new(index + 1, Some("time"), msg).into()));
+            }
+            policy.merge(&mut step, next);
+        }
+        Some(Ok(step))
+    }))
+}
+
+/// How power is smoothed over the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// moving average
+    #[default]
+    Mean,
+    /// moving median, which keeps sharp changes of power
+    Median,
+}
+
+impl Filter {
+    fn apply(self, watts: &mut [u16]) -> u16 {
+        match self {
+            Filter::Mean => (watts.iter().map(|watts| *watts as f64).sum::<f64>() / watts.len() as f64).round() as u16,
+            Filter::Median => {
+                watts.sort_unstable();
+                let mid = watts.len() / 2;
+                match watts.len() % 2 {
+                    0 => (watts[mid - 1] as u32 + watts[mid] as u32).div_ceil(2) as u16,
+                    _ => watts[mid],
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Filter::Mean),
+            "median" => Ok(Filter::Median),
+            _ => Err(format!("unknown filter \"{}\", expected \"mean\" or \"median\"", s)),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Filter::Mean => write!(f, "mean"),
+            Filter::Median => write!(f, "median"),
+        }
+    }
+}
+
+/// Smooths the power of the steps over a window of the given seconds centered on each step.
+/// Only steps within half the window ahead are read in advance.
+pub fn smooth(steps: Steps<'_>, window: u32, filter: Filter) -> Steps<'_> {
+    let half = Duration::from_secs(window as u64) / 2;
+    let mut steps = steps.fuse();
+    let mut pending: VecDeque<Step> = VecDeque::new();
+    let mut samples: VecDeque<(Duration, u16)> = VecDeque::new();
+    Box::new(std::iter::from_fn(move || {
+        // read until the window of the first pending step is complete
+        while !matches!((pending.front(), pending.back()), (Some(first), Some(last)) if last.time > first.time + half) {
+            match steps.next() {
+                Some(Ok(step)) => {
+                    if let Some(watts) = step.watts {
+                        samples.push_back((step.time, watts));
+                    }
+                    pending.push_back(step);
+                },
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+
+        let mut step = pending.pop_front()?;
+        while samples.front().is_some_and(|(time, _)| *time + half < step.time) {
+            samples.pop_front();
+        }
+        if step.watts.is_some() {
+            let mut watts: Vec<u16> = samples.iter().filter(|(time, _)| *time <= step.time + half).map(|(_, watts)| *watts).collect();
+            step.watts = Some(filter.apply(&mut watts));
+        }
+        Some(Ok(step))
+    }))
+}
+
+/// Drops the given seconds at the start and the end of the steps, like the warmup and cooldown of a recorded ride.
+/// The row in effect at the new start moves there without its texts, a row without power marks the new end,
+/// and the times are shifted to start at zero. Only the rows within the trimmed end of the latest one are held back.
+pub fn trim(steps: Steps<'_>, start: u32, end: u32) -> Steps<'_> {
+    let (from, margin) = (Duration::from_secs(start as u64), Duration::from_secs(end as u64));
+    let mut steps = steps.fuse();
+    let mut current: Option<Step> = None;
+    let mut pending: VecDeque<Step> = VecDeque::new();
+    let mut latest = Duration::ZERO;
+    let mut done = false;
+    Box::new(std::iter::from_fn(move || loop {
+        // rows earlier than the trimmed end of the latest row stay in any case
+        if pending.front().is_some_and(|step| end == 0 || step.time + margin < latest) {
+            let step = pending.pop_front()?;
+            return Some(Ok(Step { time: step.time - from, ..step }));
+        }
+        if done {
+            return None;
+        }
+        match steps.next() {
+            Some(Ok(step)) => {
+                latest = latest.max(step.time);
+                if step.time < from {
+                    current = Some(step);
+                    continue;
+                }
+                if let Some(first) = current.take().filter(|_| step.time > from) {
+                    pending.push_back(Step { time: from, text: None, text_duration: None, hints: vec![], ..first });
+                }
+                pending.push_back(step);
+            },
+            Some(Err(err)) => return Some(Err(err)),
+            None => {
+                done = true;
+                let to = latest.saturating_sub(margin);
+                if to <= from {
+                    return Some(Err(Error::Validation(format!("trimming {} leaves nothing of {}", format_duration(start + end), format_time(latest)))));
+                }
+                if end > 0 {
+                    // the rows held back all fall into the trimmed end
+                    pending.clear();
+                    return Some(Ok(Step { time: to - from, ..Step::default() }));
+                }
+            },
+        }
+    }))
+}
+
+/// Shortest segment found by [`segment`] in seconds, unless given
+pub const DEFAULT_SEGMENT_LENGTH: u32 = 30;
+
+/// Finds the natural interval boundaries of dense recorded power by change-point detection.
+/// Runs of powered rows are split by binary segmentation where the mean power changes by at
+/// least the given watts, into segments of at least the given seconds. Every segment becomes
+/// one step at its mean power, showing the texts of its rows as hints.
+/// Rows without power, key rows and the final row keep their own boundaries.
+/// As the segmentation looks at whole runs, all steps are read upfront.
+pub fn segment(steps: Steps<'_>, threshold: u16, min_length: u32) -> Steps<'_> {
+    let steps = match steps.collect::<Result<Vec<_>, _>>() {
+        Ok(steps) => steps,
+        Err(err) => return Box::new(std::iter::once(Err(err))),
+    };
+    // weights are the seconds until the next row, the final row only marks the end
+    let weights: Vec<f64> = steps.windows(2).map(|pair| pair[1].time.saturating_sub(pair[0].time).as_secs_f64()).chain([0.0]).collect();
+
+    let mut starts = vec![];
+    let mut run = 0;
+    for index in 0..steps.len() {
+        let boundary = index == 0
+            || index + 1 == steps.len()
+            || steps[index].watts.is_none()
+            || steps[index - 1].watts.is_none()
+            || steps[index].key != steps[index - 1].key;
+        if boundary {
+            if index > run {
+                split(&steps, &weights, run, index, threshold as f64, min_length as f64, &mut starts);
+            }
+            starts.push(index);
+            run = index;
+        }
+    }
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut segments = vec![];
+    let mut steps = steps.into_iter().zip(weights).enumerate().peekable();
+    for (position, start) in starts.iter().enumerate() {
+        let end = starts.get(position + 1).copied().unwrap_or(usize::MAX);
+        let Some((_, (mut first, weight))) = steps.next() else { break };
+        let (mut total, mut power, mut cadence, mut cadence_weight) = (weight, first.watts.map_or(0.0, |watts| watts as f64 * weight), 0.0, 0.0);
+        if let Some(rpm) = first.cadence {
+            cadence += rpm as f64 * weight;
+            cadence_weight += weight;
+        }
+        while let Some((_, (step, weight))) = steps.next_if(|(index, _)| *index < end) {
+            total += weight;
+            power += step.watts.unwrap_or(0) as f64 * weight;
+            if let Some(rpm) = step.cadence {
+                cadence += rpm as f64 * weight;
+                cadence_weight += weight;
+            }
+            let offset = step.time.saturating_sub(first.time).as_secs_f64().round() as u32;
+            if let Some(text) = step.text {
+                first.hints.push(Hint { offset, text, duration: step.text_duration });
+            }
+            first.hints.extend(step.hints.into_iter().map(|hint| Hint { offset: offset + hint.offset, ..hint }));
+        }
+        if end > *start + 1 && total > 0.0 {
+            first.watts = first.watts.map(|_| (power / total).round() as u16);
+            if cadence_weight > 0.0 {
+                first.cadence = Some((cadence / cadence_weight).round() as u16);
+            }
+        }
+        segments.push(Ok(first));
+    }
+    Box::new(segments.into_iter())
+}
+
+/// Recursively adds the best change points of the rows from..to by binary segmentation
+fn split(steps: &[Step], weights: &[f64], from: usize, to: usize, threshold: f64, min_length: f64, starts: &mut Vec<usize>) {
+    let watts = |index: usize| steps[index].watts.unwrap_or(0) as f64;
+    let total: f64 = weights[from..to].iter().sum();
+    let sum: f64 = (from..to).map(|index| watts(index) * weights[index]).sum();
+
+    let mut best: Option<(usize, f64, f64)> = None;
+    let (mut left, mut left_sum) = (0.0, 0.0);
+    for index in from + 1..to {
+        left += weights[index - 1];
+        left_sum += watts(index - 1) * weights[index - 1];
+        let right = total - left;
+        if left < min_length || right < min_length {
+            continue;
+        }
+        let change = left_sum / left - (sum - left_sum) / right;
+        // reduction of the squared error by splitting here
+        let gain = left * right / total * change * change;
+        if best.is_none_or(|(_, best, _)| gain > best) {
+            best = Some((index, gain, change.abs()));
+        }
+    }
+
+    if let Some((index, _, _)) = best.filter(|(_, _, change)| *change >= threshold) {
+        starts.push(index);
+        split(steps, weights, from, index, threshold, min_length, starts);
+        split(steps, weights, index, to, threshold, min_length, starts);
+    }
+}
+
+/// Averages power samples (seconds since start, watts) over intervals of the given length.
+/// Intervals without samples don't produce a step.
+fn average(samples: &[(u32, u16)], interval: u32) -> Vec<Step> {
+    let interval = interval.max(1);
+    let mut buckets: BTreeMap<u32, (u32, u32)> = BTreeMap::new();
+    for (seconds, watts) in samples {
+        let entry = buckets.entry(seconds / interval).or_insert((0, 0));
+        entry.0 += *watts as u32;
+        entry.1 += 1;
+    }
+
+    let mut steps = Vec::with_capacity(buckets.len());
+    for (bucket, (sum, count)) in buckets {
+        let time = Duration::from_secs((bucket * interval) as u64);
+        let watts = (sum as f64 / count as f64).round() as u16;
+        steps.push(Step { time, watts: Some(watts), ..Step::default() });
+    }
+
+    steps
+}
+
+#[test]
+fn test_parse_power() {
+    let zones = ZoneModel::coggan();
+    assert_eq!(Ok(Some(180)), parse_power("180", PowerUnit::Watts, 250, &zones));
+    assert_eq!(Ok(Some(188)), parse_power("75%", PowerUnit::Watts, 250, &zones));
+    assert_eq!(Ok(Some(188)), parse_power("75", PowerUnit::Percent, 250, &zones));
+    assert_eq!(Ok(Some(164)), parse_power("z2", PowerUnit::Watts, 250, &zones));
+    assert_eq!(Ok(None), parse_power("", PowerUnit::Watts, 250, &zones));
+    assert_eq!(Ok(None), parse_power("free", PowerUnit::Percent, 250, &zones));
+    assert!(parse_power("1e9", PowerUnit::Watts, 250, &zones).is_err());
+    assert!(parse_power("99999%", PowerUnit::Watts, 250, &zones).is_err());
+}
+
+#[test]
+fn test_split_text_duration() {
+    assert_eq!(("Go!".to_string(), Some(15)), split_text_duration("Go! @15s"));
+    assert_eq!(("Hold it".to_string(), Some(90)), split_text_duration("Hold it@1:30"));
+    assert_eq!(("mail@example.com".to_string(), None), split_text_duration("mail@example.com"));
+    assert_eq!(("Plain".to_string(), None), split_text_duration("Plain"));
+}
+
+#[test]
+fn test_parse_messages() {
+    let hints = parse_messages("Get ready | +10s Go!@5s|+1m Halfway|");
+    let hints: Vec<(u32, &str, Option<u32>)> = hints.iter().map(|hint| (hint.offset, hint.text.as_str(), hint.duration)).collect();
+    assert_eq!(vec![(0, "Get ready", None), (10, "Go!", Some(5)), (60, "Halfway", None)], hints);
+    assert_eq!("+more power", parse_messages("+more power")[0].text);
+}
+
+#[test]
+fn test_time_format() {
+    let seconds = |format: &TimeFormat, text| format.parse(text).map(|time| time.as_secs());
+    assert_eq!(Ok(3723), seconds(&TimeFormat::Auto, "1:02:03"));
+    assert_eq!(Ok(300), seconds(&TimeFormat::Auto, "5:00"));
+    assert_eq!(Ok(5400), seconds(&TimeFormat::Auto, "90:00"));
+    assert_eq!(Ok(300), seconds(&TimeFormat::Auto, "300"));
+    assert_eq!(Ok(500_000_000), TimeFormat::Auto.parse("1:02.5").map(|time| time.subsec_nanos()));
+    assert_eq!(Ok(100_000), seconds(&TimeFormat::Hms, "27:46:40"));
+    assert_eq!(Err("input is out of range".to_string()), seconds(&TimeFormat::Auto, "1:75:00"));
+    assert_eq!(Err("input is out of range".to_string()), seconds(&TimeFormat::Auto, "99999999:00:00"));
+    assert_eq!(Err("input is out of range".to_string()), seconds(&TimeFormat::Seconds, "4294967296"));
+    assert_eq!(Err("input contains invalid characters".to_string()), seconds(&TimeFormat::Auto, "1:xx"));
+    assert_eq!(Err("premature end of input".to_string()), seconds(&TimeFormat::Hms, "5:00"));
+    assert_eq!(Err("trailing input".to_string()), seconds(&TimeFormat::Seconds, "5:00"));
+    assert_eq!(Ok(72), seconds(&"%H.%M.%S".parse().unwrap(), "00.01.12"));
+}
+
+#[test]
+fn test_merge_duplicates() {
+    let step = |seconds, watts, text: Option<&str>| Step { time: Duration::from_secs(seconds), watts, text: text.map(str::to_string), ..Step::default() };
+    let steps = || vec![step(0, Some(200), Some("Warm up")), step(0, Some(250), Some("Go")), step(0, None, None), step(60, None, None)];
+    let merged = |policy| merge_duplicates(Box::new(steps().into_iter().map(Ok)), policy).collect::<Result<Vec<_>, _>>();
+
+    let last = merged(Duplicates::Last).unwrap();
+    assert_eq!(2, last.len());
+    assert_eq!((Some(250), Some("Go".to_string())), (last[0].watts, last[0].text.clone()));
+    let first = merged(Duplicates::First).unwrap();
+    assert_eq!((Some(200), Some("Warm up".to_string())), (first[0].watts, first[0].text.clone()));
+    let texts = merged(Duplicates::MergeText).unwrap();
+    assert_eq!((Some(250), "Go"), (texts[0].watts, texts[0].hints[0].text.as_str()));
+    assert_eq!("Error in line 2: time 00:00:00 is the time of the previous row as well", merged(Duplicates::Error).unwrap_err().to_string());
+}
+
+#[test]
+fn test_smooth() {
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = || vec![step(0, Some(100)), step(1, Some(100)), step(2, Some(400)), step(3, Some(100)), step(4, Some(100)), step(5, None)];
+    let smoothed = |filter| smooth(Box::new(steps().into_iter().map(Ok)), 2, filter).map(|step| step.unwrap().watts).collect::<Vec<_>>();
+
+    assert_eq!(vec![Some(100), Some(200), Some(200), Some(200), Some(100), None], smoothed(Filter::Mean));
+    assert_eq!(vec![Some(100), Some(100), Some(100), Some(100), Some(100), None], smoothed(Filter::Median));
+}
+
+#[test]
+fn test_segment() {
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let mut steps: Vec<Step> = (0..180).map(|seconds| step(seconds, Some(if (60..120).contains(&seconds) { 300 + (seconds % 3) as u16 * 10 } else { 150 + (seconds % 2) as u16 * 10 }))).collect();
+    steps[90].text = Some("Halfway".to_string());
+    steps.push(step(180, None));
+    let segments: Vec<Step> = segment(Box::new(steps.into_iter().map(Ok)), 50, 30).map(Result::unwrap).collect();
+
+    let times: Vec<(u64, Option<u16>)> = segments.iter().map(|step| (step.time.as_secs(), step.watts)).collect();
+    assert_eq!(vec![(0, Some(155)), (60, Some(310)), (120, Some(155)), (180, None)], times);
+    assert_eq!((30, "Halfway"), (segments[1].hints[0].offset, segments[1].hints[0].text.as_str()));
+}
+
+#[test]
+fn test_trim() {
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = || vec![step(0, Some(100)), step(300, Some(200)), step(900, Some(300)), step(1200, Some(100)), step(1500, None)];
+    let trimmed = |start, end| trim(Box::new(steps().into_iter().map(Ok)), start, end).map(|step| step.map(|step| (step.time.as_secs(), step.watts)));
+
+    assert_eq!(vec![(0, Some(200)), (500, Some(300)), (800, Some(100)), (1000, None)], trimmed(400, 100).collect::<Result<Vec<_>, _>>().unwrap());
+    assert_eq!(vec![(0, Some(200)), (600, Some(300)), (900, Some(100)), (1200, None)], trimmed(300, 0).collect::<Result<Vec<_>, _>>().unwrap());
+    assert!(trimmed(1000, 500).collect::<Result<Vec<_>, _>>().is_err());
+
+    // rows stream through, only the trimmed end is held back
+    let endless = (0..).map(move |minute| Ok(step(minute * 60, Some(100))));
+    assert_eq!(3, trim(Box::new(endless), 120, 300).take(3).count());
+}
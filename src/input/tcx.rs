@@ -0,0 +1,138 @@
+use std::io::{BufReader, Read};
+use std::time::Duration;
+
+use chrono::DateTime;
+use quick_xml::events::Event;
+
+use super::{average, InputReader};
+use crate::{Error, Step};
+
+/// Reads the power stream of TCX activities and courses.
+/// Course points become text hints.
+#[derive(Debug)]
+pub struct TcxReader {
+    /// averaging interval in seconds
+    pub interval: u32,
+}
+
+#[derive(Debug, Default)]
+struct Point {
+    /// milliseconds since the epoch
+    time: Option<i64>,
+    watts: Option<u16>,
+    name: Option<String>,
+    notes: Option<String>,
+}
+
+impl InputReader for TcxReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let (trackpoints, course_points) = self.read_points(source)?;
+
+        let Some(start) = trackpoints.iter().filter_map(|point| point.time).min() else {
+            return Err(Error::Parse("no trackpoints found in TCX file".to_string()));
+        };
+
+        let samples: Vec<(u32, u16)> = trackpoints.iter()
+            .filter_map(|point| Some((((point.time? - start).max(0) / 1000) as u32, point.watts?)))
+            .collect();
+        if samples.is_empty() {
+            return Err(Error::Parse("no power data found in TCX file".to_string()));
+        }
+
+        let mut steps = average(&samples, self.interval);
+
+        for point in course_points {
+            let (Some(time), Some(text)) = (point.time, point.notes.or(point.name)) else {
+                continue;
+            };
+            let time = Duration::from_millis((time - start).max(0) as u64);
+            steps.push(Step { time, text: Some(text), ..Step::default() });
+        }
+
+        // stable, so power steps stay in front of hints at the same time
+        steps.sort_by_key(|step| step.time);
+        Ok(steps)
+    }
+}
+
+impl TcxReader {
+    pub fn new(interval: u32) -> Self {
+        TcxReader { interval }
+    }
+
+    fn read_points(&self, source: &mut dyn Read) -> Result<(Vec<Point>, Vec<Point>), Error> {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(source));
+        reader.config_mut().trim_text(true);
+
+        let mut trackpoints = Vec::new();
+        let mut course_points = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+        let mut current: Option<Point> = None;
+
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                    if name == "Trackpoint" || name == "CoursePoint" {
+                        current = Some(Point::default());
+                    }
+                    path.push(name);
+                },
+                Event::End(_) => {
+                    let name = path.pop().unwrap_or_default();
+                    if let Some(point) = current.take_if(|_| name == "Trackpoint" || name == "CoursePoint") {
+                        if name == "Trackpoint" {
+                            trackpoints.push(point);
+                        } else {
+                            course_points.push(point);
+                        }
+                    }
+                },
+                Event::Text(t) => {
+                    let (Some(point), Some(name)) = (&mut current, path.last()) else {
+                        continue;
+                    };
+                    let text = t.unescape()?.into_owned();
+                    match name as &str {
+                        "Time" => point.time = Some(DateTime::parse_from_rfc3339(&text)
+                            .map_err(|err| Error::Parse(format!("invalid time \"{}\" at byte {}: {}", text, reader.buffer_position(), err)))?
+                            .timestamp_millis()),
+                        "Watts" => point.watts = Some(text.parse::<f64>()
+                            .map_err(|err| Error::Parse(format!("invalid power \"{}\" at byte {}: {}", text, reader.buffer_position(), err)))?
+                            .round() as u16),
+                        "Name" => point.name = Some(text),
+                        "Notes" => point.notes = Some(text),
+                        _ => {},
+                    }
+                },
+                Event::Eof => break,
+                _ => {},
+            }
+            buf.clear();
+        }
+
+        Ok((trackpoints, course_points))
+    }
+}
+
+#[test]
+fn test_read() {
+    let tcx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+  <Courses><Course><Name>Test</Name><Track>
+    <Trackpoint><Time>2023-05-01T10:00:00Z</Time><Extensions><ns3:TPX><ns3:Watts>100</ns3:Watts></ns3:TPX></Extensions></Trackpoint>
+    <Trackpoint><Time>2023-05-01T10:00:01Z</Time><Extensions><ns3:TPX><ns3:Watts>200</ns3:Watts></ns3:TPX></Extensions></Trackpoint>
+    <Trackpoint><Time>2023-05-01T10:00:02Z</Time><Extensions><ns3:TPX><ns3:Watts>300</ns3:Watts></ns3:TPX></Extensions></Trackpoint>
+  </Track>
+  <CoursePoint><Name>Climb</Name><Time>2023-05-01T10:00:02Z</Time><Notes>Up that hill</Notes></CoursePoint>
+  </Course></Courses>
+</TrainingCenterDatabase>"#;
+
+    let steps = TcxReader::new(2).read(&mut tcx.as_bytes()).unwrap();
+    assert_eq!(3, steps.len());
+    assert_eq!(Some(150), steps[0].watts);
+    assert_eq!(Some(300), steps[1].watts);
+    assert_eq!(None, steps[2].watts);
+    assert_eq!(Some("Up that hill".to_string()), steps[2].text);
+}
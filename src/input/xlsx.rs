@@ -0,0 +1,97 @@
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+use calamine::{open_workbook_from_rs, Data, Reader, Xlsx};
+
+use super::{apply_time_mode, watts, InputError, InputReader, TimeFormat, TimeMode};
+use crate::duration::format_time;
+use crate::{Error, Step};
+
+/// Reads the first sheet of XLSX workbooks with `time,power,text` header columns
+#[derive(Debug)]
+pub struct XlsxReader {
+    pub time_mode: TimeMode,
+    /// sort rows by time instead of failing on rows before the previous one, in time mode
+    pub sort: bool,
+}
+
+impl InputReader for XlsxReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data))?;
+        let range = workbook.worksheet_range_at(0)
+            .ok_or_else(|| Error::Parse("workbook has no sheets".to_string()))??;
+
+        let mut rows = range.rows();
+        let header = rows.next().ok_or_else(|| Error::Parse("sheet is empty".to_string()))?;
+        let column = |name: &str| header.iter().position(|cell| cell.to_string().trim().eq_ignore_ascii_case(name));
+        let time_column = column("time").ok_or_else(|| Error::Parse("missing column \"time\"".to_string()))?;
+        let (power_column, text_column) = (column("power"), column("text"));
+
+        let mut steps = Vec::new();
+        for (index, row) in rows.enumerate() {
+            // the header is row 1
+            let line = index + 2;
+            let cell = |column: Option<usize>| column.and_then(|column| row.get(column)).unwrap_or(&Data::Empty);
+            let limit = |power: f64, text: String| watts(power, &text).map_err(|err| InputError::new(line, Some("power"), err));
+
+            let time = time(cell(Some(time_column)))
+                .ok_or_else(|| InputError::new(line, Some("time"), format!("invalid time \"{}\"", cell(Some(time_column)))))?;
+            let watts = match cell(power_column) {
+                Data::Empty => None,
+                Data::Int(power) => Some(limit(*power as f64, power.to_string())?),
+                Data::Float(power) => Some(limit(*power, power.to_string())?),
+                Data::String(watts) if watts.trim().is_empty() => None,
+                Data::String(watts) => Some(watts.trim().parse::<u16>().map_err(|err| InputError::new(line, Some("power"), err.to_string()))?),
+                other => return Err(InputError::new(line, Some("power"), format!("invalid power \"{}\"", other)).into()),
+            };
+            let text = match cell(text_column) {
+                Data::Empty => None,
+                text => Some(text.to_string()),
+            };
+
+            steps.push(Step { time, watts, text, ..Step::default() });
+        }
+
+        if self.sort && self.time_mode == TimeMode::Time {
+            // stable, so texts stay behind the power of the same time
+            steps.sort_by_key(|step| step.time);
+        }
+        if let Err((index, last_time)) = apply_time_mode(&mut steps, self.time_mode) {
+            let msg = format!("time {} is before last time {}, use --sort-input to sort rows by time", format_time(steps[index].time), format_time(last_time));
+            return Err(InputError::new(index + 2, Some("time"), msg).into());
+        }
+
+        Ok(steps)
+    }
+}
+
+impl XlsxReader {
+    pub fn new(time_mode: TimeMode) -> Self {
+        XlsxReader { time_mode, sort: false }
+    }
+}
+
+/// Time cells may be text, formatted Excel times (fractions of a day) or plain seconds
+fn time(cell: &Data) -> Option<Duration> {
+    let seconds = match cell {
+        Data::String(text) => return TimeFormat::Auto.parse(text.trim()).ok(),
+        Data::DateTime(time) => (time.as_f64().fract() * 86_400_000.0).round() / 1000.0,
+        Data::Float(seconds) => *seconds,
+        Data::Int(seconds) => *seconds as f64,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[test]
+fn test_time() {
+    let expected = Some(Duration::from_secs(90));
+    assert_eq!(expected, time(&Data::String("00:01:30".to_string())));
+    assert_eq!(expected, time(&Data::Float(90.0)));
+    assert_eq!(expected, time(&Data::Int(90)));
+    assert_eq!(Some(Duration::from_millis(90250)), time(&Data::Float(90.25)));
+    assert_eq!(None, time(&Data::Empty));
+}
@@ -0,0 +1,156 @@
+use std::io::Read;
+
+use serde::Deserialize;
+
+use super::{InputReader, Metadata};
+use crate::{parse_duration, Error, Step};
+
+/// Reads hand-written YAML workouts, made of blocks with duration, power and text.
+/// Blocks can be grouped and repeated.
+#[derive(Debug)]
+pub struct YamlReader {
+    /// absolute FTP in watts, to adapt power of workouts written for a different FTP
+    pub ftp: u16,
+}
+
+#[derive(Debug, Deserialize)]
+struct Workout {
+    name: Option<String>,
+    description: Option<String>,
+    author: Option<String>,
+    /// absolute FTP in watts the power values are based on
+    ftp: Option<u16>,
+    blocks: Vec<Block>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Block {
+    Repeat {
+        repeat: u32,
+        blocks: Vec<Block>,
+    },
+    Interval {
+        duration: Duration,
+        power: Option<u16>,
+        text: Option<String>,
+        #[serde(default)]
+        hints: Vec<Hint>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct Hint {
+    offset: Duration,
+    text: String,
+}
+
+/// Duration in seconds or as text like "5m"
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Duration {
+    Seconds(u32),
+    Text(String),
+}
+
+impl Duration {
+    fn seconds(&self) -> Result<u32, Error> {
+        match self {
+            Duration::Seconds(seconds) => Ok(*seconds),
+            Duration::Text(text) => parse_duration(text).map_err(Error::Parse),
+        }
+    }
+}
+
+impl InputReader for YamlReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        Ok(self.read_with_metadata(source)?.0)
+    }
+
+    fn read_with_metadata(&self, source: &mut dyn Read) -> Result<(Vec<Step>, Metadata), Error> {
+        let workout: Workout = serde_yaml::from_reader(source)?;
+
+        let mut steps = Vec::new();
+        let mut cursor = 0;
+        self.add_blocks(&workout.blocks, workout.ftp, &mut cursor, &mut steps)?;
+        // the end of the last block
+        if cursor > 0 {
+            steps.push(Step { time: time(cursor), ..Step::default() });
+        }
+
+        // stable, so power steps stay in front of hints at the same time
+        steps.sort_by_key(|step| step.time);
+
+        let metadata = Metadata { name: workout.name, description: workout.description, author: workout.author, ..Metadata::default() };
+        Ok((steps, metadata))
+    }
+}
+
+impl YamlReader {
+    pub fn new(ftp: u16) -> Self {
+        YamlReader { ftp }
+    }
+
+    fn add_blocks(&self, blocks: &[Block], ftp: Option<u16>, cursor: &mut u32, steps: &mut Vec<Step>) -> Result<(), Error> {
+        for block in blocks {
+            match block {
+                Block::Repeat { repeat, blocks } => {
+                    for _ in 0..*repeat {
+                        self.add_blocks(blocks, ftp, cursor, steps)?;
+                    }
+                },
+                Block::Interval { duration, power, text, hints } => {
+                    let watts = match (power, ftp) {
+                        (Some(watts), Some(ftp)) if ftp > 0 => Some((*watts as f64 * self.ftp as f64 / ftp as f64).round() as u16),
+                        (watts, _) => *watts,
+                    };
+                    steps.push(Step { time: time(*cursor), watts, text: text.clone(), ..Step::default() });
+
+                    for hint in hints {
+                        steps.push(Step { time: time(*cursor + hint.offset.seconds()?), text: Some(hint.text.clone()), ..Step::default() });
+                    }
+
+                    *cursor += duration.seconds()?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+fn time(seconds: u32) -> std::time::Duration {
+    std::time::Duration::from_secs(seconds as u64)
+}
+
+#[test]
+fn test_read() {
+    let yaml = "
+name: Sweet Spot
+blocks:
+  - duration: 5m
+    power: 150
+    text: Warm up
+  - repeat: 2
+    blocks:
+      - duration: 90
+        power: 250
+        text: Go!
+        hints:
+          - offset: 1m
+            text: Halfway
+      - duration: 1m
+        power: 150
+";
+
+    let (steps, metadata) = YamlReader::new(250).read_with_metadata(&mut yaml.as_bytes()).unwrap();
+    assert_eq!(Some("Sweet Spot".to_string()), metadata.name);
+    let times: Vec<u64> = steps.iter().map(|step| step.time.as_secs()).collect();
+    assert_eq!(vec![0, 300, 360, 390, 450, 510, 540, 600], times);
+    assert_eq!(Some("Halfway".to_string()), steps[2].text);
+    assert_eq!(None, steps[2].watts);
+    assert_eq!(Some(150), steps[6].watts);
+    assert_eq!(None, steps[7].watts);
+
+    let sections = crate::Converter::new(250).translate(steps);
+    assert_eq!(600, sections.iter().map(|sec| sec.start + sec.duration).max().unwrap());
+}
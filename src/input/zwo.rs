@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+use std::time::Duration;
+
+use quick_xml::events::{BytesStart, Event};
+
+use super::InputReader;
+use crate::{Error, Step};
+
+/// Reads Zwift workout files (ZWO), so they can be processed again.
+/// Ramps are split into steps of the given interval.
+#[derive(Debug)]
+pub struct ZwoReader {
+    /// absolute FTP in watts, used to convert relative power
+    pub ftp: u16,
+    /// step length for ramps in seconds
+    pub interval: u32,
+}
+
+impl InputReader for ZwoReader {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<Step>, Error> {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(source));
+        reader.config_mut().trim_text(true);
+
+        let mut steps = Vec::new();
+        let mut in_workout = false;
+        // start of the current block and start of the next one, in seconds
+        let mut block_start = 0.0;
+        let mut next_start = 0.0;
+
+        let mut buf = Vec::new();
+        loop {
+            let event = reader.read_event_into(&mut buf)?;
+            let position = reader.buffer_position();
+            let element = match &event {
+                Event::Start(e) | Event::Empty(e) => e,
+                Event::End(e) => {
+                    if e.local_name().as_ref().eq_ignore_ascii_case(b"workout") {
+                        in_workout = false;
+                    }
+                    buf.clear();
+                    continue;
+                },
+                Event::Eof => break,
+                _ => {
+                    buf.clear();
+                    continue;
+                },
+            };
+
+            let name = String::from_utf8_lossy(element.local_name().as_ref()).to_lowercase();
+            if name == "workout" {
+                in_workout = true;
+            } else if in_workout {
+                let attrs = attributes(element)?;
+                let number = |key: &str| -> Result<f64, Error> {
+                    let value = attrs.get(key).ok_or_else(|| Error::Parse(format!("{} lacks attribute {} at byte {}", name, key, position)))?;
+                    value.parse().map_err(|err| Error::Parse(format!("invalid {} \"{}\" at byte {}: {}", key, value, position, err)))
+                };
+
+                match &name as &str {
+                    "textevent" => {
+                        let offset = number("timeoffset")?;
+                        let text = attrs.get("message").cloned().unwrap_or_default();
+                        let text_duration = attrs.contains_key("duration").then(|| number("duration")).transpose()?.map(|duration| duration as u32);
+                        steps.push(Step { time: time(block_start + offset), text: Some(text), text_duration, ..Step::default() });
+                    },
+                    "steadystate" => {
+                        block_start = next_start;
+                        steps.push(Step { time: time(block_start), watts: Some(self.watts(number("power")?)), ..Step::default() });
+                        next_start += number("duration")?;
+                    },
+                    "warmup" | "cooldown" | "ramp" => {
+                        block_start = next_start;
+                        let duration = number("duration")?;
+                        self.ramp(&mut steps, block_start, duration, number("powerlow")?, number("powerhigh")?)?;
+                        next_start += duration;
+                    },
+                    "intervalst" => {
+                        block_start = next_start;
+                        let repeat = number("repeat")? as u32;
+                        let (on_duration, off_duration) = (number("onduration")?, number("offduration")?);
+                        let (on_power, off_power) = (self.watts(number("onpower")?), self.watts(number("offpower")?));
+                        for _ in 0..repeat {
+                            steps.push(Step { time: time(next_start), watts: Some(on_power), ..Step::default() });
+                            next_start += on_duration;
+                            steps.push(Step { time: time(next_start), watts: Some(off_power), ..Step::default() });
+                            next_start += off_duration;
+                        }
+                    },
+                    "freeride" => {
+                        // no power target, which ends the previous block
+                        block_start = next_start;
+                        steps.push(Step { time: time(block_start), ..Step::default() });
+                        next_start += number("duration")?;
+                    },
+                    _ => {},
+                }
+            }
+            buf.clear();
+        }
+        // the end of the last block
+        if next_start > 0.0 {
+            steps.push(Step { time: time(next_start), ..Step::default() });
+        }
+
+        // stable, so power steps stay in front of hints at the same time
+        steps.sort_by_key(|step| step.time);
+
+        // hints at the very start of a block belong to its power step
+        let mut merged: Vec<Step> = Vec::with_capacity(steps.len());
+        for step in steps {
+            match merged.last_mut() {
+                Some(last) if last.time == step.time && last.watts.is_some() && last.text.is_none() && step.watts.is_none() => {
+                    last.text = step.text;
+                    last.text_duration = step.text_duration;
+                },
+                _ => merged.push(step),
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+impl ZwoReader {
+    pub fn new(ftp: u16, interval: u32) -> Self {
+        ZwoReader { ftp, interval }
+    }
+
+    fn watts(&self, power: f64) -> u16 {
+        (power * self.ftp as f64).round() as u16
+    }
+
+    /// Splits a linear ramp into steps with the power of their midpoints
+    fn ramp(&self, steps: &mut Vec<Step>, start: f64, duration: f64, low: f64, high: f64) -> Result<(), Error> {
+        let count = (duration / self.interval.max(1) as f64).round().max(1.0) as u32;
+        let length = duration / count as f64;
+        for i in 0..count {
+            let fraction = (i as f64 + 0.5) / count as f64;
+            let power = low + (high - low) * fraction;
+            steps.push(Step { time: time(start + i as f64 * length), watts: Some(self.watts(power)), ..Step::default() });
+        }
+        Ok(())
+    }
+}
+
+/// Collects the attributes of an element, with lowercase keys
+fn attributes(element: &BytesStart) -> Result<HashMap<String, String>, Error> {
+    let mut attrs = HashMap::new();
+    for attr in element.attributes() {
+        let attr = attr?;
+        let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_lowercase();
+        attrs.insert(key, attr.unescape_value()?.into_owned());
+    }
+    Ok(attrs)
+}
+
+fn time(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds).unwrap_or_default()
+}
+
+#[test]
+fn test_read() {
+    let zwo = r#"<workout_file>
+    <name>Test</name>
+    <workout>
+        <Warmup Duration="60" PowerLow="0.4" PowerHigh="0.6"/>
+        <SteadyState Duration="120" Power="1.0" pace="0">
+            <textevent timeoffset="10" message="Fish &amp; Chips"/>
+        </SteadyState>
+        <IntervalsT Repeat="2" OnDuration="30" OffDuration="30" OnPower="1.2" OffPower="0.5"/>
+    </workout>
+</workout_file>"#;
+
+    let steps = ZwoReader::new(200, 30).read(&mut zwo.as_bytes()).unwrap();
+    let watts: Vec<Option<u16>> = steps.iter().map(|step| step.watts).collect();
+    assert_eq!(vec![Some(90), Some(110), Some(200), None, Some(240), Some(100), Some(240), Some(100), None], watts);
+    assert_eq!(Duration::from_secs(70), steps[3].time);
+    assert_eq!(Some("Fish & Chips".to_string()), steps[3].text);
+    assert_eq!(Duration::from_secs(270), steps[7].time);
+    assert_eq!(Duration::from_secs(300), steps[8].time);
+}
+
+#[test]
+fn test_duration() {
+    use crate::Converter;
+
+    let zwo = r#"<workout_file>
+    <workout>
+        <SteadyState Duration="600" Power="0.5"/>
+        <FreeRide Duration="300"/>
+        <SteadyState Duration="1200" Power="0.8"/>
+    </workout>
+</workout_file>"#;
+
+    let steps = ZwoReader::new(200, 30).read(&mut zwo.as_bytes()).unwrap();
+    assert_eq!((Duration::from_secs(600), None), (steps[1].time, steps[1].watts));
+    assert_eq!((Duration::from_secs(2100), None), (steps[3].time, steps[3].watts));
+
+    let converter = Converter { free_ride: true, ..Converter::new(200) };
+    let sections = converter.translate(steps);
+    assert_eq!(2100, sections.iter().map(|sec| sec.start + sec.duration).max().unwrap());
+}
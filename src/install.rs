@@ -0,0 +1,57 @@
+//! Locating Zwift's custom workout folders
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// Finds the `Documents/Zwift/Workouts` directory of the current user
+pub fn workouts_dir() -> Option<PathBuf> {
+    let home = env::var_os("USERPROFILE").or_else(|| env::var_os("HOME"))?;
+    let home = PathBuf::from(home);
+
+    // Windows may move the documents into OneDrive
+    [home.join("Documents"), home.join("OneDrive").join("Documents")]
+        .into_iter()
+        .map(|documents| documents.join("Zwift").join("Workouts"))
+        .find(|dir| dir.is_dir())
+}
+
+/// Lists the Zwift user IDs, which are the numeric subdirectories of the workouts directory
+pub fn user_ids(workouts_dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut ids: Vec<String> = fs::read_dir(workouts_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+/// Determines the workout directory to install into, using the given user ID if there are several
+pub fn install_dir(user_id: Option<&str>) -> Result<PathBuf, Error> {
+    let dir = workouts_dir().ok_or_else(|| Error::Validation("Zwift workouts directory not found".to_string()))?;
+    let ids = user_ids(&dir).map_err(|err| std::io::Error::new(err.kind(), format!("{}: {}", dir.display(), err)))?;
+
+    match (user_id, &ids[..]) {
+        (Some(id), _) if ids.iter().any(|known| known == id) => Ok(dir.join(id)),
+        (Some(id), _) => Err(Error::Validation(format!("unknown Zwift user ID {}, found: {}", id, ids.join(", ")))),
+        (None, [id]) => Ok(dir.join(id)),
+        (None, []) => Err(Error::Validation(format!("no Zwift user ID found in {}", dir.display()))),
+        (None, _) => Err(Error::Validation(format!("several Zwift user IDs found, choose one with --zwift-user: {}", ids.join(", ")))),
+    }
+}
+
+#[test]
+fn test_user_ids() {
+    let dir = env::temp_dir().join(format!("course2zwift-install-{}", std::process::id()));
+    fs::create_dir_all(dir.join("1234")).unwrap();
+    fs::create_dir_all(dir.join("567")).unwrap();
+    fs::create_dir_all(dir.join("backup")).unwrap();
+
+    let ids = user_ids(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(vec!["1234".to_string(), "567".to_string()], ids.unwrap());
+}
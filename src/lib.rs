@@ -0,0 +1,41 @@
+//! Create Zwift workouts from table data
+//!
+//! An [`InputReader`] reads rows of time, power and (optional) hint texts
+//! from a source format, the [`Converter`] turns them into [`Section`]s,
+//! which form a [`Course`]. An [`OutputWriter`] finally serializes the
+//! course into a target format like ZWO.
+
+mod blocks;
+mod converter;
+mod course;
+mod duration;
+mod fit;
+mod pace;
+mod template;
+pub mod chart;
+pub mod completions;
+pub mod config;
+pub mod cues;
+pub mod diff;
+pub mod error;
+pub mod fidelity;
+pub mod input;
+pub mod install;
+pub mod lint;
+pub mod manual;
+pub mod output;
+pub mod report;
+pub mod stats;
+pub mod validate;
+pub mod zones;
+
+pub use blocks::{BlockOption, RampSpec, DEFAULT_RAMP_TOLERANCE};
+pub use converter::{Converter, Explanation, Gap, PowerLimit, Rounding, Step, DEFAULT_DURATION_RASTER, DEFAULT_GAP_AFTER, DEFAULT_GAP_POWER};
+pub use course::{Course, DurationType, Hint, Kind, Section, Sport};
+pub use duration::{format_duration, parse_duration};
+pub use error::Error;
+pub use input::{Duplicates, Filter, InputFormat, InputReader, Metadata, PowerUnit, Steps, TimeFormat, TimeMode};
+pub use output::{Format, OutputWriter};
+pub use pace::{parse_pace, PaceUnit, RunPace};
+pub use template::expand_templates;
+pub use zones::{Zone, ZoneModel};
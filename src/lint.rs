@@ -0,0 +1,118 @@
+//! Sanity checks for converted workouts
+
+use std::fmt::{Display, Formatter};
+
+use crate::{Course, Kind, DEFAULT_DURATION_RASTER};
+
+/// Workouts longer than this are impractical to ride in Zwift, in seconds
+const MAX_DURATION: u32 = 6 * 3600;
+/// Workouts with more blocks get hard to follow and edit in Zwift
+const MAX_SECTIONS: usize = 500;
+
+/// A suspicious finding in a course
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    /// index of the section, if the warning is about a single one
+    pub section: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.section {
+            Some(index) => write!(f, "section {}: {}", index + 1, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks courses for results which are valid but likely wrong
+#[derive(Debug)]
+pub struct Linter {
+    /// sections shorter than this are reported, in seconds
+    pub raster: u32,
+    /// highest plausible power relative to FTP
+    pub max_power: f64,
+    /// largest plausible power change between sections, relative to FTP
+    pub max_jump: f64,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter {
+            raster: DEFAULT_DURATION_RASTER,
+            max_power: 3.0,
+            max_jump: 1.0,
+        }
+    }
+}
+
+impl Linter {
+    pub fn lint(&self, course: &Course) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut warn = |section: Option<usize>, message: String| warnings.push(Warning { section, message });
+
+        let mut last_power = None;
+        for (index, sec) in course.sections.iter().enumerate() {
+            if sec.duration < self.raster {
+                warn(Some(index), format!("duration {}s is shorter than the raster of {}s", sec.duration, self.raster));
+            }
+            let peak = sec.power.max(sec.end_power());
+            if peak > self.max_power {
+                warn(Some(index), format!("power {:.0}% FTP is above {:.0}%", peak * 100.0, self.max_power * 100.0));
+            }
+            if let (Some(last), false) = (last_power, matches!(sec.kind, Kind::FreeRide { .. })) {
+                let jump: f64 = sec.power - last;
+                if jump.abs() > self.max_jump {
+                    warn(Some(index), format!("power changes by {:+.0}% FTP", jump * 100.0));
+                }
+            }
+            for hint in &sec.text {
+                if hint.offset >= sec.duration {
+                    warn(Some(index), format!("text \"{}\" at {}s is beyond the duration of {}s", hint.text, hint.offset, sec.duration));
+                }
+            }
+            // free rides have no power to jump from
+            last_power = match sec.kind {
+                Kind::FreeRide { .. } => None,
+                _ => Some(sec.end_power()),
+            };
+        }
+
+        let duration: u32 = course.sections.iter().map(|sec| sec.duration).sum();
+        if duration > MAX_DURATION {
+            warn(None, format!("total duration of {}h{:02}m exceeds {}h", duration / 3600, duration % 3600 / 60, MAX_DURATION / 3600));
+        }
+        if course.sections.len() > MAX_SECTIONS {
+            warn(None, format!("{} sections exceed {}", course.sections.len(), MAX_SECTIONS));
+        }
+
+        warnings
+    }
+}
+
+#[test]
+fn test_lint() {
+    use crate::{Hint, Section};
+
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![
+            Section { start: 0, duration: 60, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![Hint { offset: 60, text: "Late".to_string(), duration: None }] },
+            Section { start: 60, duration: 10, power: 3.5, kind: Kind::SteadyState, cadence: None, text: vec![] },
+            Section { start: 70, duration: 60, power: 0.6, kind: Kind::SteadyState, cadence: None, text: vec![] },
+        ],
+        ..Course::default()
+    };
+
+    let warnings: Vec<String> = Linter::default().lint(&course).iter().map(|w| w.to_string()).collect();
+    assert_eq!(vec![
+        "section 1: text \"Late\" at 60s is beyond the duration of 60s",
+        "section 2: duration 10s is shorter than the raster of 30s",
+        "section 2: power 350% FTP is above 300%",
+        "section 2: power changes by +300% FTP",
+        "section 3: power changes by -290% FTP",
+    ], warnings);
+}
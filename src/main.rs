@@ -1,301 +1,1284 @@
-use std::cmp::max;
-use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::prelude::*;
-use std::time;
-
-use chrono::{naive::NaiveTime, Timelike};
-use clap::Parser;
-use serde::Deserialize;
-
-const DEFAULT_DURATION_RASTER: u32 = 30;
-const DEFAULT_COURSE_TYPE: &str = "ride";
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use clap::builder::PossibleValuesParser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use course2zwift::input::{merge_duplicates, parse_encoding, segment, smooth, trim, write_csv, Columns, CsvReader, Delimiter, ErgReader, FitReader, GpxReader, JsonReader, TcxReader, XlsxReader, YamlReader, ZwoReader, DEFAULT_CDA, DEFAULT_CRR, DEFAULT_SEGMENT_LENGTH, DEFAULT_WEIGHT};
+use course2zwift::chart::render_terminal;
+use course2zwift::completions::{generate, Shell};
+use course2zwift::config::Config;
+use course2zwift::cues::{add_cues, countdown, remind, Dictionary, Reminder};
+use course2zwift::diff::compare;
+use course2zwift::fidelity::Fidelity;
+use course2zwift::install::install_dir;
+use course2zwift::lint::Linter;
+use course2zwift::manual::man_page;
+use course2zwift::output::{file_name, Indent, Newline, XmlStyle, ZipWriter};
+use course2zwift::report::{Failure, ReportFormat, Success};
+use course2zwift::stats::{reach_duration, reach_tss, zone_table, Stats, TssAdjust};
+use course2zwift::validate::validate;
+use course2zwift::{expand_templates, format_duration, parse_duration, parse_pace, BlockOption, Converter, Course, Duplicates, DurationType, Error, Filter, PaceUnit, PowerUnit, RunPace, Sport, Zone, ZoneModel, DEFAULT_RAMP_TOLERANCE, Format, Gap, InputFormat, PowerLimit, Rounding, InputReader, TimeFormat, TimeMode, DEFAULT_DURATION_RASTER, DEFAULT_GAP_AFTER, DEFAULT_GAP_POWER};
+
 const DEFAULT_AUTHOR: &str = "Mathias Lieber";
 const DEFAULT_TIME_MODE: &str = "time";
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Chart width when the terminal width is unknown
+const PREVIEW_WIDTH: usize = 72;
+/// Chart height of previews
+const PREVIEW_HEIGHT: usize = 12;
+/// FTP used when only relative power is given, so watts equal percent
+const NOMINAL_FTP: u16 = 100;
+/// Environment variable with the FTP to use when the FTP argument is left out
+const FTP_VARIABLE: &str = "C2Z_FTP";
 
 /// CLI options
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct CourseBuilder {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// course name
-    name: String,
+    #[arg(required_unless_present = "interactive")]
+    name: Option<String>,
     /// optional description
-    #[arg(short, long)]
+    #[arg(short, long, env = "C2Z_DESCRIPTION", global = true)]
     description: Option<String>,
-    /// customizable author
-    #[arg(long, short = 'A', default_value_t = DEFAULT_AUTHOR.to_string())]
-    author: String,
+    /// customizable author [default: "Mathias Lieber"]
+    #[arg(long, short = 'A', env = "C2Z_AUTHOR", global = true)]
+    author: Option<String>,
     /// time mode: Must be "time" or "duration"
-    #[arg(short, long, default_value_t = DEFAULT_TIME_MODE.to_string())]
+    #[arg(short, long, default_value_t = DEFAULT_TIME_MODE.to_string(), env = "C2Z_TIME_MODE", global = true)]
     time_mode: String,
-    /// customizable sport type
-    #[arg(short = 'T', long, default_value_t = DEFAULT_COURSE_TYPE.to_string())]
-    sport_type: String,
-    /// absolute FTP in watts
-    ftp: u16,
+    /// time column format: "hms" like "1:05:00", "ms" like "65:00", "seconds" like "3900", "auto" to detect it, or a format like "%H.%M.%S"
+    #[arg(long, default_value_t = TimeFormat::Auto, env = "C2Z_TIME_FORMAT", global = true)]
+    time_format: TimeFormat,
+    /// sport type: "bike" or "run"
+    #[arg(short = 'T', long, default_value_t = Sport::Bike, env = "C2Z_SPORT_TYPE", global = true)]
+    sport_type: Sport,
+    /// workout tag shown in Zwift, may be repeated
+    #[arg(long, env = "C2Z_TAG", global = true)]
+    tag: Vec<String>,
+    /// category grouping the workout in Zwift's workout picker
+    #[arg(long, env = "C2Z_CATEGORY", global = true)]
+    category: Option<String>,
+    /// subcategory within the category, e.g. the week of a plan
+    #[arg(long, requires = "category", env = "C2Z_SUBCATEGORY", global = true)]
+    subcategory: Option<String>,
+    /// absolute FTP in watts, may be left out with --power-unit percent, --threshold-pace, C2Z_FTP or an FTP in the config file
+    #[arg(required_unless_present = "interactive")]
+    ftp: Option<String>,
     /// time shrink factor
-    #[arg(short, long, default_value_t = 1.0)]
+    #[arg(short, long, default_value_t = 1.0, env = "C2Z_ACCELERATION", global = true)]
     acceleration: f64,
+    /// shrink or stretch the course to this duration like "1h", computing the --acceleration
+    #[arg(long, value_parser = parse_duration, conflicts_with = "acceleration", env = "C2Z_TARGET_DURATION", global = true)]
+    target_duration: Option<u32>,
+    /// scale the power, or with --tss-adjust the duration, so the workout has this TSS
+    #[arg(long, conflicts_with = "preserve_tss", env = "C2Z_TARGET_TSS", global = true)]
+    target_tss: Option<f64>,
+    /// what --target-tss changes: "power" within --min-power and --max-power, or "duration"
+    #[arg(long, default_value_t = TssAdjust::Power, requires = "target_tss", env = "C2Z_TSS_ADJUST", global = true)]
+    tss_adjust: TssAdjust,
+    /// drop this much of the start of the input, like the warmup of a recorded ride
+    #[arg(long, value_parser = parse_duration, env = "C2Z_TRIM_START", global = true)]
+    trim_start: Option<u32>,
+    /// drop this much of the end of the input, like the cooldown of a recorded ride
+    #[arg(long, value_parser = parse_duration, env = "C2Z_TRIM_END", global = true)]
+    trim_end: Option<u32>,
     /// power scale factor
-    #[arg(short, long, default_value_t = 1.0)]
+    #[arg(short, long, default_value_t = 1.0, env = "C2Z_SCALE", global = true)]
     scale: f64,
+    /// raise the power of courses shrunk by --acceleration to keep their TSS, reporting the factors
+    #[arg(long, env = "C2Z_PRESERVE_TSS", global = true)]
+    preserve_tss: bool,
+    /// lowest power of sections after scaling, in percent of FTP like "50%" or in watts
+    #[arg(long, env = "C2Z_MIN_POWER", global = true)]
+    min_power: Option<PowerLimit>,
+    /// highest power of sections after scaling, in percent of FTP like "150%" or in watts
+    #[arg(long, env = "C2Z_MAX_POWER", global = true)]
+    max_power: Option<PowerLimit>,
     /// duration rasterization in seconds
-    #[arg(short, long, default_value_t = DEFAULT_DURATION_RASTER)]
+    #[arg(short, long, default_value_t = DEFAULT_DURATION_RASTER, env = "C2Z_RASTER", global = true)]
     raster: u32,
-    /// path to the CSV file to read
-    file: std::path::PathBuf,
+    /// rounding of section starts and durations to the raster: "nearest", "up" never shortening or "down" never lengthening a section
+    #[arg(long, default_value_t = Rounding::Nearest, env = "C2Z_ROUND", global = true)]
+    round: Rounding,
+    /// shortest section like "30s", shorter ones are merged into their neighbor
+    #[arg(long, value_parser = parse_duration, env = "C2Z_MIN_DURATION", global = true)]
+    min_duration: Option<u32>,
+    /// rows marked in the "key" column keep their exact start and duration, the sections between them absorb the rounding
+    #[arg(long, env = "C2Z_ANCHOR", global = true)]
+    anchor: bool,
+    /// warmup block: "auto" to detect an initial ramp, or e.g. "10m:0.4-0.75" to replace the start
+    #[arg(long, env = "C2Z_WARMUP", global = true)]
+    warmup: Option<BlockOption>,
+    /// cooldown block: "auto" to detect a final ramp, or e.g. "5m:0.7-0.4" to replace the end
+    #[arg(long, env = "C2Z_COOLDOWN", global = true)]
+    cooldown: Option<BlockOption>,
+    /// largest deviation from a straight line relative to FTP for steps to become a ramp, 0 disables ramps
+    #[arg(long, default_value_t = DEFAULT_RAMP_TOLERANCE, env = "C2Z_RAMP_TOLERANCE", global = true)]
+    ramp_tolerance: f64,
+    /// keep repeated on/off pairs as single steps instead of interval blocks
+    #[arg(long, env = "C2Z_NO_INTERVALS", global = true)]
+    no_intervals: bool,
+    /// merge consecutive steady sections whose power differs by at most this relative to FTP, like 0.02
+    #[arg(long, env = "C2Z_MERGE_TOLERANCE", global = true)]
+    merge_tolerance: Option<f64>,
+    /// merge similar steady sections unless one deviates from their average by more than this, in percent of FTP like "3%" or in watts
+    #[arg(long, env = "C2Z_SIMPLIFY", global = true)]
+    simplify: Option<PowerLimit>,
+    /// split steady sections longer than this into equal parts, like "20m"
+    #[arg(long, value_parser = parse_duration, env = "C2Z_MAX_SECTION", global = true)]
+    max_section: Option<u32>,
+    /// add texts like "Halfway" or "Part 2 of 3" to the parts of split sections
+    #[arg(long, requires = "max_section", env = "C2Z_SECTION_PROGRESS", global = true)]
+    section_progress: bool,
+    /// rows with an empty or "free" power start a FreeRide block instead of adding text only
+    #[arg(long, env = "C2Z_FREE_RIDE", global = true)]
+    free_ride: bool,
+    /// FreeRide blocks keep the road flat
+    #[arg(long, requires = "free_ride", env = "C2Z_FLAT_ROAD", global = true)]
+    flat_road: bool,
+    /// silences between rows longer than --gap-after: "hold" the previous power, "rest" at --gap-power or "freeride"
+    #[arg(long, default_value_t = Gap::Hold, env = "C2Z_GAP", global = true)]
+    gap: Gap,
+    /// time without a row after which a silence is a gap, like "2m"
+    #[arg(long, default_value_t = DEFAULT_GAP_AFTER, value_parser = parse_duration, env = "C2Z_GAP_AFTER", global = true)]
+    gap_after: u32,
+    /// power of recovery blocks filling gaps relative to FTP
+    #[arg(long, default_value_t = DEFAULT_GAP_POWER, env = "C2Z_GAP_POWER", global = true)]
+    gap_power: f64,
+    /// total mass of rider and bike in kg (GPX input)
+    #[arg(long, default_value_t = DEFAULT_WEIGHT, env = "C2Z_WEIGHT", global = true)]
+    weight: f64,
+    /// aerodynamic drag area in m² (GPX input)
+    #[arg(long, default_value_t = DEFAULT_CDA, env = "C2Z_CDA", global = true)]
+    cda: f64,
+    /// rolling resistance coefficient (GPX input)
+    #[arg(long, default_value_t = DEFAULT_CRR, env = "C2Z_CRR", global = true)]
+    crr: f64,
+    /// input format: "csv", "fit", "tcx", "gpx", "erg", "mrc", "zwo", "json", "yaml" or "xlsx" [default: by file extension]
+    #[arg(short, long, env = "C2Z_INPUT_FORMAT", global = true)]
+    input_format: Option<InputFormat>,
+    /// CSV column separator: a single character like ";", "tab", or "auto" to detect comma, semicolon or tab
+    #[arg(long, default_value_t = Delimiter::Auto, env = "C2Z_DELIMITER", global = true)]
+    delimiter: Delimiter,
+    /// encoding of CSV files not saved as UTF-8, like "latin1" or "windows-1252" of older Excel versions
+    #[arg(long, value_parser = parse_encoding, env = "C2Z_ENCODING", global = true)]
+    encoding: Option<&'static encoding_rs::Encoding>,
+    /// fail on unknown CSV columns, a missing power column and rows with more or fewer fields than the header
+    #[arg(long, env = "C2Z_STRICT", global = true)]
+    strict: bool,
+    /// ignore unknown CSV columns and extra fields, missing fields are empty, the default
+    #[arg(long, conflicts_with = "strict", env = "C2Z_LENIENT", global = true)]
+    lenient: bool,
+    /// sort CSV, JSON and XLSX rows by time instead of failing on rows before the previous one
+    #[arg(long, env = "C2Z_SORT_INPUT", global = true)]
+    sort_input: bool,
+    /// rows sharing a time: "last" or "first" row wins, "merge-text" like last but showing all texts, or "error"
+    #[arg(long, default_value_t = Duplicates::MergeText, env = "C2Z_DUP", global = true)]
+    dup: Duplicates,
+    /// smooth the power over a window of this many seconds around every row, for raw recorded power
+    #[arg(long, value_parser = parse_duration, env = "C2Z_SMOOTH", global = true)]
+    smooth: Option<u32>,
+    /// filter smoothing the power: "mean" or "median" keeping sharp changes
+    #[arg(long, default_value_t = Filter::Mean, requires = "smooth", env = "C2Z_SMOOTH_FILTER", global = true)]
+    smooth_filter: Filter,
+    /// find the interval boundaries of raw recorded power by change-point detection, where the mean power changes by at least these watts
+    #[arg(long, env = "C2Z_SEGMENT", global = true)]
+    segment: Option<u16>,
+    /// shortest segment found by --segment [default: 30s]
+    #[arg(long, value_parser = parse_duration, requires = "segment", env = "C2Z_SEGMENT_MIN", global = true)]
+    segment_min: Option<u32>,
+    /// CSV files have no header row, their columns are time, power and text
+    #[arg(long, conflicts_with_all = ["col_time", "col_power", "col_text", "col_cadence", "col_hr", "col_distance", "col_text_duration"], env = "C2Z_NO_HEADER", global = true)]
+    no_header: bool,
+    /// name of the CSV time column, like "Timestamp" [default: "time"]
+    #[arg(long, env = "C2Z_COL_TIME", global = true)]
+    col_time: Option<String>,
+    /// name of the CSV power column, like "Watts" [default: "power"]
+    #[arg(long, env = "C2Z_COL_POWER", global = true)]
+    col_power: Option<String>,
+    /// name of the CSV text column, like "Note" [default: "text"]
+    #[arg(long, env = "C2Z_COL_TEXT", global = true)]
+    col_text: Option<String>,
+    /// name of the CSV cadence column [default: "cadence"]
+    #[arg(long, env = "C2Z_COL_CADENCE", global = true)]
+    col_cadence: Option<String>,
+    /// name of the CSV heart rate column [default: "hr"]
+    #[arg(long, env = "C2Z_COL_HR", global = true)]
+    col_hr: Option<String>,
+    /// name of the CSV distance column [default: "distance"]
+    #[arg(long, env = "C2Z_COL_DISTANCE", global = true)]
+    col_distance: Option<String>,
+    /// name of the CSV text duration column [default: "text_duration"]
+    #[arg(long, env = "C2Z_COL_TEXT_DURATION", global = true)]
+    col_text_duration: Option<String>,
+    /// output format: Must be "zwo", "fit", "tcx", "erg", "mrc", "json", "zip", "svg", "png" or "html"
+    #[arg(short, long, default_value_t = Format::Zwo, env = "C2Z_FORMAT", global = true)]
+    format: Format,
+    /// write an XML declaration, TCX files always have one
+    #[arg(long, env = "C2Z_XML_DECLARATION", global = true)]
+    xml_declaration: bool,
+    /// omit the XML declaration of TCX files
+    #[arg(long, conflicts_with = "xml_declaration", env = "C2Z_NO_XML_DECLARATION", global = true)]
+    no_xml_declaration: bool,
+    /// XML indentation: number of spaces or "tab" [default: 4 for ZWO, 2 for TCX]
+    #[arg(long, env = "C2Z_INDENT", global = true)]
+    indent: Option<Indent>,
+    /// XML line ending: "lf" or "crlf"
+    #[arg(long, default_value_t = Newline::Lf, env = "C2Z_NEWLINE", global = true)]
+    newline: Newline,
+    /// write XML without any whitespace between elements
+    #[arg(long, conflicts_with = "indent", env = "C2Z_MINIFY", global = true)]
+    minify: bool,
+    /// write to this file instead of stdout
+    #[arg(short, long, env = "C2Z_OUTPUT", global = true)]
+    output: Option<PathBuf>,
+    /// write to this directory, with a file name derived from the course name
+    #[arg(long, conflicts_with = "output", env = "C2Z_OUT_DIR", global = true)]
+    out_dir: Option<PathBuf>,
+    /// write into the Zwift custom workouts directory
+    #[arg(long, conflicts_with_all = ["output", "out_dir"], env = "C2Z_INSTALL", global = true)]
+    install: bool,
+    /// Zwift user ID to install for, if there are several
+    #[arg(long, env = "C2Z_ZWIFT_USER", global = true)]
+    zwift_user: Option<String>,
+    /// file with time and text rows to add to the sections, in any input format
+    #[arg(long, env = "C2Z_CUES", global = true)]
+    cues: Option<PathBuf>,
+    /// language to translate the texts into, like "de", looked up in --dictionary
+    #[arg(long, requires = "dictionary", env = "C2Z_LANG", global = true)]
+    lang: Option<String>,
+    /// CSV file with the original texts in the first column and their translations in one column per language
+    #[arg(long, requires = "lang", env = "C2Z_DICTIONARY", global = true)]
+    dictionary: Option<PathBuf>,
+    /// add texts like "30 seconds to go" these times before every hard effort, like "30,10,5"
+    #[arg(long, value_delimiter = ',', value_parser = parse_duration, env = "C2Z_COUNTDOWN", global = true)]
+    countdown: Vec<u32>,
+    /// text repeated at a fixed interval, like "Drink!@15m", may be repeated
+    #[arg(long, env = "C2Z_REMIND", global = true)]
+    remind: Vec<Reminder>,
+    /// power relative to FTP above which sections are efforts to count down to
+    #[arg(long, default_value_t = 1.0, env = "C2Z_COUNTDOWN_POWER", global = true)]
+    countdown_power: f64,
+    /// print duration, power, TSS and time in zone of every converted workout
+    #[arg(long, env = "C2Z_STATS", global = true)]
+    stats: bool,
+    /// print how much rasterization shifted the power changes and changed the work
+    #[arg(long, env = "C2Z_FIDELITY", global = true)]
+    fidelity: bool,
+    /// print how every input row was turned into sections and how its time was rounded
+    #[arg(long, env = "C2Z_EXPLAIN", global = true)]
+    explain: bool,
+    /// warn about suspicious results like very short sections or implausible power
+    #[arg(long, env = "C2Z_LINT", global = true)]
+    lint: bool,
+    /// largest power change between sections relative to FTP before --lint warns
+    #[arg(long, default_value_t = 1.0, env = "C2Z_MAX_JUMP", global = true)]
+    max_jump: f64,
+    /// number of files to convert in parallel [default: number of CPUs]
+    #[arg(short, long, env = "C2Z_JOBS", global = true)]
+    jobs: Option<usize>,
+    /// overwrite an existing output file
+    #[arg(long, env = "C2Z_FORCE", global = true)]
+    force: bool,
+    /// convert again whenever an input file changes, implies --force
+    #[arg(short, long, env = "C2Z_WATCH", global = true)]
+    watch: bool,
+    /// how to report results and errors: "text", or "json" with results on stdout and errors on stderr
+    #[arg(long, default_value_t = ReportFormat::Text, env = "C2Z_REPORT", global = true)]
+    report: ReportFormat,
+    /// ask for a missing name, FTP or input file, and show a preview to confirm before writing
+    #[arg(long, conflicts_with = "watch", env = "C2Z_INTERACTIVE", global = true)]
+    interactive: bool,
+    /// power column unit: "watts" or "percent" of FTP, values like "75%" are always relative
+    #[arg(long, env = "C2Z_POWER_UNIT", global = true)]
+    power_unit: Option<PowerUnit>,
+    /// zone model for labels like "Z2" or "SS" in the power column: "coggan", "seiler" or a YAML/JSON file
+    #[arg(long, default_value = "coggan", env = "C2Z_ZONES", global = true)]
+    zones: String,
+    /// custom zone in percent of FTP with optional target, like "Z2=56-75@65", may be repeated
+    #[arg(long, env = "C2Z_ZONE", global = true)]
+    zone: Vec<Zone>,
+    /// lactate threshold heart rate in bpm, converts an "hr" column into power via the matching zones
+    #[arg(long, env = "C2Z_LTHR", global = true)]
+    lthr: Option<u16>,
+    /// threshold pace of run workouts like "4:15", paces like "4:30" in the power column are relative to it
+    #[arg(long, env = "C2Z_THRESHOLD_PACE", global = true)]
+    threshold_pace: Option<String>,
+    /// unit of paces: "km" or "mi"
+    #[arg(long, default_value_t = PaceUnit::Km, env = "C2Z_PACE_UNIT", global = true)]
+    pace_unit: PaceUnit,
+    /// pace of your Zwift profile the threshold pace corresponds to: "mile", "5k", "10k", "half" or "marathon"
+    #[arg(long, default_value_t = RunPace::TenK, env = "C2Z_RUN_PACE", global = true)]
+    run_pace: RunPace,
+    /// threshold pace in seconds per km, resolved from --threshold-pace
+    #[arg(skip)]
+    threshold_seconds: Option<f64>,
+    /// zone model resolved from --zones and --zone
+    #[arg(skip)]
+    zone_model: ZoneModel,
+    /// config file with defaults [default: ~/.config/course2zwift/config.toml]
+    #[arg(long, env = "C2Z_CONFIG", global = true)]
+    config: Option<PathBuf>,
+    /// FTP from the environment or the config file, used when the FTP argument is left out
+    #[arg(skip)]
+    default_ftp: Option<u16>,
+    /// paths or glob patterns of the input files to read, or "-" for stdin.
+    /// Several inputs are named "<NAME> <file name>" and written to --out-dir,
+    /// or bundled into one archive with --format zip.
+    files: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct Record {
-    time: String,
-    #[serde(deserialize_with = "csv::invalid_option")]
-    power: Option<u16>,
-    text: Option<String>,
+#[derive(Subcommand)]
+enum Command {
+    /// Convert workouts, the same as leaving out the subcommand
+    Convert(Conversion),
+    /// Convert workouts into the Zwift custom workouts directory, the same as --install
+    Install(Conversion),
+    /// Convert a ZWO file back into CSV
+    ToCsv {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// path to the ZWO file to read, or "-" for stdin
+        file: PathBuf,
+    },
+    /// Convert all CSV files in a directory tree
+    Batch {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// directory to search for CSV files
+        dir: PathBuf,
+    },
+    /// Print duration, power, TSS and time in zone of a workout
+    Stats {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// path to the file to read, or "-" for stdin
+        file: PathBuf,
+        /// print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the planned time in zone of a workout, to verify its intensity distribution
+    Zones {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// path to the file to read, or "-" for stdin
+        file: PathBuf,
+        /// print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Draw the power profile of a workout in the terminal, colored by zone
+    Preview {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// path to the file to read, or "-" for stdin
+        file: PathBuf,
+        /// chart width in characters [default: terminal width]
+        #[arg(long)]
+        width: Option<usize>,
+        /// chart height in lines
+        #[arg(long, default_value_t = PREVIEW_HEIGHT)]
+        height: usize,
+        /// draw without colors, which are left out anyway when not writing to a terminal
+        #[arg(long)]
+        no_color: bool,
+    },
+    /// Compare two workouts section by section, e.g. a CSV file with the ZWO file written before
+    Diff {
+        /// absolute FTP in watts
+        ftp: u16,
+        /// path to the original workout
+        a: PathBuf,
+        /// path to the changed workout
+        b: PathBuf,
+    },
+    /// Check ZWO files against the rules of Zwift's workout format
+    Validate {
+        /// paths to the ZWO files to check, or "-" for stdin
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print a completion script for "bash", "zsh" or "fish"
+    #[command(hide = true)]
+    Completions {
+        shell: Shell,
+    },
+    /// Print the man page in roff format
+    #[command(hide = true)]
+    Man,
 }
 
-#[derive(Debug)]
-struct Step {
-    time: NaiveTime,
-    watts: Option<u16>,
-    text: Option<String>,
+/// Arguments of a conversion, given without subcommand or with `convert` or `install`
+#[derive(Args)]
+struct Conversion {
+    /// course name
+    name: String,
+    /// absolute FTP in watts, may be left out with --power-unit percent, --threshold-pace, C2Z_FTP or an FTP in the config file
+    ftp: String,
+    /// paths or glob patterns of the input files to read, or "-" for stdin
+    files: Vec<String>,
 }
 
-struct Course {
+fn main() -> ExitCode {
+    let matches = CourseBuilder::command().get_matches();
+    let builder = CourseBuilder::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let report = builder.report;
+    match start(builder, &matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match report {
+                ReportFormat::Text => eprintln!("{}", err),
+                ReportFormat::Json => eprintln!("{}", serde_json::to_string(&Failure::new(None, &err)).unwrap_or_default()),
+            }
+            ExitCode::from(err.exit_code())
+        },
+    }
+}
+
+/// Applies the configuration and runs the command
+fn start(mut builder: CourseBuilder, matches: &ArgMatches) -> Result<(), Error> {
+    // the convert and install subcommands are spelled out forms of a plain conversion
+    match builder.command.take() {
+        Some(Command::Convert(conversion)) => builder.take_conversion(conversion),
+        Some(Command::Install(conversion)) => {
+            if builder.output.is_some() || builder.out_dir.is_some() {
+                return Err(Error::Validation("install writes into the Zwift workouts directory, leave out --output and --out-dir".to_string()));
+            }
+            builder.install = true;
+            builder.take_conversion(conversion);
+        },
+        command => builder.command = command,
+    }
+    if builder.zwift_user.is_some() && !builder.install && matches.value_source("zwift_user") == Some(ValueSource::CommandLine) {
+        return Err(Error::Validation("--zwift-user needs --install".to_string()));
+    }
+    let config = Config::load(builder.config.as_deref())?;
+    builder.apply(config, matches)?;
+
+    builder.zone_model = match builder.zones.parse() {
+        Ok(model) => model,
+        Err(_) => ZoneModel::load(Path::new(&builder.zones))?,
+    };
+    for zone in &builder.zone {
+        builder.zone_model.set(zone.clone());
+    }
+
+    if let Some(pace) = &builder.threshold_pace {
+        if builder.sport_type != Sport::Run {
+            return Err(Error::Validation("--threshold-pace needs --sport-type run".to_string()));
+        }
+        let seconds = parse_pace(pace, builder.pace_unit).ok_or_else(|| Error::Validation(format!("invalid threshold pace \"{}\"", pace)))?;
+        builder.threshold_seconds = Some(seconds);
+    }
+
+    if builder.sport_type == Sport::Run && (builder.free_ride || builder.gap == Gap::FreeRide) {
+        return Err(Error::Validation("free rides are only available for bike workouts".to_string()));
+    }
+
+    if builder.install {
+        if builder.format != Format::Zwo {
+            return Err(Error::Validation("only ZWO files can be installed".to_string()));
+        }
+        builder.out_dir = Some(install_dir(builder.zwift_user.as_deref())?);
+    }
+
+    match &builder.time_mode as &str {
+        "time" | "duration" => {},
+        _ => return Err(Error::Validation("time mode must be \"time\" or \"duration\"".to_string())),
+    }
+
+    if builder.interactive && builder.command.is_none() {
+        builder.prompt()?;
+    }
+
+    if builder.watch {
+        // regenerating the output is the whole point
+        builder.force = true;
+        return builder.watch();
+    }
+
+    builder.execute()
+}
+
+/// The command line definition with the names of values parsed by FromStr, which clap can't list itself,
+/// for completions and the man page
+fn completion_command() -> clap::Command {
+    let names = |names: Vec<&'static str>| PossibleValuesParser::new(names);
+    CourseBuilder::command()
+        .mut_arg("format", |arg| arg.value_parser(names(Format::all().iter().map(|format| format.extension()).collect())))
+        .mut_arg("input_format", |arg| arg.value_parser(names(InputFormat::all().iter().map(|format| format.name()).collect())))
+        .mut_arg("sport_type", |arg| arg.value_parser(["bike", "run"]))
+        .mut_arg("time_mode", |arg| arg.value_parser(["time", "duration"]))
+        .mut_arg("pace_unit", |arg| arg.value_parser(["km", "mi"]))
+        .mut_arg("run_pace", |arg| arg.value_parser(["mile", "5k", "10k", "half", "marathon"]))
+}
+
+fn to_csv(builder: &CourseBuilder, ftp: u16, path: &Path) -> Result<(), Error> {
+    let mut file = open(path)?;
+    let steps = ZwoReader::new(ftp, builder.raster).read(&mut file)?;
+
+    let mut sink = builder.sink(builder.output.clone())?;
+    write_csv(&steps, &mut sink)?;
+    sink.flush()?;
+    Ok(())
+}
+
+/// Opens the input file, or stdin for "-"
+fn open(path: &Path) -> std::io::Result<Box<dyn Read>> {
+    if path == Path::new("-") {
+        Ok(Box::new(std::io::stdin().lock()))
+    } else {
+        let file = File::open(path).map_err(|err| std::io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Validates ZWO files, reporting every violation
+fn validate_all(files: &[PathBuf]) -> Result<(), Error> {
+    let mut count = 0;
+    for path in files {
+        let mut xml = String::new();
+        open(path)?.read_to_string(&mut xml)?;
+
+        let violations = validate(&xml);
+        if violations.is_empty() {
+            eprintln!("ok      {}", path.display());
+        }
+        for violation in &violations {
+            eprintln!("{}: {}", path.display(), violation);
+        }
+        count += violations.len();
+    }
+
+    if count > 0 {
+        return Err(Error::Validation(format!("{} problems found", count)));
+    }
+    Ok(())
+}
+
+/// Modification times of the files, missing ones have none
+fn modified(files: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.iter()
+        .map(|file| (file.clone(), fs::metadata(file).and_then(|meta| meta.modified()).ok()))
+        .collect()
+}
+
+/// One conversion when converting several files
+struct Job {
+    input: PathBuf,
     name: String,
-    description: Option<String>,
-    author: String,
-    sport_type: String,
-    sections: Vec<Section>,
+    output: PathBuf,
 }
 
-#[derive(Debug)]
-struct Section {
-    start: u32,
-    duration: u32,
-    power: f64,
-    text: Vec<Hint>,
+/// Derives a workout name from the file name, e.g. "sweet_spot_3x15.csv" becomes "sweet spot 3x15"
+fn name_from_file(path: &Path) -> String {
+    path.file_stem().unwrap_or_default().to_string_lossy().replace('_', " ")
 }
 
-#[derive(Debug)]
-struct Hint {
-    offset: u32,
-    text: String,
+/// Expands glob patterns, plain paths are kept as they are
+fn expand(patterns: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            files.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let mut matches = glob::glob(pattern)
+            .map_err(|err| Error::Validation(format!("invalid pattern \"{}\": {}", pattern, err)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(std::io::Error::from)?;
+        if matches.is_empty() {
+            return Err(Error::Validation(format!("no files match \"{}\"", pattern)));
+        }
+        matches.sort();
+        files.append(&mut matches);
+    }
+    Ok(files)
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let builder = CourseBuilder::parse();
+/// Collects all files with the given extension in the directory tree, sorted by path
+fn find_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
 
-    match &builder.time_mode as &str {
-        "time" | "duration" => {},
-        _ => panic!("Error: time mode must be \"time\" or \"duration\".")
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, extension, files)?;
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension)) {
+            files.push(path);
+        }
     }
 
-    builder.run()
+    Ok(())
 }
 
-impl CourseBuilder {
-    fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let records = self.read_csv_file()?;
+/// Chart width fitting the terminal, leaving room for the axis labels
+fn preview_width() -> usize {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse::<usize>().ok()?.checked_sub(8)).unwrap_or(PREVIEW_WIDTH)
+}
+
+/// Asks on stderr and reads the trimmed answer from stdin
+fn ask(question: &str) -> Result<String, Error> {
+    eprint!("{}: ", question);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Err(Error::Validation("no answer given".to_string()));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Asks a yes or no question, yes being the default
+fn confirm(question: &str) -> Result<bool, Error> {
+    loop {
+        match ask(&format!("{} [Y/n]", question))?.to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => eprintln!("Please answer y or n"),
+        }
+    }
+}
+
+/// Creates the output file along with missing parent directories.
+/// Existing files are only replaced when forced.
+fn create(path: &Path, force: bool) -> std::io::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+
+    options.open(path).map_err(|err| match err.kind() {
+        ErrorKind::AlreadyExists => std::io::Error::new(err.kind(), format!("{} already exists, use --force to overwrite", path.display())),
+        _ => err,
+    })
+}
 
-        let steps = self.parse_records(&records)?;
+impl CourseBuilder {
+    fn execute(&self) -> Result<(), Error> {
+        match &self.command {
+            Some(Command::ToCsv { ftp, file }) => return to_csv(self, *ftp, file),
+            Some(Command::Batch { ftp, dir }) => return self.batch(*ftp, dir),
+            Some(Command::Validate { files }) => return validate_all(files),
+            Some(Command::Completions { shell }) => return Ok(generate(*shell, completion_command(), &mut std::io::stdout())?),
+            Some(Command::Man) => return Ok(man_page(completion_command(), &mut std::io::stdout())?),
+            Some(Command::Stats { ftp, file, json }) => return self.print_stats(*ftp, file, *json),
+            Some(Command::Zones { ftp, file, json }) => return self.print_zones(*ftp, file, *json),
+            Some(Command::Diff { ftp, a, b }) => {
+                let (a, b) = (self.convert(&name_from_file(a), *ftp, a)?, self.convert(&name_from_file(b), *ftp, b)?);
+                for difference in compare(&a, &b) {
+                    println!("{}", difference);
+                }
+                return Ok(());
+            },
+            Some(Command::Preview { ftp, file, width, height, no_color }) => {
+                let course = self.convert(&name_from_file(file), *ftp, file)?;
+                let width = width.unwrap_or_else(preview_width);
+                let color = !no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+                print!("{}", render_terminal(&course, &self.zone_model, width, *height, color));
+                return Ok(());
+            },
+            // conversions given as subcommand were taken over in main
+            Some(Command::Convert(_)) | Some(Command::Install(_)) | None => {},
+        }
 
-        let course = Course{
-            name: self.name.clone(),
-            description: self.description.clone(),
-            author: self.author.clone(),
-            sport_type: self.sport_type.clone(),
-            sections: self.translate(steps),
+        // clap requires it unless there is a subcommand, or it was asked for
+        let Some(name) = &self.name else {
+            unreachable!("missing required arguments");
         };
 
-        // Let's just write to stdout (and expand tabs)
-        println!("{}", course.to_string().replace("\t", "    "));
-        Ok(())
+        let (ftp, patterns) = self.ftp_and_patterns()?;
+        match &expand(&patterns)?[..] {
+            [file] => self.run(name, ftp, file),
+            files => self.run_all(name, ftp, files),
+        }
     }
 
-    fn read_csv_file(&self) -> std::io::Result<Vec<Record>> {
-        let mut file = File::open(&*self.file.to_string_lossy())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    /// Runs the conversion again whenever one of the input files changes
+    fn watch(&self) -> Result<(), Error> {
+        loop {
+            let inputs = self.inputs()?;
+            if inputs.iter().any(|input| input == Path::new("-")) {
+                return Err(Error::Validation("stdin can't be watched".to_string()));
+            }
 
-        // TODO: Parse file entries
-        let mut reader = csv::Reader::from_reader((&contents as &str).as_bytes());
+            let before = modified(&inputs);
+            if let Err(err) = self.execute() {
+                match self.report {
+                    ReportFormat::Text => eprintln!("{}", err),
+                    ReportFormat::Json => eprintln!("{}", serde_json::to_string(&Failure::new(None, &err))?),
+                }
+            }
+            eprintln!("Watching {} files for changes...", inputs.len());
 
-        let mut line = 0;
-        let mut records: Vec<Record> = Vec::new();
-        for record in reader.deserialize() {
-            line += 1;
-            match record {
-                Err(err) => {
-                    let msg = format!("Error in line {}: {}", line, err.to_string());
-                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
-                },
-                Ok(record) => {
-                    // println!("{:?}", record);
-                    records.push(record);
+            loop {
+                thread::sleep(WATCH_INTERVAL);
+                if modified(&self.inputs()?) != before {
+                    break;
                 }
             }
         }
+    }
 
-        Ok(records)
+    /// All input files of the current command
+    fn inputs(&self) -> Result<Vec<PathBuf>, Error> {
+        match &self.command {
+            Some(Command::ToCsv { file, .. }) => Ok(vec![file.clone()]),
+            Some(Command::Validate { files }) => Ok(files.clone()),
+            Some(Command::Completions { .. }) | Some(Command::Man) => Ok(vec![]),
+            Some(Command::Diff { a, b, .. }) => Ok(vec![a.clone(), b.clone()]),
+            Some(Command::Stats { file, .. }) | Some(Command::Zones { file, .. }) | Some(Command::Preview { file, .. }) => Ok(vec![file.clone()]),
+            Some(Command::Batch { dir, .. }) => {
+                let mut files = Vec::new();
+                find_files(dir, "csv", &mut files)?;
+                Ok(files)
+            },
+            Some(Command::Convert(_)) | Some(Command::Install(_)) | None => expand(&self.ftp_and_patterns()?.1),
+        }
     }
 
-    fn parse_records(&self, records: &Vec<Record>) -> Result<Vec<Step>, Box<dyn std::error::Error>> {
-        let mut line = 0;
+    fn take_conversion(&mut self, conversion: Conversion) {
+        self.name = Some(conversion.name);
+        self.ftp = Some(conversion.ftp);
+        self.files = conversion.files;
+    }
 
-        let mut steps = Vec::new();
-        let mut last_time = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+    /// Uses the values of the config file for options given neither on the command line nor as environment variable
+    fn apply(&mut self, config: Config, matches: &ArgMatches) -> Result<(), Error> {
+        let given = |id: &str| matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable));
 
-        for result in records {
-            line += 1;
+        if self.author.is_none() {
+            self.author = config.author;
+        }
+        self.default_ftp = match std::env::var(FTP_VARIABLE) {
+            Ok(ftp) => Some(ftp.parse().map_err(|err| Error::Validation(format!("invalid FTP \"{}\" in {}: {}", ftp, FTP_VARIABLE, err)))?),
+            Err(_) => config.ftp,
+        };
+        if let (Some(sport_type), false) = (config.sport_type, given("sport_type")) {
+            self.sport_type = sport_type;
+        }
+        if let (Some(raster), false) = (config.raster, given("raster")) {
+            self.raster = raster;
+        }
+        if let (Some(zones), false) = (config.zones, given("zones")) {
+            self.zones = zones;
+        }
+        if let (Some(format), false) = (config.format, given("format")) {
+            self.format = format;
+        }
+        if self.output.is_none() && self.out_dir.is_none() && !self.install {
+            self.out_dir = config.out_dir;
+        }
+        Ok(())
+    }
 
-            let mut step: Step = self.parse_step(&result)?;
+    /// Asks for the name, FTP and input file of a conversion not given on the command line
+    fn prompt(&mut self) -> Result<(), Error> {
+        if self.files.iter().chain(&self.ftp).any(|pattern| pattern == "-") {
+            return Err(Error::Validation("--interactive reads answers from stdin, give an input file instead".to_string()));
+        }
 
-            // TODO: Translate durations to time
-            if self.time_mode.eq("duration") {
-                let new_end = last_time + time::Duration::new(step.time.num_seconds_from_midnight() as u64, 0);
+        while self.name.as_deref().is_none_or(str::is_empty) {
+            self.name = Some(ask("Course name")?);
+        }
 
-                step.time = last_time;
-                last_time = new_end;
-            } else {
-                // check if time is monotonic ascending
-                if step.time < last_time {
-                    let msg = format!("Error in line {}: time {} is before last time {}", line, step.time.to_string(), last_time.to_string());
-                    return Err(Box::<dyn std::error::Error>::from(msg));
+        // an FTP is needed unless relative power or paces are given, or it is known from elsewhere
+        let relative = self.power_unit == Some(PowerUnit::Percent) || self.threshold_pace.is_some();
+        if !relative && self.default_ftp.is_none() {
+            if let Some(file) = self.ftp.take_if(|ftp| ftp.parse::<u16>().is_err()) {
+                self.files.insert(0, file);
+            }
+            while self.ftp.is_none() {
+                let ftp = ask("FTP in watts")?;
+                match ftp.parse::<u16>() {
+                    Ok(_) => self.ftp = Some(ftp),
+                    Err(err) => eprintln!("Invalid FTP \"{}\": {}", ftp, err),
                 }
             }
+        }
+
+        // the FTP argument may hold the first input already
+        let given = self.ftp.as_ref().is_some_and(|ftp| ftp.parse::<u16>().is_err());
+        while !given && self.files.is_empty() {
+            let file = ask("Input file")?;
+            match expand(std::slice::from_ref(&file)) {
+                Ok(files) if files.iter().all(|file| file.is_file()) => self.files.push(file),
+                _ => eprintln!("No file matches \"{}\"", file),
+            }
+        }
+        if self.ftp.is_none() {
+            // relative power, the input takes the place of the FTP
+            self.ftp = Some(self.files.remove(0));
+        }
+        Ok(())
+    }
 
-            steps.push(step);
+    /// Shows the power profile and statistics of the course, and asks whether to write it
+    fn confirm_write(&self, course: &Course, output: Option<&Path>) -> Result<bool, Error> {
+        let color = std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal();
+        eprint!("{}", render_terminal(course, &self.zone_model, preview_width(), PREVIEW_HEIGHT, color));
+        if course.duration_type == DurationType::Time {
+            eprint!("{}", self.stats(course)?);
         }
+        let target = output.map(|path| path.display().to_string()).unwrap_or("stdout".to_string());
+        confirm(&format!("Write \"{}\" to {}?", course.name, target))
+    }
+
+    /// The FTP and the input patterns. With relative power or paces the FTP may be left out,
+    /// so the argument in its place is the first input then.
+    fn ftp_and_patterns(&self) -> Result<(u16, Vec<String>), Error> {
+        let Some(ftp) = &self.ftp else {
+            unreachable!("missing required arguments");
+        };
+
+        let mut patterns = self.files.clone();
+        let ftp = match (ftp.parse(), self.default_ftp) {
+            (Ok(ftp), _) => ftp,
+            (Err(_), _) if self.power_unit == Some(PowerUnit::Percent) || self.threshold_pace.is_some() => {
+                patterns.insert(0, ftp.clone());
+                NOMINAL_FTP
+            },
+            (Err(_), Some(default_ftp)) => {
+                patterns.insert(0, ftp.clone());
+                default_ftp
+            },
+            (Err(err), None) => return Err(Error::Validation(format!("invalid FTP \"{}\": {}", ftp, err))),
+        };
 
-        return Ok(steps)
+        if patterns.is_empty() {
+            return Err(Error::Validation("no input files given".to_string()));
+        }
+        Ok((ftp, patterns))
     }
 
-    fn parse_step(&self, record: &Record) -> Result<Step, Box<dyn std::error::Error>> {
-        let time = NaiveTime::parse_from_str(&record.time, "%H:%M:%S")?;
-        let step = Step{time, watts: record.power, text: record.text.clone()};
-        Ok(step)
+    fn run(&self, name: &str, ftp: u16, path: &Path) -> Result<(), Error> {
+        let output = match (&self.output, &self.out_dir) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Some(dir)) => Some(dir.join(file_name(name, self.format))),
+            (None, None) if self.report == ReportFormat::Json => {
+                return Err(Error::Validation("--report json prints to stdout, write the workout with --output, --out-dir or --install".to_string()));
+            },
+            (None, None) => None,
+        };
+        let course = self.convert(name, ftp, path)?;
+
+        if self.interactive && !self.confirm_write(&course, output.as_deref())? {
+            eprintln!("Nothing written");
+            return Ok(());
+        }
+        self.write(&course, output.clone())?;
+        if self.report == ReportFormat::Json {
+            println!("{}", serde_json::to_string(&self.success(&course, path, output))?);
+        }
+        Ok(())
     }
 
-    fn translate(&self, steps: Vec<Step>) -> Vec<Section> {
-        let mut out = Vec::<Section>::new();
-        let mut cur_sec: Option<Section> = None;
+    /// Result of a conversion, with statistics of time based workouts
+    fn success(&self, course: &Course, input: &Path, output: Option<PathBuf>) -> Success {
+        let stats = (course.duration_type == DurationType::Time).then(|| Stats::of(course, &self.zone_model));
+        Success { name: course.name.clone(), input: input.to_path_buf(), output, stats }
+    }
 
-        for step in steps {
-            // Use acceleration factor
-            let local_time = (step.time.num_seconds_from_midnight() as f64 / self.acceleration).round() as u32;
+    /// Converts several inputs, named after their files, into the output directory
+    fn run_all(&self, prefix: &str, ftp: u16, files: &[PathBuf]) -> Result<(), Error> {
+        // archive entries are relative to the archive root
+        let out_dir = match &self.out_dir {
+            _ if self.format == Format::Zip => Path::new(""),
+            Some(out_dir) => out_dir.as_path(),
+            None => return Err(Error::Validation("multiple inputs need --out-dir or --format zip".to_string())),
+        };
 
-            let power = match step.watts {
-                // Scale power
-                Some(watts) => Some(((watts as f64 * self.scale / self.ftp as f64 * 100.0).round() as u32) as f64 / 100.0),
-                None => None,
-            };
+        let jobs: Vec<Job> = files.iter()
+            .map(|input| {
+                let name = format!("{} {}", prefix, name_from_file(input));
+                let output = out_dir.join(file_name(&name, self.file_format()));
+                Job { input: input.clone(), name, output }
+            })
+            .collect();
 
-            let mut offset = 0;
-            let mut rounded_offset = 0;
-            if let Some(sec) = &mut cur_sec {
-                if local_time > sec.start {
-                    offset = local_time - sec.start;
-                    rounded_offset = round(offset, self.raster);
-                    sec.duration = rounded_offset;
-                }
+        if self.interactive {
+            for job in &jobs {
+                eprintln!("{} -> {}", job.input.display(), job.output.display());
             }
+            if !confirm(&format!("Convert {} files?", jobs.len()))? {
+                eprintln!("Nothing written");
+                return Ok(());
+            }
+        }
+        self.convert_all(prefix, ftp, &jobs)
+    }
 
-            match (&mut cur_sec, power, &step.text) {
-                (Some(sec), None, Some(text)) => {
-                    // add text to existing node
-                    rounded_offset = round(offset, 5);
-                    sec.text.push(Hint {offset: rounded_offset, text: text.clone()});
-                    if sec.duration < rounded_offset {
-                        sec.duration += self.raster;
-                    }
-                },
-                (section, Some(power), _) => {
-                    let mut new_start_time = round(local_time, self.raster);
-                    // close existing node
-                    if let Some(sec) = &section {
-                        new_start_time = sec.start + sec.duration;
-                        out.push(cur_sec.unwrap());
-                    }
+    /// Converts every CSV file in the directory tree, next to the source or into the output directory
+    fn batch(&self, ftp: u16, dir: &Path) -> Result<(), Error> {
+        let mut files = Vec::new();
+        find_files(dir, "csv", &mut files)?;
+
+        let jobs: Vec<Job> = files.into_iter()
+            .map(|input| {
+                let name = name_from_file(&input);
+                let relative_dir = input.parent().unwrap_or(dir).strip_prefix(dir).unwrap_or(Path::new(""));
+                let target_dir = match &self.out_dir {
+                    _ if self.format == Format::Zip => relative_dir.to_path_buf(),
+                    Some(out_dir) => out_dir.join(relative_dir),
+                    None => input.parent().unwrap_or(dir).to_path_buf(),
+                };
+                let output = target_dir.join(file_name(&name, self.file_format()));
+                Job { input, name, output }
+            })
+            .collect();
+
+        let archive_name = dir.canonicalize()?.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        self.convert_all(&archive_name, ftp, &jobs)
+    }
+
+    /// Runs all jobs, reporting success or failure per file.
+    /// With --format zip the results are bundled into one archive named after `name`.
+    fn convert_all(&self, name: &str, ftp: u16, jobs: &[Job]) -> Result<(), Error> {
+        if self.output.is_some() && self.format != Format::Zip {
+            return Err(Error::Validation("one file is written per input, use --out-dir instead of --output".to_string()));
+        }
+        if self.report == ReportFormat::Json && self.format == Format::Zip && self.output.is_none() && self.out_dir.is_none() {
+            return Err(Error::Validation("--report json prints to stdout, write the archive with --output or --out-dir".to_string()));
+        }
 
-                    // start new node
-                    let mut sec = Section{ start: new_start_time, duration: self.raster, power, text: vec!()};
-                    if let Some(text) = &step.text {
-                        sec.text.push(Hint{offset: 0, text: text.clone()})
+        let workers = self.jobs
+            .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1)
+            .clamp(1, jobs.len().max(1));
+
+        // every worker takes the next job until all are done
+        let next = AtomicUsize::new(0);
+        let failures = AtomicUsize::new(0);
+        let archived = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let result = self.convert(&job.name, ftp, &job.input).and_then(|course| {
+                            let success = self.success(&course, &job.input, Some(job.output.clone()));
+                            if self.format == Format::Zip {
+                                archived.lock().unwrap().push((job.output.clone(), course));
+                            } else {
+                                self.write(&course, Some(job.output.clone()))?;
+                            }
+                            Ok(success)
+                        });
+                        match (result, self.report) {
+                            (Ok(_), ReportFormat::Text) => eprintln!("ok      {} -> {}", job.input.display(), job.output.display()),
+                            (Ok(success), ReportFormat::Json) => println!("{}", serde_json::to_string(&success).unwrap_or_default()),
+                            (Err(err), report) => {
+                                match report {
+                                    ReportFormat::Text => eprintln!("FAILED  {}: {}", job.input.display(), err),
+                                    ReportFormat::Json => eprintln!("{}", serde_json::to_string(&Failure::new(Some(&job.input), &err)).unwrap_or_default()),
+                                }
+                                failures.fetch_add(1, Ordering::Relaxed);
+                            },
+                        }
                     }
-                    cur_sec = Some(sec);
-                },
-                _ => {},
+                });
             }
-        }
+        });
 
-        if let Some(sec) = cur_sec {
-            out.push(sec);
+        let failed = failures.into_inner();
+
+        if self.format == Format::Zip {
+            // keep the input order regardless of which worker finished first
+            let mut archived = archived.into_inner().unwrap();
+            archived.sort_by_key(|(path, _)| jobs.iter().position(|job| &job.output == path));
+            let entries: Vec<(String, &Course)> = archived.iter()
+                .map(|(path, course)| (path.to_string_lossy().replace('\\', "/"), course))
+                .collect();
+
+            let output = self.output.clone().or_else(|| self.out_dir.as_ref().map(|dir| dir.join(file_name(name, Format::Zip))));
+            let mut sink = self.sink(output)?;
+            ZipWriter::with_style(self.xml_style()).write_all(&entries, &mut sink)?;
+            sink.flush()?;
         }
 
-        out
+        if self.report == ReportFormat::Text {
+            eprintln!("{} of {} files converted", jobs.len() - failed, jobs.len());
+        }
+        if failed > 0 {
+            return Err(Error::Validation(format!("{} files failed", failed)));
+        }
+        Ok(())
     }
-}
 
-impl Display for Course {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "<workout_file>\n")?;
+    fn convert(&self, name: &str, ftp: u16, path: &Path) -> Result<Course, Error> {
+        let mut file = open(path)?;
+        let reader = self.reader(ftp, path, self.strict);
+        let (steps, metadata) = reader.stream(&mut file)?;
+        let mut steps = merge_duplicates(steps, self.dup);
+        if let Some(window) = self.smooth {
+            steps = smooth(steps, window, self.smooth_filter);
+        }
+        if let Some(threshold) = self.segment {
+            // unlike the other adapters, change-point detection reads the whole input upfront
+            steps = segment(steps, threshold, self.segment_min.unwrap_or(DEFAULT_SEGMENT_LENGTH));
+        }
+        if self.trim_start.is_some() || self.trim_end.is_some() {
+            steps = trim(steps, self.trim_start.unwrap_or(0), self.trim_end.unwrap_or(0));
+        }
+        let mut converter = self.converter(ftp);
+        let mut factors = vec![];
+        if let Some(target) = self.target_duration {
+            if metadata.duration_type == DurationType::Distance {
+                return Err(Error::Validation("--target-duration needs time based workouts".to_string()));
+            }
+            // the duration is known only after reading all rows
+            let rows = steps.collect::<Result<Vec<_>, _>>()?;
+            reach_duration(&mut converter, &rows, target)?;
+            factors.push(format!("time shrunk by {:.2} to {}", converter.acceleration, format_duration(target)));
+            steps = Box::new(rows.into_iter().map(Ok));
+        }
+        if let Some(target) = self.target_tss {
+            if metadata.duration_type == DurationType::Distance {
+                return Err(Error::Validation("--target-tss needs time based workouts".to_string()));
+            }
+            if self.tss_adjust == TssAdjust::Duration && self.target_duration.is_some() {
+                return Err(Error::Validation("--tss-adjust duration can't keep the --target-duration".to_string()));
+            }
+            // the rows are converted repeatedly, so they are read upfront
+            let rows = steps.collect::<Result<Vec<_>, _>>()?;
+            let (scale, acceleration) = (converter.scale, converter.acceleration);
+            let reached = reach_tss(&mut converter, &rows, target, self.tss_adjust);
+            factors.push(match self.tss_adjust {
+                TssAdjust::Power => format!("power scaled by {:.2} for a TSS of {:.0}", converter.scale / scale, reached),
+                TssAdjust::Duration => format!("time shrunk by {:.2} for a TSS of {:.0}", converter.acceleration / acceleration, reached),
+            });
+            steps = Box::new(rows.into_iter().map(Ok));
+        }
+        if converter.preserve_tss && converter.acceleration != 1.0 {
+            if factors.is_empty() {
+                factors.push(format!("time shrunk by {:.2}", converter.acceleration));
+            }
+            factors.push(format!("power scaled by {:.2} to keep the TSS", converter.power_factor() / converter.scale));
+        }
+        if !factors.is_empty() {
+            eprintln!("{}: {}", name, factors.join(", "));
+        }
+        let sections = if self.explain || self.fidelity {
+            // both look at all rows, so they are read upfront
+            let steps = steps.collect::<Result<Vec<_>, _>>()?;
+            if metadata.duration_type == DurationType::Distance {
+                let report = if self.explain { "explanation" } else { "fidelity report" };
+                return Err(Error::Validation(format!("the {} needs time based workouts", report)));
+            }
+            if self.explain {
+                eprintln!("{}:", name);
+                for explanation in converter.explain(steps.clone()) {
+                    eprintln!("{}", explanation);
+                }
+            }
+            let sections = converter.translate(steps.clone());
+            if self.fidelity {
+                eprint!("{}:\n{}", name, Fidelity::of(&converter, &steps, &sections));
+            }
+            sections
+        } else {
+            converter.translate_stream(steps)?
+        };
+
+        let mut course = Course{
+            name: name.to_string(),
+            description: self.description.clone().or(metadata.description),
+            author: self.author.clone().or(metadata.author).unwrap_or(DEFAULT_AUTHOR.to_string()),
+            sport_type: self.sport_type,
+            tags: self.tag.clone(),
+            category: self.category.clone(),
+            subcategory: self.subcategory.clone(),
+            pace: self.threshold_pace.as_ref().map(|_| self.run_pace),
+            duration_type: metadata.duration_type,
+            ftp,
+            sections,
+        };
+        if let Some(path) = &self.cues {
+            // cues have no power column
+            let cues = self.reader(ftp, path, false).read(&mut open(path)?)?;
+            add_cues(&mut course, &cues, converter.acceleration);
+        }
+        countdown(&mut course, &self.countdown, self.countdown_power);
+        for reminder in &self.remind {
+            remind(&mut course, reminder);
+        }
+        if let (Some(lang), Some(path)) = (&self.lang, &self.dictionary) {
+            let dictionary = Dictionary::read(&mut open(path)?, lang)?;
+            for text in dictionary.translate(&mut course) {
+                eprintln!("warning: {}: no {} translation for \"{}\"", name, lang, text);
+            }
+        }
+        // after translating, so translations may contain placeholders as well
+        expand_templates(&mut course);
 
-        self.write_header(f)?;
-        self.write_sections(f)?;
+        if self.lint {
+            let linter = Linter { raster: self.raster, max_jump: self.max_jump, ..Linter::default() };
+            for warning in linter.lint(&course) {
+                eprintln!("warning: {}: {}", name, warning);
+            }
+        }
+        if self.stats {
+            eprint!("{}:\n{}", name, self.stats(&course)?);
+        }
 
-        write!(f, "</workout_file>\n")?;
-        Ok(())
+        Ok(course)
     }
-}
 
-impl Course {
-    fn write_header(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "\t<author>{}</author>\n", self.author)?;
-        write!(f, "\t<name>{}</name>\n", self.name)?;
+    /// Prints the statistics of a single workout to stdout
+    fn print_stats(&self, ftp: u16, path: &Path, json: bool) -> Result<(), Error> {
+        let course = self.convert(&name_from_file(path), ftp, path)?;
+        let stats = self.stats(&course)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        } else {
+            print!("{}", stats);
+        }
+        Ok(())
+    }
 
-        if let Some(description) = &self.description {
-            write!(f, "\t<description>{}</description>\n", description)?;
+    fn print_zones(&self, ftp: u16, path: &Path, json: bool) -> Result<(), Error> {
+        let course = self.convert(&name_from_file(path), ftp, path)?;
+        let stats = self.stats(&course)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&stats.zones)?);
         } else {
-            write!(f, "\t<description/>\n")?;
+            print!("{}", zone_table(&stats.zones, course.ftp));
         }
+        Ok(())
+    }
 
-        write!(f, "\t<sportType>{}</sportType>\n", self.sport_type)?;
-        write!(f, "\t<tags/>\n")?;
+    fn stats(&self, course: &Course) -> Result<Stats, Error> {
+        if course.duration_type == DurationType::Distance {
+            return Err(Error::Validation("statistics need time based workouts".to_string()));
+        }
+        Ok(Stats::of(course, &self.zone_model))
+    }
 
+    fn write(&self, course: &Course, output: Option<PathBuf>) -> Result<(), Error> {
+        let mut sink = self.sink(output)?;
+        self.format.writer(self.xml_style(), &self.zone_model).write(course, &mut sink)?;
+        sink.flush()?;
         Ok(())
     }
 
-    fn write_sections(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "\t<workout>\n")?;
+    fn xml_style(&self) -> XmlStyle {
+        XmlStyle {
+            declaration: match (self.xml_declaration, self.no_xml_declaration) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            indent: if self.minify { Some(Indent::None) } else { self.indent },
+            newline: self.newline,
+        }
+    }
 
-        for sec in &self.sections {
-            if sec.text.len() == 0 {
-                write!(f, "\t\t<SteadyState Duration=\"{}\" Power=\"{}\" pace=\"0\"/>\n", sec.duration, sec.power)?;
-            } else {
-                write!(f, "\t\t<SteadyState Duration=\"{}\" Power=\"{}\" pace=\"0\">\n", sec.duration, sec.power)?;
-                for hint in &sec.text {
-                    write!(f, "\t\t\t<textevent timeoffset=\"{}\" message=\"{}\"/>\n", hint.offset, hint.text)?;
-                }
-                write!(f, "\t\t</SteadyState>\n")?;
-            }
+    /// Format of the individual files, archives contain ZWO files
+    fn file_format(&self) -> Format {
+        match self.format {
+            Format::Zip => Format::Zwo,
+            format => format,
         }
+    }
 
-        write!(f, "\t</workout>\n")?;
-        Ok(())
+    /// Opens the given output file, or stdout
+    fn sink(&self, output: Option<PathBuf>) -> std::io::Result<Box<dyn Write>> {
+        match output {
+            Some(path) => Ok(Box::new(BufWriter::new(create(&path, self.force)?))),
+            None => Ok(Box::new(std::io::stdout().lock())),
+        }
     }
-}
 
-fn round(offset: u32, step: u32) -> u32 {
-    max(step, ((offset as f64 / step as f64).round()) as u32 * step)
-}
+    fn converter(&self, ftp: u16) -> Converter {
+        Converter {
+            ftp,
+            acceleration: self.acceleration,
+            scale: self.scale,
+            preserve_tss: self.preserve_tss,
+            min_power: self.min_power.map(|limit| limit.relative(ftp)),
+            max_power: self.max_power.map(|limit| limit.relative(ftp)),
+            raster: self.raster,
+            rounding: self.round,
+            min_duration: self.min_duration,
+            anchor: self.anchor,
+            warmup: self.warmup,
+            cooldown: self.cooldown,
+            ramp_tolerance: self.ramp_tolerance,
+            intervals: !self.no_intervals,
+            merge_tolerance: self.merge_tolerance,
+            simplify: self.simplify.map(|tolerance| tolerance.relative(ftp)),
+            max_section: self.max_section,
+            section_progress: self.section_progress,
+            free_ride: self.free_ride,
+            flat_road: self.flat_road,
+            gap: self.gap,
+            gap_after: self.gap_after,
+            gap_power: self.gap_power,
+        }
+    }
+
+    fn reader(&self, ftp: u16, path: &Path, strict: bool) -> Box<dyn InputReader> {
+        let time_mode = if self.time_mode == "duration" { TimeMode::Duration } else { TimeMode::Time };
 
-#[test]
-fn test_round() {
-    assert_eq!(30, round(0, 30));
-    assert_eq!(30, round(10,30));
-    assert_eq!(10, round(12,5));
-    assert_eq!(30, round(20, 30));
-    assert_eq!(30, round(30, 30));
-    assert_eq!(30, round(40, 30));
-    assert_eq!(60, round(50, 30));
+        match self.input_format.unwrap_or_else(|| InputFormat::from_path(path)) {
+            InputFormat::Csv => Box::new(CsvReader {
+                time_format: self.time_format.clone(),
+                power_unit: self.power_unit.unwrap_or_default(),
+                zones: self.zone_model.clone(),
+                lthr: self.lthr,
+                threshold_pace: self.threshold_seconds,
+                pace_unit: self.pace_unit,
+                columns: Columns {
+                    time: self.col_time.clone(),
+                    power: self.col_power.clone(),
+                    text: self.col_text.clone(),
+                    cadence: self.col_cadence.clone(),
+                    hr: self.col_hr.clone(),
+                    distance: self.col_distance.clone(),
+                    text_duration: self.col_text_duration.clone(),
+                },
+                has_header: !self.no_header,
+                delimiter: self.delimiter,
+                encoding: self.encoding,
+                strict,
+                sort: self.sort_input,
+                ..CsvReader::new(time_mode, ftp)
+            }),
+            // average recorded samples over the raster to get one step per section
+            InputFormat::Fit => Box::new(FitReader::new(self.raster)),
+            InputFormat::Tcx => Box::new(TcxReader::new(self.raster)),
+            InputFormat::Gpx => Box::new(GpxReader {
+                interval: self.raster,
+                weight: self.weight,
+                cda: self.cda,
+                crr: self.crr,
+            }),
+            InputFormat::Erg | InputFormat::Mrc => Box::new(ErgReader::new(ftp)),
+            InputFormat::Zwo => Box::new(ZwoReader::new(ftp, self.raster)),
+            InputFormat::Json => Box::new(JsonReader { sort: self.sort_input, ..JsonReader::new(time_mode, ftp) }),
+            InputFormat::Yaml => Box::new(YamlReader::new(ftp)),
+            InputFormat::Xlsx => Box::new(XlsxReader { sort: self.sort_input, ..XlsxReader::new(time_mode) }),
+        }
+    }
 }
@@ -0,0 +1,179 @@
+//! Man page generated from the command line definition
+
+use std::io::Write;
+
+use clap::{Arg, Command};
+
+const DESCRIPTION: &str = "\
+course2zwift creates Zwift workouts from table data with time, power and optional text columns. \
+Every row starts a section of constant power, which is rasterized to whole multiples of the raster size. \
+The workout can be scaled by time and power, and written as ZWO, FIT, TCX, ERG, MRC and other formats.";
+
+const CSV_FORMAT: &[(&str, &str)] = &[
+    ("time", "Start of the row as h:mm:ss, m:ss or seconds, or its length with --time-mode duration."),
+    ("distance", "Start of the row in meters instead of time, which makes the workout distance based."),
+    ("power", "Absolute watts, percent of FTP like 75%, a zone label like Z2 or SS, or a pace like 4:30 for run workouts. \
+        Rows without power only add their text to the current section."),
+    ("text", "Message shown in Zwift. A suffix like @30s sets how long it is shown, several messages are separated by |, \
+        and an offset like +10s moves a message relative to the row."),
+    ("cadence", "Optional target cadence in rpm."),
+    ("hr", "Optional heart rate in bpm, used for rows without power with --lthr."),
+    ("text_duration", "Optional time to show the text, like 30s."),
+    ("raster", "Optional raster of the section starting in the row, like 5s, instead of --raster."),
+    ("key", "Optional mark like x of key intervals, which keep their exact start and duration with --anchor."),
+];
+
+const EXIT_STATUS: &[(&str, &str)] = &[
+    ("0", "Success."),
+    ("2", "Unknown option or missing argument."),
+    ("64", "Invalid option values or options that don't fit together."),
+    ("65", "Malformed input, like an invalid time in a CSV line."),
+    ("66", "An input file does not exist."),
+    ("74", "Reading or writing failed otherwise."),
+];
+
+const TIME_MODES: &[(&str, &str)] = &[
+    ("time", "The time column holds the start of every row, counted from the start of the workout. This is the default."),
+    ("duration", "The time column holds how long every row lasts, so rows can be moved around without changing the others."),
+];
+
+/// Writes the man page in roff format, with the options and subcommands of the command
+pub fn man_page(mut command: Command, sink: &mut dyn Write) -> std::io::Result<()> {
+    // adds the help options and propagates global options to the subcommands
+    command.build();
+    let name = command.get_name().to_string();
+
+    writeln!(sink, ".TH {} 1 \"\" \"{} {}\"", name.to_uppercase(), name, env!("CARGO_PKG_VERSION"))?;
+    writeln!(sink, ".SH NAME")?;
+    writeln!(sink, "{} \\- create Zwift workouts from CSV files", name)?;
+
+    writeln!(sink, ".SH SYNOPSIS")?;
+    writeln!(sink, "\\fB{}\\fR [\\fIOPTIONS\\fR] \\fINAME\\fR \\fIFTP\\fR \\fIFILES\\fR...", name)?;
+    writeln!(sink, ".br")?;
+    writeln!(sink, "\\fB{}\\fR \\fICOMMAND\\fR [\\fIOPTIONS\\fR] ...", name)?;
+
+    writeln!(sink, ".SH DESCRIPTION")?;
+    writeln!(sink, "{}", escape(DESCRIPTION))?;
+
+    writeln!(sink, ".SH OPTIONS")?;
+    for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        write_arg(arg, sink)?;
+    }
+
+    writeln!(sink, ".SH COMMANDS")?;
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help") {
+        let positionals: Vec<String> = sub.get_positionals().map(value_name).collect();
+        writeln!(sink, ".TP")?;
+        writeln!(sink, "\\fB{}\\fR {}", sub.get_name(), positionals.iter().map(|value| format!("\\fI{}\\fR", value)).collect::<Vec<_>>().join(" "))?;
+        writeln!(sink, "{}", escape(&sub.get_about().map(|about| about.to_string()).unwrap_or_default()))?;
+        // global options are listed above already
+        let options: Vec<&Arg> = sub.get_arguments().filter(|arg| !arg.is_positional() && !arg.is_global_set() && !arg.is_hide_set() && arg.get_id() != "help").collect();
+        if !options.is_empty() {
+            writeln!(sink, ".RS")?;
+            for arg in options {
+                write_arg(arg, sink)?;
+            }
+            writeln!(sink, ".RE")?;
+        }
+    }
+
+    writeln!(sink, ".SH CSV FORMAT")?;
+    writeln!(sink, "The first row names the columns, the others hold one step each:")?;
+    write_list(CSV_FORMAT, sink)?;
+
+    writeln!(sink, ".SH TIME MODES")?;
+    write_list(TIME_MODES, sink)?;
+
+    writeln!(sink, ".SH FILES")?;
+    writeln!(sink, ".TP")?;
+    writeln!(sink, "\\fI~/.config/{}/config.toml\\fR", name)?;
+    writeln!(sink, "Defaults for author, FTP, sport type, raster, zones, format and output directory, as key = value lines.")?;
+
+    writeln!(sink, ".SH ENVIRONMENT")?;
+    writeln!(sink, "Every option can be set as variable named C2Z_ and the option in upper case, like C2Z_RASTER. \
+        C2Z_FTP replaces a left out FTP argument. \
+        Command line options take precedence over the environment, which takes precedence over the config file.")?;
+
+    writeln!(sink, ".SH EXIT STATUS")?;
+    write_list(EXIT_STATUS, sink)?;
+
+    writeln!(sink, ".SH EXAMPLES")?;
+    writeln!(sink, ".nf")?;
+    writeln!(sink, "{} \"Sweet Spot\" 250 sweet_spot.csv > sweet_spot.zwo", name)?;
+    writeln!(sink, "{} \\-\\-format fit \\-\\-raster 15 \"Sweet Spot\" 250 sweet_spot.csv \\-o sweet_spot.fit", name)?;
+    writeln!(sink, "{} stats 250 sweet_spot.csv", name)?;
+    writeln!(sink, ".fi")
+}
+
+fn write_arg(arg: &Arg, sink: &mut dyn Write) -> std::io::Result<()> {
+    let mut flags = Vec::new();
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("\\fB\\-{}\\fR", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let takes_value = arg.get_num_args().is_none_or(|range| range.takes_values());
+    let value = if takes_value { format!(" \\fI{}\\fR", value_name(arg)) } else { String::new() };
+
+    writeln!(sink, ".TP")?;
+    if arg.is_positional() {
+        writeln!(sink, "\\fI{}\\fR", value_name(arg))?;
+    } else {
+        writeln!(sink, "{}{}", flags.join(", "), value)?;
+    }
+
+    let mut text = arg.get_help().map(|help| help.to_string()).unwrap_or_default();
+    let defaults: Vec<String> = arg.get_default_values().iter().map(|value| value.to_string_lossy().into_owned()).collect();
+    if takes_value && !defaults.is_empty() {
+        text.push_str(&format!(" [default: {}]", defaults.join(",")));
+    }
+    if let Some(variable) = arg.get_env() {
+        text.push_str(&format!(" [env: {}]", variable.to_string_lossy()));
+    }
+    writeln!(sink, "{}", escape(&text))
+}
+
+fn write_list(items: &[(&str, &str)], sink: &mut dyn Write) -> std::io::Result<()> {
+    for (name, text) in items {
+        writeln!(sink, ".TP")?;
+        writeln!(sink, "\\fB{}\\fR", escape(name))?;
+        writeln!(sink, "{}", escape(text))?;
+    }
+    Ok(())
+}
+
+fn value_name(arg: &Arg) -> String {
+    match arg.get_value_names() {
+        Some(names) if !names.is_empty() => names.iter().map(|name| name.to_string()).collect::<Vec<_>>().join(" "),
+        _ => arg.get_id().to_string().to_uppercase(),
+    }
+}
+
+/// Escapes backslashes and hyphens, and lines that would start a request
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    text.lines()
+        .map(|line| if line.starts_with('.') || line.starts_with('\'') { format!("\\&{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_man_page() {
+    let command = Command::new("c2z")
+        .arg(Arg::new("name").help("course name").required(true))
+        .arg(Arg::new("raster").short('r').long("raster").default_value("30").env("C2Z_RASTER").help("duration rasterization in seconds").global(true))
+        .subcommand(Command::new("stats").about("Print stats").arg(Arg::new("file")).arg(Arg::new("json").long("json").action(clap::ArgAction::SetTrue).help("print JSON")));
+
+    let mut out = Vec::new();
+    man_page(command, &mut out).unwrap();
+    let page = String::from_utf8(out).unwrap();
+    assert!(page.starts_with(".TH C2Z 1 \"\" \"c2z "));
+    assert!(page.contains(".TP\n\\fINAME\\fR\ncourse name\n"));
+    assert!(page.contains(".TP\n\\fB\\-r\\fR, \\fB\\-\\-raster\\fR \\fIRASTER\\fR\nduration rasterization in seconds [default: 30] [env: C2Z_RASTER]\n"));
+    assert!(page.contains(".TP\n\\fBstats\\fR \\fIFILE\\fR\nPrint stats\n.RS\n.TP\n\\fB\\-\\-json\\fR\nprint JSON\n.RE\n"));
+    assert!(page.contains(".SH CSV FORMAT\n"));
+    assert!(page.contains("\\fBduration\\fR\n"));
+    assert_eq!("\\&.hidden \\e \\-x", escape(".hidden \\ -x"));
+}
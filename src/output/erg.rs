@@ -0,0 +1,104 @@
+use std::io::Write;
+
+use super::OutputWriter;
+use crate::{Course, DurationType};
+
+/// How long text messages without a duration are shown, in seconds
+const TEXT_DURATION: u32 = 10;
+
+/// Writes ERG files with absolute watts, based on the course FTP
+#[derive(Debug, Default)]
+pub struct ErgWriter {}
+
+impl OutputWriter for ErgWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        write_course_file(course, sink, Unit::Watts)
+    }
+}
+
+impl ErgWriter {
+    pub fn new() -> Self {
+        ErgWriter {}
+    }
+}
+
+/// Power unit of the course data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Unit {
+    /// absolute watts (ERG)
+    Watts,
+    /// percent of FTP (MRC)
+    Percent,
+}
+
+/// Writes the course header, data and text blocks shared by ERG and MRC files
+pub(super) fn write_course_file(course: &Course, sink: &mut dyn Write, unit: Unit) -> std::io::Result<()> {
+    if course.duration_type == DurationType::Distance {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "ERG and MRC files only support time based durations"));
+    }
+
+    writeln!(sink, "[COURSE HEADER]")?;
+    writeln!(sink, "VERSION = 2")?;
+    writeln!(sink, "UNITS = ENGLISH")?;
+    writeln!(sink, "DESCRIPTION = {}", course.description.as_deref().unwrap_or(&course.name))?;
+    writeln!(sink, "FILE NAME = {}", course.name)?;
+    match unit {
+        Unit::Watts => {
+            writeln!(sink, "FTP = {}", course.ftp)?;
+            writeln!(sink, "MINUTES WATTS")?;
+        },
+        Unit::Percent => writeln!(sink, "MINUTES PERCENT")?,
+    }
+    writeln!(sink, "[END COURSE HEADER]")?;
+
+    writeln!(sink, "[COURSE DATA]")?;
+    let mut start = 0;
+    let value = |power: f64| match unit {
+        Unit::Watts => (power * course.ftp as f64).round(),
+        Unit::Percent => (power * 100.0).round(),
+    };
+    for (duration, from, to) in course.sections.iter().flat_map(|sec| sec.parts()) {
+        // ramps are drawn as a line between start and end power
+        writeln!(sink, "{:.2}\t{}", start as f64 / 60.0, value(from))?;
+        writeln!(sink, "{:.2}\t{}", (start + duration) as f64 / 60.0, value(to))?;
+        start += duration;
+    }
+    writeln!(sink, "[END COURSE DATA]")?;
+
+    if course.sections.iter().any(|sec| !sec.text.is_empty()) {
+        writeln!(sink, "[COURSE TEXT]")?;
+        let mut start = 0;
+        for sec in &course.sections {
+            for hint in &sec.text {
+                writeln!(sink, "{}\t{}\t{}", start + hint.offset, hint.text, hint.duration.unwrap_or(TEXT_DURATION))?;
+            }
+            start += sec.duration;
+        }
+        writeln!(sink, "[END COURSE TEXT]")?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_write() {
+    use crate::{Hint, Kind, Section};
+
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![
+            Section { start: 0, duration: 90, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![] },
+            Section { start: 90, duration: 30, power: 1.2, kind: Kind::SteadyState, cadence: None, text: vec![Hint { offset: 10, text: "Go!".to_string(), duration: None }] },
+        ],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ErgWriter::new().write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("FTP = 200\n"));
+    assert!(out.contains("[COURSE DATA]\n0.00\t100\n1.50\t100\n1.50\t240\n2.00\t240\n[END COURSE DATA]\n"));
+    assert!(out.contains("[COURSE TEXT]\n100\tGo!\t10\n[END COURSE TEXT]\n"));
+}
@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use super::OutputWriter;
+use crate::fit::{Encoder, Value, MESG_FILE_ID, MESG_WORKOUT, MESG_WORKOUT_STEP};
+use crate::{Course, DurationType, Kind, Sport};
+
+const FILE_TYPE_WORKOUT: u8 = 5;
+const MANUFACTURER_DEVELOPMENT: u16 = 255;
+const SPORT_RUNNING: u8 = 1;
+const SPORT_CYCLING: u8 = 2;
+const DURATION_TYPE_TIME: u8 = 0;
+const DURATION_TYPE_DISTANCE: u8 = 1;
+const TARGET_TYPE_POWER: u8 = 4;
+const TARGET_TYPE_OPEN: u8 = 2;
+const INTENSITY_ACTIVE: u8 = 0;
+
+/// Writes Garmin FIT workout files
+#[derive(Debug, Default)]
+pub struct FitWriter {}
+
+impl OutputWriter for FitWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        let mut encoder = Encoder::new();
+
+        encoder.message(MESG_FILE_ID, &[
+            (0, Value::Enum(FILE_TYPE_WORKOUT)),
+            (1, Value::UInt16(MANUFACTURER_DEVELOPMENT)),
+            (2, Value::UInt16(0)),
+        ]);
+
+        let sport = match course.sport_type {
+            Sport::Run => SPORT_RUNNING,
+            Sport::Bike => SPORT_CYCLING,
+        };
+        // intervals are written as individual steps
+        let steps: usize = course.sections.iter().map(|sec| sec.parts().len()).sum();
+        encoder.message(MESG_WORKOUT, &[
+            (4, Value::Enum(sport)),
+            (6, Value::UInt16(steps as u16)),
+            (8, string(&course.name)),
+        ]);
+
+        let mut index = 0;
+        for sec in &course.sections {
+            for (part, (duration, from, to)) in sec.parts().into_iter().enumerate() {
+                // power targets up to 1000 are interpreted as %FTP, ramps become a range
+                let (low, high) = ((from.min(to) * 100.0).round() as u32, (from.max(to) * 100.0).round() as u32);
+
+                // durations are given in milliseconds or centimeters
+                let (duration_type, duration) = match course.duration_type {
+                    DurationType::Time => (DURATION_TYPE_TIME, duration * 1000),
+                    DurationType::Distance => (DURATION_TYPE_DISTANCE, duration * 100),
+                };
+                let mut fields = vec![
+                    (254, Value::UInt16(index)),
+                    (1, Value::Enum(duration_type)),
+                    (2, Value::UInt32(duration)),
+                    (7, Value::Enum(INTENSITY_ACTIVE)),
+                ];
+                if matches!(sec.kind, Kind::FreeRide { .. }) {
+                    fields.push((3, Value::Enum(TARGET_TYPE_OPEN)));
+                } else {
+                    fields.extend([
+                        (3, Value::Enum(TARGET_TYPE_POWER)),
+                        (4, Value::UInt32(0)),
+                        (5, Value::UInt32(low)),
+                        (6, Value::UInt32(high)),
+                    ]);
+                }
+
+                // FIT has no timed text events, so keep the hints as notes of the first step
+                if part == 0 && !sec.text.is_empty() {
+                    let notes: Vec<&str> = sec.text.iter().map(|hint| &hint.text as &str).collect();
+                    fields.push((8, string(&notes.join(" / "))));
+                }
+
+                encoder.message(MESG_WORKOUT_STEP, &fields);
+                index += 1;
+            }
+        }
+
+        sink.write_all(&encoder.finish())
+    }
+}
+
+impl FitWriter {
+    pub fn new() -> Self {
+        FitWriter {}
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string(), (s.len() + 1).min(255) as u8)
+}
+
+#[test]
+fn test_write() {
+    use crate::fit::decode;
+    use crate::Section;
+
+    let section = |start, duration, power, kind| Section { start, duration, power, kind, cadence: None, text: vec![] };
+    let course = Course {
+        name: "Test".to_string(),
+        ftp: 250,
+        sections: vec![
+            section(0, 60, 0.5, Kind::SteadyState),
+            section(60, 120, 0.8, Kind::Ramp { end_power: 0.6 }),
+            section(180, 300, 0.0, Kind::FreeRide { flat_road: false }),
+        ],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    FitWriter::new().write(&course, &mut out).unwrap();
+    let steps: Vec<_> = decode(&out).unwrap().into_iter().filter(|message| message.global == MESG_WORKOUT_STEP).collect();
+    let targets: Vec<_> = steps.iter().map(|step| (step.u32(2), step.u8(3), step.u32(4), step.u32(5), step.u32(6))).collect();
+    assert_eq!(vec![
+        (Some(60000), Some(TARGET_TYPE_POWER), Some(0), Some(50), Some(50)),
+        (Some(120000), Some(TARGET_TYPE_POWER), Some(0), Some(60), Some(80)),
+        (Some(300000), Some(TARGET_TYPE_OPEN), None, None, None),
+    ], targets);
+}
@@ -0,0 +1,116 @@
+use std::io::Write;
+
+use quick_xml::escape::escape;
+
+use super::{OutputWriter, SvgWriter};
+use crate::duration::format_duration;
+use crate::stats::Stats;
+use crate::{Course, DurationType, ZoneModel};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #333; }
+svg { max-width: 100%; height: auto; }
+polygon:hover { opacity: 0.75; }
+#info { min-height: 3em; white-space: pre-line; }
+td { padding: 0 1em 0 0; }
+td + td { text-align: right; }";
+
+/// Shows the tooltip of the hovered block or text below the graph
+const SCRIPT: &str = "const info = document.getElementById('info');
+document.querySelectorAll('polygon, circle').forEach(shape => {
+  shape.addEventListener('mouseenter', () => info.textContent = shape.querySelector('title').textContent);
+});";
+
+/// Writes a standalone HTML page with the power profile and the planned load, for reviewing plans in a browser
+#[derive(Debug, Default)]
+pub struct HtmlWriter {
+    /// zones to color the blocks and count the time in zone by
+    pub zones: ZoneModel,
+}
+
+impl OutputWriter for HtmlWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(sink, "<!DOCTYPE html>")?;
+        writeln!(sink, "<html>")?;
+        writeln!(sink, "<head>")?;
+        writeln!(sink, "<meta charset=\"utf-8\">")?;
+        writeln!(sink, "<title>{}</title>", escape(&course.name))?;
+        writeln!(sink, "<style>\n{}\n</style>", STYLE)?;
+        writeln!(sink, "</head>")?;
+        writeln!(sink, "<body>")?;
+        writeln!(sink, "<h1>{}</h1>", escape(&course.name))?;
+        writeln!(sink, "<p>by {}, FTP {} W</p>", escape(&course.author), course.ftp)?;
+        if let Some(description) = &course.description {
+            writeln!(sink, "<p>{}</p>", escape(description))?;
+        }
+
+        SvgWriter::new(self.zones.clone()).write(course, sink)?;
+        writeln!(sink)?;
+        writeln!(sink, "<p id=\"info\"></p>")?;
+
+        // power statistics are based on time
+        if course.duration_type == DurationType::Time {
+            self.write_stats(&Stats::of(course, &self.zones), sink)?;
+        }
+
+        writeln!(sink, "<script>\n{}\n</script>", SCRIPT)?;
+        writeln!(sink, "</body>")?;
+        writeln!(sink, "</html>")
+    }
+}
+
+impl HtmlWriter {
+    pub fn new(zones: ZoneModel) -> Self {
+        HtmlWriter { zones }
+    }
+
+    fn write_stats(&self, stats: &Stats, sink: &mut dyn Write) -> std::io::Result<()> {
+        let rows = [
+            ("Duration", format_duration(stats.duration)),
+            ("Average power", format!("{:.0} W", stats.average_power)),
+            ("Normalized power", format!("{:.0} W", stats.normalized_power)),
+            ("Intensity factor", format!("{:.2}", stats.intensity_factor)),
+            ("TSS", format!("{:.0}", stats.tss)),
+            ("Work", format!("{:.0} kJ", stats.work)),
+        ];
+
+        writeln!(sink, "<table>")?;
+        for (label, value) in rows {
+            writeln!(sink, "<tr><td>{}</td><td>{}</td></tr>", label, value)?;
+        }
+        for time in &stats.zones {
+            writeln!(sink, "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>", escape(&time.zone), format_duration(time.duration), time.share)?;
+        }
+        writeln!(sink, "</table>")
+    }
+}
+
+#[test]
+fn test_write() {
+    use crate::{Kind, Section};
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut course = Course {
+        name: "Over & Under".to_string(),
+        description: Some("Hard".to_string()),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![section(0, 1800, 1.0), section(1800, 1800, 0.5)],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    HtmlWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("<!DOCTYPE html>\n<html>\n"));
+    assert!(out.contains("<title>Over &amp; Under</title>"));
+    assert!(out.contains("<svg xmlns="));
+    assert!(out.contains("<tr><td>Duration</td><td>1h</td></tr>"));
+    assert!(out.contains("<tr><td>Average power</td><td>150 W</td></tr>"));
+    assert!(out.contains("<tr><td>Z4</td><td>30m</td><td>50%</td></tr>"));
+    assert!(out.ends_with("</html>\n"));
+
+    course.duration_type = DurationType::Distance;
+    let mut out = Vec::new();
+    HtmlWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains("<table>"));
+}
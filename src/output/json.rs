@@ -0,0 +1,64 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::OutputWriter;
+use crate::stats::{Stats, ZoneTime};
+use crate::{Course, DurationType, ZoneModel};
+
+/// Writes the course model as JSON, for further analysis
+#[derive(Debug, Default)]
+pub struct JsonWriter {
+    /// zones to report the time in zone for
+    pub zones: ZoneModel,
+}
+
+/// The course with its planned time in zone, which only time based workouts have
+#[derive(Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    course: &'a Course,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zones: Option<Vec<ZoneTime>>,
+}
+
+impl OutputWriter for JsonWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        let zones = (course.duration_type == DurationType::Time).then(|| Stats::of(course, &self.zones).zones);
+        serde_json::to_writer_pretty(&mut *sink, &Report { course, zones })?;
+        writeln!(sink)
+    }
+}
+
+impl JsonWriter {
+    pub fn new(zones: ZoneModel) -> Self {
+        JsonWriter { zones }
+    }
+}
+
+#[test]
+fn test_write() {
+    use crate::{Kind, Section};
+
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![Section { start: 0, duration: 600, power: 0.65, kind: Kind::SteadyState, cadence: None, text: vec![] }],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    JsonWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!("Test", json["name"]);
+    assert_eq!("Z2", json["zones"][1]["zone"]);
+    assert_eq!(600, json["zones"][1]["duration"]);
+    assert_eq!(100.0, json["zones"][1]["share"]);
+
+    course.duration_type = DurationType::Distance;
+    let mut out = Vec::new();
+    JsonWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert!(json.get("zones").is_none());
+}
@@ -0,0 +1,173 @@
+//! Writers serializing a [`Course`] into various target formats
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::{Course, ZoneModel};
+
+mod erg;
+mod fit;
+mod html;
+mod json;
+mod mrc;
+#[cfg(feature = "png")]
+mod png;
+mod svg;
+mod tcx;
+mod xml;
+mod zip;
+mod zwo;
+
+pub use self::erg::ErgWriter;
+pub use self::fit::FitWriter;
+pub use self::html::HtmlWriter;
+pub use self::json::JsonWriter;
+pub use self::mrc::MrcWriter;
+#[cfg(feature = "png")]
+pub use self::png::PngWriter;
+pub use self::svg::SvgWriter;
+pub use self::tcx::TcxWriter;
+pub use self::xml::{Indent, Newline, XmlStyle};
+pub use self::zip::ZipWriter;
+pub use self::zwo::ZwoWriter;
+
+/// A target format a course can be written to
+pub trait OutputWriter {
+    /// Serializes the whole course into the given sink
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()>;
+}
+
+/// Available output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Zwift workout file
+    Zwo,
+    /// Garmin FIT workout file
+    Fit,
+    /// TCX structured workout
+    Tcx,
+    /// ERG file with absolute watts
+    Erg,
+    /// MRC file with power relative to FTP
+    Mrc,
+    /// JSON representation of the course model
+    Json,
+    /// ZIP archive of ZWO files
+    Zip,
+    /// SVG graph of the power profile
+    Svg,
+    /// PNG image of the power profile
+    #[cfg(feature = "png")]
+    Png,
+    /// HTML page with the power profile and statistics
+    Html,
+}
+
+impl Format {
+    /// All formats, e.g. to list them
+    pub fn all() -> &'static [Format] {
+        &[
+            Format::Zwo, Format::Fit, Format::Tcx, Format::Erg, Format::Mrc, Format::Json, Format::Zip, Format::Svg,
+            #[cfg(feature = "png")]
+            Format::Png,
+            Format::Html,
+        ]
+    }
+
+    /// File extension without dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Zwo => "zwo",
+            Format::Fit => "fit",
+            Format::Tcx => "tcx",
+            Format::Erg => "erg",
+            Format::Mrc => "mrc",
+            Format::Json => "json",
+            Format::Zip => "zip",
+            Format::Svg => "svg",
+            #[cfg(feature = "png")]
+            Format::Png => "png",
+            Format::Html => "html",
+        }
+    }
+
+    /// Creates the writer for this format, XML formats are laid out in the given style, graphs colored by the zones
+    pub fn writer(&self, style: XmlStyle, zones: &ZoneModel) -> Box<dyn OutputWriter> {
+        match self {
+            Format::Zwo => Box::new(ZwoWriter::with_style(style)),
+            Format::Fit => Box::new(FitWriter::new()),
+            Format::Tcx => Box::new(TcxWriter::with_style(style)),
+            Format::Erg => Box::new(ErgWriter::new()),
+            Format::Mrc => Box::new(MrcWriter::new()),
+            Format::Json => Box::new(JsonWriter::new(zones.clone())),
+            Format::Zip => Box::new(ZipWriter::with_style(style)),
+            Format::Svg => Box::new(SvgWriter::with_style(style, zones.clone())),
+            #[cfg(feature = "png")]
+            Format::Png => Box::new(PngWriter::new(zones.clone())),
+            Format::Html => Box::new(HtmlWriter::new(zones.clone())),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zwo" => Ok(Format::Zwo),
+            "fit" => Ok(Format::Fit),
+            "tcx" => Ok(Format::Tcx),
+            "erg" => Ok(Format::Erg),
+            "mrc" => Ok(Format::Mrc),
+            "json" => Ok(Format::Json),
+            "zip" => Ok(Format::Zip),
+            "svg" => Ok(Format::Svg),
+            #[cfg(feature = "png")]
+            "png" => Ok(Format::Png),
+            "html" => Ok(Format::Html),
+            _ => Err(format!("unknown format \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Format::Zwo => write!(f, "zwo"),
+            Format::Fit => write!(f, "fit"),
+            Format::Tcx => write!(f, "tcx"),
+            Format::Erg => write!(f, "erg"),
+            Format::Mrc => write!(f, "mrc"),
+            Format::Json => write!(f, "json"),
+            Format::Zip => write!(f, "zip"),
+            Format::Svg => write!(f, "svg"),
+            #[cfg(feature = "png")]
+            Format::Png => write!(f, "png"),
+            Format::Html => write!(f, "html"),
+        }
+    }
+}
+
+/// Derives a file name from the workout name, e.g. "Sweet Spot 3x15" becomes "Sweet_Spot_3x15.zwo"
+pub fn file_name(name: &str, format: Format) -> String {
+    let mut stem = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() || c == '-' || c == '.' {
+            stem.push(c);
+        } else if !stem.ends_with('_') {
+            stem.push('_');
+        }
+    }
+
+    let stem = stem.trim_matches(|c| c == '_' || c == '.');
+    let stem = if stem.is_empty() { "workout" } else { stem };
+    format!("{}.{}", stem, format.extension())
+}
+
+#[test]
+fn test_file_name() {
+    assert_eq!("Sweet_Spot_3x15.zwo", file_name("Sweet Spot 3x15", Format::Zwo));
+    assert_eq!("Over-Unders_4_2.fit", file_name(" Over-Unders: 4 / 2 ", Format::Fit));
+    assert_eq!("workout.zwo", file_name("../", Format::Zwo));
+}
@@ -0,0 +1,56 @@
+use std::io::Write;
+
+use super::erg::{write_course_file, Unit};
+use super::OutputWriter;
+use crate::Course;
+
+/// Writes MRC files with power relative to FTP
+#[derive(Debug, Default)]
+pub struct MrcWriter {}
+
+impl OutputWriter for MrcWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        write_course_file(course, sink, Unit::Percent)
+    }
+}
+
+impl MrcWriter {
+    pub fn new() -> Self {
+        MrcWriter {}
+    }
+}
+
+#[test]
+fn test_write() {
+    use crate::{Kind, Section};
+
+    let course = Course {
+        name: "Test".to_string(),
+        description: Some("Short one".to_string()),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![
+            Section { start: 0, duration: 90, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![] },
+            Section { start: 90, duration: 30, power: 1.25, kind: Kind::SteadyState, cadence: None, text: vec![] },
+        ],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    MrcWriter::new().write(&course, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "\
+[COURSE HEADER]
+VERSION = 2
+UNITS = ENGLISH
+DESCRIPTION = Short one
+FILE NAME = Test
+MINUTES PERCENT
+[END COURSE HEADER]
+[COURSE DATA]
+0.00\t50
+1.50\t50
+1.50\t125
+2.00\t125
+[END COURSE DATA]
+");
+}
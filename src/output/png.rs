@@ -0,0 +1,163 @@
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::OutputWriter;
+use crate::chart::{power_at, zone_index, FREE_RIDE_FILL, MIN_TOP, ZONE_COLORS};
+use crate::{Course, ZoneModel};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 300;
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+const FILTER_NONE: u8 = 0;
+const BACKGROUND: [u8; 3] = [0xff, 0xff, 0xff];
+const GRID: [u8; 3] = [0x99, 0x99, 0x99];
+const MARKER: [u8; 3] = [0x33, 0x33, 0x33];
+/// Height of the strip with text markers at the top, in pixels
+const MARKER_STRIP: u32 = 10;
+
+/// Writes the power profile as PNG image, colored by zone, for places that don't display SVG
+#[derive(Debug, Default)]
+pub struct PngWriter {
+    /// zones to color the blocks by
+    pub zones: ZoneModel,
+}
+
+impl OutputWriter for PngWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        let pixels = self.render(course);
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&WIDTH.to_be_bytes());
+        header.extend_from_slice(&HEIGHT.to_be_bytes());
+        header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGB, 0, 0, 0]);
+
+        // every row is prefixed with its filter type
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in pixels.chunks((WIDTH * 3) as usize) {
+            encoder.write_all(&[FILTER_NONE])?;
+            encoder.write_all(row)?;
+        }
+        let data = encoder.finish()?;
+
+        sink.write_all(&SIGNATURE)?;
+        write_chunk(sink, b"IHDR", &header)?;
+        write_chunk(sink, b"IDAT", &data)?;
+        write_chunk(sink, b"IEND", &[])
+    }
+}
+
+impl PngWriter {
+    pub fn new(zones: ZoneModel) -> Self {
+        PngWriter { zones }
+    }
+
+    /// Draws the graph into RGB pixels, row by row from the top
+    fn render(&self, course: &Course) -> Vec<u8> {
+        let total: u32 = course.sections.iter().map(|sec| sec.duration).sum();
+        let top = course.sections.iter().map(|sec| sec.power.max(sec.end_power())).fold(MIN_TOP, f64::max);
+        let plot = (HEIGHT - MARKER_STRIP) as f64;
+        let row_of = |power: f64| HEIGHT - (power / top * plot).round() as u32;
+
+        let mut pixels = [BACKGROUND].repeat((WIDTH * HEIGHT) as usize);
+        let mut set = |x: u32, y: u32, color: [u8; 3]| {
+            if x < WIDTH && y < HEIGHT {
+                pixels[(y * WIDTH + x) as usize] = color;
+            }
+        };
+
+        for x in 0..WIDTH {
+            let time = (x as f64 + 0.5) * total as f64 / WIDTH as f64;
+            // free rides are drawn as a low bar
+            let (power, color) = match power_at(course, time) {
+                Some(power) => (power, rgb(ZONE_COLORS[zone_index(&self.zones, power) % ZONE_COLORS.len()])),
+                None if time < total as f64 => (0.5, rgb(FREE_RIDE_FILL)),
+                None => continue,
+            };
+            for y in row_of(power)..HEIGHT {
+                set(x, y, color);
+            }
+        }
+
+        // power lines every 50% FTP, FTP itself dashed
+        let mut power = 0.5;
+        while power <= top {
+            let dash = if power == 1.0 { 8 } else { 2 };
+            for x in (0..WIDTH).filter(|x| x / dash % 2 == 0) {
+                set(x, row_of(power), GRID);
+            }
+            power += 0.5;
+        }
+
+        let mut start = 0;
+        for sec in &course.sections {
+            for hint in &sec.text {
+                let x = ((start + hint.offset) as f64 / total.max(1) as f64 * (WIDTH - 1) as f64).round() as u32;
+                for y in 2..MARKER_STRIP - 2 {
+                    set(x, y, MARKER);
+                    set(x + 1, y, MARKER);
+                }
+            }
+            start += sec.duration;
+        }
+
+        pixels.concat()
+    }
+}
+
+/// Red, green and blue of a color like "#338cff"
+fn rgb(hex: &str) -> [u8; 3] {
+    let channel = |index: usize| u8::from_str_radix(&hex[1 + index * 2..3 + index * 2], 16).unwrap_or(0);
+    [channel(0), channel(1), channel(2)]
+}
+
+/// Writes a chunk with its length and checksum
+fn write_chunk(sink: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    sink.write_all(&(data.len() as u32).to_be_bytes())?;
+    sink.write_all(kind)?;
+    sink.write_all(data)?;
+    sink.write_all(&crc.finalize().to_be_bytes())
+}
+
+#[test]
+fn test_write() {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use crate::{Kind, Section};
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![section(0, 60, 0.6), section(60, 60, 1.3)],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    PngWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    assert_eq!(SIGNATURE, out[..8]);
+    assert_eq!(b"IHDR", &out[12..16]);
+    assert_eq!(WIDTH.to_be_bytes(), out[16..20]);
+    assert_eq!(b"IEND", &out[out.len() - 8..out.len() - 4]);
+
+    let length = u32::from_be_bytes(out[33..37].try_into().unwrap()) as usize;
+    assert_eq!(b"IDAT", &out[37..41]);
+    let mut raw = Vec::new();
+    ZlibDecoder::new(&out[41..41 + length]).read_to_end(&mut raw).unwrap();
+    assert_eq!(((WIDTH * 3 + 1) * HEIGHT) as usize, raw.len());
+
+    // bottom left pixel is endurance, bottom right one is anaerobic
+    let last_row = &raw[raw.len() - (WIDTH * 3) as usize..];
+    assert_eq!(rgb(ZONE_COLORS[1]), last_row[..3]);
+    assert_eq!(rgb(ZONE_COLORS[5]), last_row[last_row.len() - 3..]);
+}
@@ -0,0 +1,225 @@
+use std::io::Write;
+
+use quick_xml::events::BytesText;
+use quick_xml::Writer;
+
+use super::{Indent, OutputWriter, XmlStyle};
+use crate::chart::{zone_index, FREE_RIDE_FILL, MIN_TOP, ZONE_COLORS};
+use crate::duration::format_duration;
+use crate::{Course, DurationType, Kind, Section, ZoneModel};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+const LEFT: f64 = 50.0;
+const RIGHT: f64 = 10.0;
+const TOP: f64 = 20.0;
+const BOTTOM: f64 = 30.0;
+/// Most labels on the time axis
+const MAX_TICKS: u32 = 10;
+
+/// Writes the power profile as SVG graph, colored by zone, with markers for the texts
+#[derive(Debug, Default)]
+pub struct SvgWriter {
+    pub style: XmlStyle,
+    /// zones to color the blocks by
+    pub zones: ZoneModel,
+}
+
+impl OutputWriter for SvgWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        let total = course.sections.iter().map(|sec| sec.duration).sum::<u32>().max(1);
+        let top = course.sections.iter().map(|sec| sec.power.max(sec.end_power())).fold(MIN_TOP, f64::max);
+        let graph = Graph { total: total as f64, top };
+
+        self.style.write_document(sink, false, Indent::Spaces(2), |w| {
+            w.create_element("svg")
+                .with_attributes([
+                    ("xmlns", "http://www.w3.org/2000/svg"),
+                    ("width", &WIDTH.to_string()),
+                    ("height", &HEIGHT.to_string()),
+                    ("viewBox", &format!("0 0 {} {}", WIDTH, HEIGHT)),
+                    ("font-family", "sans-serif"),
+                    ("font-size", "11"),
+                ])
+                .write_inner_content(|w| {
+                    w.create_element("title").write_text_content(BytesText::new(&course.name))?;
+                    self.write_blocks(course, &graph, w)?;
+                    self.write_axes(course, &graph, w)?;
+                    self.write_texts(course, &graph, w)
+                })?;
+            Ok(())
+        })
+    }
+}
+
+/// Scales of the plot area
+struct Graph {
+    /// duration in seconds or meters
+    total: f64,
+    /// power at the top, relative to FTP
+    top: f64,
+}
+
+impl Graph {
+    fn x(&self, at: f64) -> f64 {
+        LEFT + at / self.total * (WIDTH - LEFT - RIGHT)
+    }
+
+    fn y(&self, power: f64) -> f64 {
+        TOP + (1.0 - power / self.top) * (HEIGHT - TOP - BOTTOM)
+    }
+}
+
+impl SvgWriter {
+    pub fn new(zones: ZoneModel) -> Self {
+        SvgWriter { style: XmlStyle::default(), zones }
+    }
+
+    pub fn with_style(style: XmlStyle, zones: ZoneModel) -> Self {
+        SvgWriter { style, zones }
+    }
+
+    fn write_blocks<W: Write>(&self, course: &Course, graph: &Graph, w: &mut Writer<W>) -> std::io::Result<()> {
+        w.create_element("g").with_attribute(("class", "blocks")).write_inner_content(|w| {
+            let mut start = 0.0;
+            for sec in &course.sections {
+                let mut offset = 0;
+                for (duration, from, to) in sec.parts() {
+                    let end = start + duration as f64;
+                    let tooltip = block_tooltip(course, sec, offset, duration, from, to);
+                    // free rides have no target, so they are drawn as a low bar
+                    let (from, to, color) = match sec.kind {
+                        Kind::FreeRide { .. } => (0.5, 0.5, FREE_RIDE_FILL),
+                        _ => (from, to, ZONE_COLORS[zone_index(&self.zones, (from + to) / 2.0) % ZONE_COLORS.len()]),
+                    };
+                    let points = format!(
+                        "{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}",
+                        graph.x(start), graph.y(0.0), graph.x(start), graph.y(from), graph.x(end), graph.y(to), graph.x(end), graph.y(0.0),
+                    );
+                    w.create_element("polygon")
+                        .with_attributes([("points", points.as_str()), ("fill", color)])
+                        .write_inner_content(|w| {
+                            w.create_element("title").write_text_content(BytesText::new(&tooltip))?;
+                            Ok(())
+                        })?;
+                    start = end;
+                    offset += duration;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn write_axes<W: Write>(&self, course: &Course, graph: &Graph, w: &mut Writer<W>) -> std::io::Result<()> {
+        w.create_element("g").with_attributes([("class", "axes"), ("stroke", "#999"), ("fill", "#333")]).write_inner_content(|w| {
+            // power lines every 50% FTP, FTP itself dashed
+            let mut power = 0.5;
+            while power <= graph.top {
+                let y = format!("{:.1}", graph.y(power));
+                let dash = if power == 1.0 { "4 3" } else { "1 3" };
+                w.create_element("line")
+                    .with_attributes([("x1", LEFT.to_string().as_str()), ("y1", &y), ("x2", &(WIDTH - RIGHT).to_string()), ("y2", &y), ("stroke-dasharray", dash)])
+                    .write_empty()?;
+                w.create_element("text")
+                    .with_attributes([("x", (LEFT - 5.0).to_string().as_str()), ("y", &y), ("text-anchor", "end"), ("stroke", "none")])
+                    .write_text_content(BytesText::new(&format!("{:.0}%", power * 100.0)))?;
+                power += 0.5;
+            }
+
+            let base = format!("{:.1}", graph.y(0.0));
+            w.create_element("line")
+                .with_attributes([("x1", LEFT.to_string().as_str()), ("y1", &base), ("x2", &(WIDTH - RIGHT).to_string()), ("y2", &base)])
+                .write_empty()?;
+
+            let step = tick_step(graph.total as u32, course.duration_type);
+            let label_y = format!("{:.1}", HEIGHT - BOTTOM + 15.0);
+            for tick in (0..=graph.total as u32).step_by(step as usize) {
+                let label = match course.duration_type {
+                    DurationType::Time => format_duration(tick),
+                    DurationType::Distance => format!("{} m", tick),
+                };
+                w.create_element("text")
+                    .with_attributes([("x", format!("{:.1}", graph.x(tick as f64)).as_str()), ("y", &label_y), ("text-anchor", "middle"), ("stroke", "none")])
+                    .write_text_content(BytesText::new(&label))?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn write_texts<W: Write>(&self, course: &Course, graph: &Graph, w: &mut Writer<W>) -> std::io::Result<()> {
+        w.create_element("g").with_attributes([("class", "texts"), ("fill", "#fff"), ("stroke", "#333")]).write_inner_content(|w| {
+            let mut start = 0;
+            for sec in &course.sections {
+                for hint in &sec.text {
+                    let x = format!("{:.1}", graph.x((start + hint.offset) as f64));
+                    w.create_element("circle")
+                        .with_attributes([("cx", x.as_str()), ("cy", &format!("{:.1}", TOP / 2.0)), ("r", "4")])
+                        .write_inner_content(|w| {
+                            w.create_element("title").write_text_content(BytesText::new(&hint.text))?;
+                            Ok(())
+                        })?;
+                }
+                start += sec.duration;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// Duration, power and texts of a part of a section, shown when hovering it
+fn block_tooltip(course: &Course, sec: &Section, offset: u32, duration: u32, from: f64, to: f64) -> String {
+    let length = match course.duration_type {
+        DurationType::Time => format_duration(duration),
+        DurationType::Distance => format!("{} m", duration),
+    };
+    let watts = |power: f64| (power * course.ftp as f64).round();
+    let mut tooltip = match sec.kind {
+        Kind::FreeRide { .. } => format!("{} free ride", length),
+        _ if from == to => format!("{} at {:.0}% ({} W)", length, from * 100.0, watts(from)),
+        _ => format!("{} from {:.0}% to {:.0}% ({}-{} W)", length, from * 100.0, to * 100.0, watts(from), watts(to)),
+    };
+    for hint in sec.text.iter().filter(|hint| hint.offset >= offset && hint.offset < offset + duration) {
+        tooltip.push('\n');
+        tooltip.push_str(&hint.text);
+    }
+    tooltip
+}
+
+/// Distance between labels on the time axis, so there are at most [`MAX_TICKS`] of them
+fn tick_step(total: u32, duration_type: DurationType) -> u32 {
+    let steps: &[u32] = match duration_type {
+        DurationType::Time => &[60, 300, 600, 900, 1800, 3600, 7200],
+        DurationType::Distance => &[100, 200, 500, 1000, 2000, 5000, 10000],
+    };
+    steps.iter().copied().find(|step| total / step <= MAX_TICKS).unwrap_or(steps[steps.len() - 1])
+}
+
+#[test]
+fn test_write() {
+    use crate::Hint;
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let mut course = Course {
+        name: "Fish & Chips".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![section(0, 1800, 0.6), section(1800, 1800, 1.2)],
+        ..Course::default()
+    };
+    course.sections[1].text.push(Hint { offset: 0, text: "Go <hard>".to_string(), duration: None });
+
+    let mut out = Vec::new();
+    SvgWriter::new(ZoneModel::coggan()).write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\""));
+    assert!(out.contains("<title>Fish &amp; Chips</title>"));
+    assert!(out.contains(&format!("<polygon points=\"50.0,270.0 50.0,145.0 420.0,145.0 420.0,270.0\" fill=\"{}\">", ZONE_COLORS[1])));
+    assert!(out.contains("<title>30m at 60% (120 W)</title>"));
+    assert!(out.contains("<title>30m at 120% (240 W)\nGo &lt;hard&gt;</title>"));
+    assert!(out.contains("<title>Go &lt;hard&gt;</title>"));
+    assert!(out.contains(">1h</text>"));
+    assert_eq!(600, tick_step(3600, DurationType::Time));
+}
@@ -0,0 +1,108 @@
+use std::io::Write;
+
+use quick_xml::events::BytesText;
+use quick_xml::Writer;
+
+use super::{Indent, OutputWriter, XmlStyle};
+use crate::{Course, DurationType, Kind, Sport};
+
+const NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2";
+const NAMESPACE_XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Writes TCX structured workouts.
+/// TCX has no power targets, so the power is kept in the step names.
+#[derive(Debug, Default)]
+pub struct TcxWriter {
+    pub style: XmlStyle,
+}
+
+impl OutputWriter for TcxWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        let sport = match course.sport_type {
+            Sport::Run => "Running",
+            Sport::Bike => "Biking",
+        };
+
+        self.style.write_document(sink, true, Indent::Spaces(2), |writer| {
+            writer.create_element("TrainingCenterDatabase")
+                .with_attributes([("xmlns", NAMESPACE), ("xmlns:xsi", NAMESPACE_XSI)])
+                .write_inner_content(|w| {
+                    w.create_element("Workouts").write_inner_content(|w| {
+                        w.create_element("Workout").with_attribute(("Sport", sport)).write_inner_content(|w| {
+                            w.create_element("Name").write_text_content(BytesText::new(&course.name))?;
+                            self.write_steps(course, w)?;
+                            if let Some(description) = &course.description {
+                                w.create_element("Notes").write_text_content(BytesText::new(description))?;
+                            }
+                            Ok(())
+                        })?;
+                        Ok(())
+                    })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+    }
+}
+
+impl TcxWriter {
+    pub fn new() -> Self {
+        TcxWriter::default()
+    }
+
+    pub fn with_style(style: XmlStyle) -> Self {
+        TcxWriter { style }
+    }
+
+    fn write_steps<W: Write>(&self, course: &Course, w: &mut Writer<W>) -> std::io::Result<()> {
+        // intervals are written as individual steps
+        let parts = course.sections.iter().flat_map(|sec| sec.parts().into_iter().map(move |part| (sec.kind, part)));
+        for (index, (kind, (duration, from, to))) in parts.enumerate() {
+            let (from, to) = ((from * 100.0).round(), (to * 100.0).round());
+            let name = match kind {
+                Kind::FreeRide { .. } => "Free ride".to_string(),
+                _ if from == to => format!("{}% FTP", from),
+                _ => format!("{}-{}% FTP", from, to),
+            };
+
+            w.create_element("Step").with_attribute(("xsi:type", "Step_t")).write_inner_content(|w| {
+                w.create_element("StepId").write_text_content(BytesText::new(&(index + 1).to_string()))?;
+                w.create_element("Name").write_text_content(BytesText::new(&name))?;
+                let (duration_type, unit) = match course.duration_type {
+                    DurationType::Time => ("Time_t", "Seconds"),
+                    DurationType::Distance => ("Distance_t", "Meters"),
+                };
+                w.create_element("Duration").with_attribute(("xsi:type", duration_type)).write_inner_content(|w| {
+                    w.create_element(unit).write_text_content(BytesText::new(&duration.to_string()))?;
+                    Ok(())
+                })?;
+                w.create_element("Intensity").write_text_content(BytesText::new("Active"))?;
+                w.create_element("Target").with_attribute(("xsi:type", "None_t")).write_empty()?;
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write() {
+    use crate::Section;
+
+    let course = Course {
+        name: "Fish & Chips".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![Section { start: 0, duration: 60, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![] }],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    TcxWriter::new().write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<Workout Sport=\"Biking\">"));
+    assert!(out.contains("<Name>Fish &amp; Chips</Name>"));
+    assert!(out.contains("<Name>50% FTP</Name>"));
+    assert!(out.contains("<Seconds>60</Seconds>"));
+}
@@ -0,0 +1,158 @@
+//! Formatting options shared by the XML based writers
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use quick_xml::events::{BytesDecl, Event};
+use quick_xml::Writer;
+
+/// Indentation of nested XML elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// everything on one line
+    None,
+    /// the given number of spaces per level
+    Spaces(usize),
+    /// one tab per level
+    Tab,
+}
+
+impl FromStr for Indent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Indent::None),
+            "tab" => Ok(Indent::Tab),
+            _ => s.parse().map(Indent::Spaces).map_err(|_| format!("unknown indent \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for Indent {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Indent::None => write!(f, "none"),
+            Indent::Spaces(width) => write!(f, "{}", width),
+            Indent::Tab => write!(f, "tab"),
+        }
+    }
+}
+
+/// Line ending between XML elements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Newline {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl FromStr for Newline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Newline::Lf),
+            "crlf" => Ok(Newline::CrLf),
+            _ => Err(format!("unknown newline \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for Newline {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Newline::Lf => write!(f, "lf"),
+            Newline::CrLf => write!(f, "crlf"),
+        }
+    }
+}
+
+/// How XML output is laid out, unset options keep the defaults of the format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XmlStyle {
+    pub declaration: Option<bool>,
+    pub indent: Option<Indent>,
+    pub newline: Newline,
+}
+
+impl XmlStyle {
+    /// Writes a complete document, `root` writes the root element
+    pub(super) fn write_document<F>(&self, sink: &mut dyn Write, declaration: bool, indent: Indent, root: F) -> std::io::Result<()>
+    where
+        F: FnOnce(&mut Writer<&mut dyn Write>) -> std::io::Result<()>,
+    {
+        let mut sink = LineEnding { inner: sink, newline: self.newline };
+        let indent = self.indent.unwrap_or(indent);
+
+        let mut writer = match indent {
+            Indent::None => Writer::new(&mut sink as &mut dyn Write),
+            Indent::Spaces(width) => Writer::new_with_indent(&mut sink as &mut dyn Write, b' ', width),
+            Indent::Tab => Writer::new_with_indent(&mut sink as &mut dyn Write, b'\t', 1),
+        };
+
+        if self.declaration.unwrap_or(declaration) {
+            writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        }
+        root(&mut writer)?;
+
+        if indent != Indent::None {
+            writeln!(sink)?;
+        }
+        Ok(())
+    }
+}
+
+/// Translates line feeds into the configured line ending
+struct LineEnding<'a> {
+    inner: &'a mut dyn Write,
+    newline: Newline,
+}
+
+impl Write for LineEnding<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.newline {
+            Newline::Lf => self.inner.write_all(buf)?,
+            Newline::CrLf => {
+                for (index, line) in buf.split(|&b| b == b'\n').enumerate() {
+                    if index > 0 {
+                        self.inner.write_all(b"\r\n")?;
+                    }
+                    self.inner.write_all(line)?;
+                }
+            },
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_write_document() {
+    let style = XmlStyle { declaration: Some(true), indent: Some(Indent::Tab), newline: Newline::CrLf };
+
+    let mut out = Vec::new();
+    style.write_document(&mut out, false, Indent::Spaces(4), |w| {
+        w.create_element("a").write_inner_content(|w| {
+            w.create_element("b").write_empty()?;
+            Ok(())
+        })?;
+        Ok(())
+    }).unwrap();
+    assert_eq!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n<a>\r\n\t<b/>\r\n</a>\r\n", String::from_utf8(out).unwrap());
+
+    let minified = XmlStyle { indent: Some(Indent::None), ..XmlStyle::default() };
+    let mut out = Vec::new();
+    minified.write_document(&mut out, false, Indent::Spaces(4), |w| {
+        w.create_element("a").write_inner_content(|w| {
+            w.create_element("b").write_empty()?;
+            Ok(())
+        })?;
+        Ok(())
+    }).unwrap();
+    assert_eq!("<a><b/></a>", String::from_utf8(out).unwrap());
+}
@@ -0,0 +1,70 @@
+use std::io::{Cursor, Write};
+
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+use super::{file_name, Format, OutputWriter, XmlStyle, ZwoWriter};
+use crate::Course;
+
+/// Bundles ZWO files into a ZIP archive
+#[derive(Debug, Default)]
+pub struct ZipWriter {
+    /// style of the contained ZWO files
+    pub style: XmlStyle,
+}
+
+impl OutputWriter for ZipWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        self.write_all(&[(file_name(&course.name, Format::Zwo), course)], sink)
+    }
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        ZipWriter::default()
+    }
+
+    pub fn with_style(style: XmlStyle) -> Self {
+        ZipWriter { style }
+    }
+
+    /// Writes one archive containing every course under the given path
+    pub fn write_all(&self, entries: &[(String, &Course)], sink: &mut dyn Write) -> std::io::Result<()> {
+        // the archive needs to seek back, so it is assembled in memory
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        for (path, course) in entries {
+            archive.start_file(path.as_str(), options)?;
+            ZwoWriter::with_style(self.style).write(course, &mut archive)?;
+        }
+
+        let data = archive.finish()?.into_inner();
+        sink.write_all(&data)
+    }
+}
+
+#[test]
+fn test_write_all() {
+    use std::io::Read;
+
+    let course = |name: &str| Course {
+        name: name.to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![],
+        ..Course::default()
+    };
+    let (first, second) = (course("First"), course("Second"));
+
+    let mut out = Vec::new();
+    let entries = [("First.zwo".to_string(), &first), ("week 2/Second.zwo".to_string(), &second)];
+    ZipWriter::new().write_all(&entries, &mut out).unwrap();
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+    assert_eq!(2, archive.len());
+
+    let mut content = String::new();
+    archive.by_name("week 2/Second.zwo").unwrap().read_to_string(&mut content).unwrap();
+    assert!(content.contains("<name>Second</name>"));
+}
@@ -0,0 +1,264 @@
+use std::io::Write;
+
+use quick_xml::events::BytesText;
+use quick_xml::Writer;
+
+use super::{Indent, OutputWriter, XmlStyle};
+use crate::{Course, DurationType, Kind, RunPace, Section, Sport};
+
+/// Writes Zwift workout files (ZWO)
+#[derive(Debug, Default)]
+pub struct ZwoWriter {
+    pub style: XmlStyle,
+}
+
+impl OutputWriter for ZwoWriter {
+    fn write(&self, course: &Course, sink: &mut dyn Write) -> std::io::Result<()> {
+        self.style.write_document(sink, false, Indent::Spaces(4), |w| {
+            w.create_element("workout_file").write_inner_content(|w| {
+                self.write_header(course, w)?;
+                self.write_sections(course, w)
+            })?;
+            Ok(())
+        })
+    }
+}
+
+impl ZwoWriter {
+    pub fn new() -> Self {
+        ZwoWriter::default()
+    }
+
+    pub fn with_style(style: XmlStyle) -> Self {
+        ZwoWriter { style }
+    }
+
+    fn write_header<W: Write>(&self, course: &Course, w: &mut Writer<W>) -> std::io::Result<()> {
+        w.create_element("author").write_text_content(BytesText::new(&course.author))?;
+        w.create_element("name").write_text_content(BytesText::new(&course.name))?;
+
+        match &course.description {
+            Some(description) => w.create_element("description").write_text_content(BytesText::new(description))?,
+            None => w.create_element("description").write_empty()?,
+        };
+
+        w.create_element("sportType").write_text_content(BytesText::new(&course.sport_type.to_string()))?;
+        if course.duration_type == DurationType::Distance {
+            w.create_element("durationType").write_text_content(BytesText::new("distance"))?;
+        }
+        if course.tags.is_empty() {
+            w.create_element("tags").write_empty()?;
+        } else {
+            w.create_element("tags").write_inner_content(|w| {
+                for tag in &course.tags {
+                    w.create_element("tag").with_attribute(("name", tag.as_str())).write_empty()?;
+                }
+                Ok(())
+            })?;
+        }
+
+        if let Some(category) = &course.category {
+            w.create_element("category").write_text_content(BytesText::new(category))?;
+        }
+        if let Some(subcategory) = &course.subcategory {
+            w.create_element("subcategory").write_text_content(BytesText::new(subcategory))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_sections<W: Write>(&self, course: &Course, w: &mut Writer<W>) -> std::io::Result<()> {
+        w.create_element("workout").write_inner_content(|w| {
+            for sec in &course.sections {
+                // bike workouts have no pace, run workouts default to the 10k pace
+                let pace = match course.sport_type {
+                    Sport::Bike => None,
+                    Sport::Run => Some(course.pace.unwrap_or_default()),
+                };
+                let (name, attributes) = self.block(sec, pace);
+                let element = w.create_element(name)
+                    .with_attributes(attributes.iter().map(|(key, value)| (*key, value.as_str())));
+
+                if sec.text.is_empty() {
+                    element.write_empty()?;
+                } else {
+                    let offset_key = match course.duration_type {
+                        DurationType::Time => "timeoffset",
+                        DurationType::Distance => "distoffset",
+                    };
+                    element.write_inner_content(|w| {
+                        for hint in &sec.text {
+                            let offset = hint.offset.to_string();
+                            let duration = hint.duration.map(|duration| duration.to_string());
+                            let mut element = w.create_element("textevent")
+                                .with_attributes([(offset_key, offset.as_str()), ("message", hint.text.as_str())]);
+                            if let Some(duration) = &duration {
+                                element = element.with_attribute(("duration", duration.as_str()));
+                            }
+                            element.write_empty()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    /// Element name and attributes of a section, run workouts target the given pace
+    fn block(&self, sec: &Section, pace: Option<RunPace>) -> (&'static str, Vec<(&'static str, String)>) {
+        let duration = ("Duration", sec.duration.to_string());
+        let pace = ("pace", pace.map_or(0, |pace| pace.index()).to_string());
+
+        let (name, mut attributes) = match sec.kind {
+            Kind::SteadyState => ("SteadyState", vec![duration, ("Power", sec.power.to_string()), pace]),
+            Kind::Warmup { end_power } => {
+                ("Warmup", vec![duration, ("PowerLow", sec.power.to_string()), ("PowerHigh", end_power.to_string()), pace])
+            },
+            Kind::Cooldown { end_power } => {
+                ("Cooldown", vec![duration, ("PowerLow", sec.power.to_string()), ("PowerHigh", end_power.to_string()), pace])
+            },
+            Kind::Ramp { end_power } => {
+                ("Ramp", vec![duration, ("PowerLow", sec.power.to_string()), ("PowerHigh", end_power.to_string()), pace])
+            },
+            Kind::IntervalsT { repeat, on_duration, off_duration, off_power, .. } => ("IntervalsT", vec![
+                ("Repeat", repeat.to_string()),
+                ("OnDuration", on_duration.to_string()),
+                ("OffDuration", off_duration.to_string()),
+                ("OnPower", sec.power.to_string()),
+                ("OffPower", off_power.to_string()),
+                pace,
+            ]),
+            Kind::FreeRide { flat_road: true } => ("FreeRide", vec![duration, ("FlatRoad", "1".to_string())]),
+            Kind::FreeRide { flat_road: false } => ("FreeRide", vec![duration]),
+        };
+
+        if let Some(cadence) = sec.cadence {
+            attributes.push(("Cadence", cadence.to_string()));
+        }
+        if let Kind::IntervalsT { cadence_resting: Some(cadence), .. } = sec.kind {
+            attributes.push(("CadenceResting", cadence.to_string()));
+        }
+        (name, attributes)
+    }
+}
+
+#[test]
+fn test_write() {
+    use crate::{Hint, Kind, Section};
+
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![
+            Section { start: 0, duration: 60, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![] },
+            Section { start: 60, duration: 30, power: 1.2, kind: Kind::SteadyState, cadence: None, text: vec![Hint { offset: 10, text: "Go!".to_string(), duration: None }] },
+        ],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ZwoWriter::new().write(&course, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "\
+<workout_file>
+    <author>Me</author>
+    <name>Test</name>
+    <description/>
+    <sportType>bike</sportType>
+    <tags/>
+    <workout>
+        <SteadyState Duration=\"60\" Power=\"0.5\" pace=\"0\"/>
+        <SteadyState Duration=\"30\" Power=\"1.2\" pace=\"0\">
+            <textevent timeoffset=\"10\" message=\"Go!\"/>
+        </SteadyState>
+    </workout>
+</workout_file>
+");
+}
+
+#[test]
+fn test_escape() {
+    use crate::{Hint, Kind, Section};
+
+    let course = Course {
+        name: "Fish & Chips".to_string(),
+        description: Some("<3 intervals".to_string()),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![Section { start: 0, duration: 60, power: 0.5, kind: Kind::SteadyState, cadence: None, text: vec![Hint { offset: 0, text: "Say \"go\"".to_string(), duration: Some(5) }] }],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ZwoWriter::new().write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<name>Fish &amp; Chips</name>"));
+    assert!(out.contains("<description>&lt;3 intervals</description>"));
+    assert!(out.contains("message=\"Say &quot;go&quot;\" duration=\"5\""));
+}
+
+#[test]
+fn test_warmup() {
+    use crate::Section;
+
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![Section { start: 0, duration: 600, power: 0.4, kind: Kind::Warmup { end_power: 0.75 }, cadence: None, text: vec![] }],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ZwoWriter::new().write(&course, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("<Warmup Duration=\"600\" PowerLow=\"0.4\" PowerHigh=\"0.75\" pace=\"0\"/>"));
+}
+
+#[test]
+fn test_tags() {
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        tags: vec!["RECOVERY".to_string(), "INTERVALS".to_string()],
+        category: Some("Plan".to_string()),
+        subcategory: Some("Week 1".to_string()),
+        ftp: 250,
+        sections: vec![],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ZwoWriter::new().write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<category>Plan</category>\n    <subcategory>Week 1</subcategory>"));
+    assert!(out.contains("    <tags>
+        <tag name=\"RECOVERY\"/>
+        <tag name=\"INTERVALS\"/>
+    </tags>
+"));
+}
+
+#[test]
+fn test_run_pace() {
+    use crate::Section;
+
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        sport_type: Sport::Run,
+        pace: Some(RunPace::FiveK),
+        duration_type: DurationType::Distance,
+        ftp: 100,
+        sections: vec![Section { start: 0, duration: 800, power: 0.9, kind: Kind::SteadyState, cadence: None, text: vec![] }],
+        ..Course::default()
+    };
+
+    let mut out = Vec::new();
+    ZwoWriter::new().write(&course, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<sportType>run</sportType>\n    <durationType>distance</durationType>"));
+    assert!(out.contains("<SteadyState Duration=\"800\" Power=\"0.9\" pace=\"1\"/>"));
+}
@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// Distance unit run paces are given per
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaceUnit {
+    /// minutes per kilometer
+    #[default]
+    Km,
+    /// minutes per mile
+    Mile,
+}
+
+impl PaceUnit {
+    /// Length of the unit in meters
+    pub fn meters(&self) -> f64 {
+        match self {
+            PaceUnit::Km => 1000.0,
+            PaceUnit::Mile => 1609.344,
+        }
+    }
+}
+
+impl FromStr for PaceUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "km" => Ok(PaceUnit::Km),
+            "mi" => Ok(PaceUnit::Mile),
+            _ => Err(format!("unknown pace unit \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for PaceUnit {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PaceUnit::Km => write!(f, "km"),
+            PaceUnit::Mile => write!(f, "mi"),
+        }
+    }
+}
+
+/// The pace from the Zwift profile run workout targets are relative to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunPace {
+    Mile,
+    FiveK,
+    #[default]
+    TenK,
+    HalfMarathon,
+    Marathon,
+}
+
+impl RunPace {
+    /// Value of the `pace` attribute in ZWO files
+    pub fn index(&self) -> u8 {
+        match self {
+            RunPace::Mile => 0,
+            RunPace::FiveK => 1,
+            RunPace::TenK => 2,
+            RunPace::HalfMarathon => 3,
+            RunPace::Marathon => 4,
+        }
+    }
+}
+
+impl FromStr for RunPace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mile" => Ok(RunPace::Mile),
+            "5k" => Ok(RunPace::FiveK),
+            "10k" => Ok(RunPace::TenK),
+            "half" => Ok(RunPace::HalfMarathon),
+            "marathon" => Ok(RunPace::Marathon),
+            _ => Err(format!("unknown run pace \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for RunPace {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RunPace::Mile => write!(f, "mile"),
+            RunPace::FiveK => write!(f, "5k"),
+            RunPace::TenK => write!(f, "10k"),
+            RunPace::HalfMarathon => write!(f, "half"),
+            RunPace::Marathon => write!(f, "marathon"),
+        }
+    }
+}
+
+/// Parses a pace like "4:30" in the given unit, or with an explicit one like "7:15/mi",
+/// into seconds per kilometer
+pub fn parse_pace(text: &str, unit: PaceUnit) -> Option<f64> {
+    let text = text.trim();
+    let (clock, unit) = match text.split_once('/') {
+        Some((clock, unit)) => (clock.trim(), unit.trim().parse().ok()?),
+        None => (text, unit),
+    };
+
+    let (minutes, seconds) = clock.split_once(':')?;
+    let minutes: u32 = minutes.parse().ok()?;
+    let seconds: u32 = seconds.parse().ok().filter(|seconds| *seconds < 60)?;
+    let per_unit = (minutes * 60 + seconds) as f64;
+    if per_unit == 0.0 {
+        return None;
+    }
+    Some(per_unit * 1000.0 / unit.meters())
+}
+
+#[test]
+fn test_parse_pace() {
+    assert_eq!(Some(270.0), parse_pace("4:30", PaceUnit::Km));
+    assert_eq!(Some(300.0), parse_pace("5:00/km", PaceUnit::Mile));
+    assert!((parse_pace("8:03/mi", PaceUnit::Km).unwrap() - 300.1).abs() < 0.1);
+    assert_eq!(None, parse_pace("250", PaceUnit::Km));
+    assert_eq!(None, parse_pace("4:75", PaceUnit::Km));
+    assert_eq!(None, parse_pace("0:00", PaceUnit::Km));
+}
@@ -0,0 +1,84 @@
+//! Machine-readable results and errors of conversions, for scripts and services
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::stats::Stats;
+use crate::Error;
+
+/// How results and errors are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// human readable lines on stderr
+    #[default]
+    Text,
+    /// one JSON object per result on stdout, per error on stderr
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown report format \"{}\", expected \"text\" or \"json\"", s)),
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ReportFormat::Text => write!(f, "text"),
+            ReportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// A converted workout
+#[derive(Debug, Serialize)]
+pub struct Success {
+    pub name: String,
+    pub input: PathBuf,
+    /// none when written to stdout
+    pub output: Option<PathBuf>,
+    /// only for time based workouts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+}
+
+/// A failed conversion, with the position in the input if known
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Failure {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl Failure {
+    /// Takes the position from errors in a line of the input
+    pub fn new(input: Option<&Path>, err: &Error) -> Self {
+        let input = input.map(Path::to_path_buf);
+        match err {
+            Error::Input(err) => Failure { input, line: Some(err.line), column: err.column.clone(), message: err.message.clone() },
+            _ => Failure { input, line: None, column: None, message: err.message() },
+        }
+    }
+}
+
+#[test]
+fn test_failure() {
+    let err = Error::from(crate::input::InputError::new(3, Some("time"), "invalid time \"1:xx\""));
+    let failure = Failure::new(Some(Path::new("a.csv")), &err);
+    assert_eq!(r#"{"input":"a.csv","line":3,"column":"time","message":"invalid time \"1:xx\""}"#, serde_json::to_string(&failure).unwrap());
+
+    let failure = Failure::new(None, &Error::Validation("no input files given".to_string()));
+    assert_eq!(r#"{"line":null,"column":null,"message":"no input files given"}"#, serde_json::to_string(&failure).unwrap());
+}
@@ -0,0 +1,281 @@
+//! Summary statistics of a course, like TSS or time in zone
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::duration::format_duration;
+use crate::{Converter, Course, Error, Kind, Section, Step, ZoneModel};
+
+/// Window of the rolling average for Normalized Power, in seconds
+const NP_WINDOW: usize = 30;
+
+/// Planned load of a course. Free rides have no power target, so they only count for the duration.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    /// total duration in seconds
+    pub duration: u32,
+    /// in watts
+    pub average_power: f64,
+    /// in watts
+    pub normalized_power: f64,
+    /// Normalized Power relative to FTP
+    pub intensity_factor: f64,
+    pub tss: f64,
+    /// work in kJ
+    pub work: f64,
+    pub zones: Vec<ZoneTime>,
+}
+
+/// Time spent in a zone
+#[derive(Debug, Serialize)]
+pub struct ZoneTime {
+    pub zone: String,
+    /// lower bound relative to FTP
+    pub low: f64,
+    /// upper bound relative to FTP
+    pub high: f64,
+    /// in seconds
+    pub duration: u32,
+    /// of the total duration, in percent
+    pub share: f64,
+}
+
+/// Conversions at most to close in on a target TSS
+const TSS_ITERATIONS: usize = 5;
+/// TSS difference accepted as reaching the target
+const TSS_PRECISION: f64 = 0.5;
+
+/// Width of the bar for 100% in the zone table, in characters
+const BAR_WIDTH: usize = 40;
+
+impl Stats {
+    /// Computes the statistics second by second, time in zone for the distinct zones of the model
+    pub fn of(course: &Course, zones: &ZoneModel) -> Self {
+        let ftp = course.ftp as f64;
+        let samples = samples(&course.sections);
+
+        let duration = course.sections.iter().map(|sec| sec.duration).sum();
+        let average_power = mean(samples.iter().copied()) * ftp;
+        let normalized_power = normalized(&samples) * ftp;
+        let intensity_factor = if ftp > 0.0 { normalized_power / ftp } else { 0.0 };
+        let tss = samples.len() as f64 / 3600.0 * intensity_factor.powi(2) * 100.0;
+        let work = samples.iter().sum::<f64>() * ftp / 1000.0;
+
+        let mut zone_times: Vec<ZoneTime> = zones.distinct().iter()
+            .map(|zone| ZoneTime { zone: zone.name.clone(), low: zone.low, high: zone.high, duration: 0, share: 0.0 })
+            .collect();
+        for power in &samples {
+            if let Some(zone) = zones.classify(*power) {
+                if let Some(time) = zone_times.iter_mut().find(|time| time.zone == zone.name) {
+                    time.duration += 1;
+                }
+            }
+        }
+        for time in &mut zone_times {
+            time.share = if duration > 0 { time.duration as f64 / duration as f64 * 100.0 } else { 0.0 };
+        }
+
+        Stats { duration, average_power, normalized_power, intensity_factor, tss, work, zones: zone_times }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "duration           {}", format_duration(self.duration))?;
+        writeln!(f, "average power      {:.0} W", self.average_power)?;
+        writeln!(f, "normalized power   {:.0} W", self.normalized_power)?;
+        writeln!(f, "intensity factor   {:.2}", self.intensity_factor)?;
+        writeln!(f, "TSS                {:.0}", self.tss)?;
+        writeln!(f, "work               {:.0} kJ", self.work)?;
+        for time in &self.zones {
+            writeln!(f, "{:<18} {:>8} {:>4.0}%", time.zone, format_duration(time.duration), time.share)?;
+        }
+        Ok(())
+    }
+}
+
+/// TSS of the sections, whose power is relative to FTP
+pub fn tss(sections: &[Section]) -> f64 {
+    let samples = samples(sections);
+    samples.len() as f64 / 3600.0 * normalized(&samples).powi(2) * 100.0
+}
+
+/// What `--target-tss` changes to reach the TSS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TssAdjust {
+    /// scale the power, within the power limits
+    #[default]
+    Power,
+    /// stretch or shrink the time, keeping the power
+    Duration,
+}
+
+impl FromStr for TssAdjust {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "power" => Ok(TssAdjust::Power),
+            "duration" => Ok(TssAdjust::Duration),
+            _ => Err(format!("unknown TSS adjustment \"{}\", expected \"power\" or \"duration\"", s)),
+        }
+    }
+}
+
+impl Display for TssAdjust {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TssAdjust::Power => write!(f, "power"),
+            TssAdjust::Duration => write!(f, "duration"),
+        }
+    }
+}
+
+/// Sets the acceleration of the converter so the steps last the target duration in seconds,
+/// the last step marking the end
+pub fn reach_duration(converter: &mut Converter, steps: &[Step], target: u32) -> Result<(), Error> {
+    let duration = steps.last().map_or(0.0, |step| step.time.as_secs_f64());
+    if duration <= 0.0 || target == 0 {
+        return Err(Error::Validation(format!("a course of {} can't be shrunk to {}", format_duration(duration.round() as u32), format_duration(target))));
+    }
+    converter.acceleration = duration / target as f64;
+    Ok(())
+}
+
+/// Changes the power scale or the acceleration of the converter so the steps give the target TSS,
+/// returning the TSS reached. Rounding and power limits keep the factors from being exact,
+/// so the steps are converted a few times, and targets out of reach of the limits are missed.
+pub fn reach_tss(converter: &mut Converter, steps: &[Step], target: f64, adjust: TssAdjust) -> f64 {
+    let mut reached = tss(&converter.translate(steps.to_vec()));
+    for _ in 0..TSS_ITERATIONS {
+        if reached <= 0.0 || (reached - target).abs() < TSS_PRECISION {
+            break;
+        }
+        match adjust {
+            TssAdjust::Power => converter.scale *= (target / reached).sqrt(),
+            TssAdjust::Duration => converter.acceleration *= reached / target,
+        }
+        reached = tss(&converter.translate(steps.to_vec()));
+    }
+    reached
+}
+
+/// Renders the time in zone as table with the power range in watts and a bar per zone
+pub fn zone_table(zones: &[ZoneTime], ftp: u16) -> String {
+    let watts = |power: f64| (power * ftp as f64).round();
+    let mut out = String::new();
+    for time in zones {
+        let range = format!("{}-{} W", watts(time.low), watts(time.high));
+        let bar = "█".repeat((time.share / 100.0 * BAR_WIDTH as f64).round() as usize);
+        let line = format!("{:<6} {:>11} {:>8} {:>4.0}% {}", time.zone, range, format_duration(time.duration), time.share, bar);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Power relative to FTP for every second with a target
+fn samples(sections: &[Section]) -> Vec<f64> {
+    let mut samples: Vec<f64> = Vec::new();
+    for sec in sections {
+        if matches!(sec.kind, Kind::FreeRide { .. }) {
+            continue;
+        }
+        for (duration, from, to) in sec.parts() {
+            samples.extend((0..duration).map(|second| from + (to - from) * (second as f64 + 0.5) / duration as f64));
+        }
+    }
+    samples
+}
+
+/// Normalized Power relative to FTP, the average power of courses shorter than its window
+fn normalized(samples: &[f64]) -> f64 {
+    match samples.len() {
+        0 => 0.0,
+        count if count < NP_WINDOW => mean(samples.iter().copied()),
+        _ => {
+            let rolling = samples.windows(NP_WINDOW).map(|window| mean(window.iter().copied()));
+            mean(rolling.map(|power| power.powi(4))).powf(0.25)
+        },
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 { 0.0 } else { sum / count as f64 }
+}
+
+#[test]
+fn test_stats() {
+    use crate::Section;
+
+    let section = |start, duration, power| Section { start, duration, power, kind: Kind::SteadyState, cadence: None, text: vec![] };
+    let course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 200,
+        sections: vec![
+            section(0, 1800, 1.0),
+            Section { kind: Kind::FreeRide { flat_road: false }, ..section(1800, 600, 0.0) },
+            section(2400, 1800, 0.5),
+        ],
+        ..Course::default()
+    };
+
+    let stats = Stats::of(&course, &ZoneModel::coggan());
+    assert_eq!(4200, stats.duration);
+    assert_eq!(150.0, stats.average_power);
+    assert_eq!(540.0, stats.work);
+    assert!(stats.normalized_power > 170.0 && stats.normalized_power < 175.0);
+    assert!((stats.tss - stats.intensity_factor.powi(2) * 100.0).abs() < 1e-9);
+    assert!((stats.tss - tss(&course.sections)).abs() < 1e-9);
+    assert_eq!(Some(1800), stats.zones.iter().find(|time| time.zone == "Z4").map(|time| time.duration));
+    assert_eq!(Some(1800), stats.zones.iter().find(|time| time.zone == "Z1").map(|time| time.duration));
+
+    let table = zone_table(&stats.zones, course.ftp);
+    assert!(table.starts_with("Z1        80-110 W      30m   43% █████████████████\n"));
+    assert!(table.contains("Z2       112-150 W       0s    0%\n"));
+}
+
+#[test]
+fn test_reach_tss() {
+    use std::time::Duration;
+
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = vec![step(0, Some(150)), step(1800, Some(200)), step(3600, None)];
+
+    let mut converter = Converter { ramp_tolerance: 0.0, ..Converter::new(250) };
+    let reached = reach_tss(&mut converter, &steps, 60.0, TssAdjust::Power);
+    assert!((reached - 60.0).abs() < TSS_PRECISION);
+    assert!(converter.scale > 1.0);
+    assert_eq!(1.0, converter.acceleration);
+
+    let mut converter = Converter { ramp_tolerance: 0.0, ..Converter::new(250) };
+    let reached = reach_tss(&mut converter, &steps, 30.0, TssAdjust::Duration);
+    assert!((reached - 30.0).abs() < TSS_PRECISION);
+    assert!(converter.acceleration > 1.0);
+    assert_eq!(1.0, converter.scale);
+
+    // the maximum power caps the course at a TSS of about 64
+    let mut converter = Converter { max_power: Some(0.8), ramp_tolerance: 0.0, ..Converter::new(250) };
+    let reached = reach_tss(&mut converter, &steps, 100.0, TssAdjust::Power);
+    assert!(reached > 60.0 && reached < 65.0);
+}
+
+#[test]
+fn test_reach_duration() {
+    use std::time::Duration;
+
+    let step = |seconds, watts| Step { time: Duration::from_secs(seconds), watts, ..Step::default() };
+    let steps = vec![step(0, Some(150)), step(1800, Some(200)), step(5400, None)];
+
+    let mut converter = Converter::new(250);
+    reach_duration(&mut converter, &steps, 3600).unwrap();
+    assert_eq!(1.5, converter.acceleration);
+    assert_eq!(3600, converter.translate(steps.clone()).iter().map(|sec| sec.start + sec.duration).max().unwrap());
+
+    assert!(reach_duration(&mut converter, &steps, 0).is_err());
+    assert!(reach_duration(&mut converter, &steps[..1], 3600).is_err());
+}
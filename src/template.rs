@@ -0,0 +1,58 @@
+use crate::duration::format_duration;
+use crate::{Course, DurationType, Section};
+
+/// Expands placeholders in the texts of all sections with the values of the section they are shown in:
+/// `{watts}`, `{percent}` and `{duration}`, the same of the following section as `{next_power}`,
+/// `{next_percent}` and `{next_duration}`, plus `{ftp}`. Unknown placeholders are kept.
+pub fn expand_templates(course: &mut Course) {
+    let ftp = course.ftp;
+    let duration_type = course.duration_type;
+    let watts = |sec: &Section| ((sec.power * ftp as f64).round() as u32).to_string();
+    let percent = |sec: &Section| ((sec.power * 100.0).round() as u32).to_string();
+    let duration = |sec: &Section| match duration_type {
+        DurationType::Time => format_duration(sec.duration),
+        DurationType::Distance => format!("{} m", sec.duration),
+    };
+
+    for index in 0..course.sections.len() {
+        let sec = &course.sections[index];
+        let mut values = vec![
+            ("{ftp}", ftp.to_string()),
+            ("{watts}", watts(sec)),
+            ("{percent}", percent(sec)),
+            ("{duration}", duration(sec)),
+        ];
+        if let Some(next) = course.sections.get(index + 1) {
+            values.extend([("{next_power}", watts(next)), ("{next_percent}", percent(next)), ("{next_duration}", duration(next))]);
+        }
+
+        for hint in &mut course.sections[index].text {
+            if hint.text.contains('{') {
+                for (placeholder, value) in &values {
+                    hint.text = hint.text.replace(placeholder, value);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_expand_templates() {
+    use crate::{Hint, Kind};
+
+    let hint = |text: &str| Hint { offset: 0, text: text.to_string(), duration: None };
+    let mut course = Course {
+        name: "Test".to_string(),
+        author: "Me".to_string(),
+        ftp: 250,
+        sections: vec![
+            Section { start: 0, duration: 90, power: 0.6, kind: Kind::SteadyState, cadence: None, text: vec![hint("Easy {duration} at {watts} W, next: {next_duration} @ {next_power} W")] },
+            Section { start: 90, duration: 180, power: 1.05, kind: Kind::SteadyState, cadence: None, text: vec![hint("{percent}% of {ftp}, {next_power} {unknown}")] },
+        ],
+        ..Course::default()
+    };
+
+    expand_templates(&mut course);
+    assert_eq!("Easy 1m30s at 150 W, next: 3m @ 263 W", course.sections[0].text[0].text);
+    assert_eq!("105% of 250, {next_power} {unknown}", course.sections[1].text[0].text);
+}
@@ -0,0 +1,193 @@
+//! Checks ZWO files against the rules of Zwift's workout format
+
+use std::fmt::{Display, Formatter};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Elements allowed directly inside `<workout_file>`
+const HEADER: &[&str] = &["author", "name", "description", "sportType", "durationType", "tags", "category", "subcategory", "workout"];
+
+/// Blocks allowed inside `<workout>`, with their required and optional numeric attributes
+const BLOCKS: &[(&str, &[&str], &[&str])] = &[
+    ("SteadyState", &["Duration", "Power"], &["pace", "Cadence", "CadenceResting"]),
+    ("Warmup", &["Duration", "PowerLow", "PowerHigh"], &["pace", "Cadence"]),
+    ("Cooldown", &["Duration", "PowerLow", "PowerHigh"], &["pace", "Cadence"]),
+    ("Ramp", &["Duration", "PowerLow", "PowerHigh"], &["pace", "Cadence"]),
+    ("IntervalsT", &["Repeat", "OnDuration", "OffDuration", "OnPower", "OffPower"], &["pace", "Cadence", "CadenceResting"]),
+    ("FreeRide", &["Duration"], &["FlatRoad", "Cadence"]),
+    ("MaxEffort", &["Duration"], &[]),
+];
+
+/// Attributes which have to be whole numbers
+const INTEGERS: &[&str] = &["Repeat", "FlatRoad", "pace"];
+
+/// A rule violation found in a ZWO file
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    /// line number, starting at 1
+    pub line: usize,
+    pub message: String,
+    /// the offending source line
+    pub context: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}\n    {}", self.line, self.message, self.context)
+    }
+}
+
+/// Validates a ZWO document, returning all violations found
+pub fn validate(xml: &str) -> Vec<Violation> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut violations = Vec::new();
+    let mut report = |position: usize, message: String| {
+        let line = xml[..position.min(xml.len())].matches('\n').count() + 1;
+        let context = xml.lines().nth(line - 1).unwrap_or_default().trim().to_string();
+        violations.push(Violation { line, message, context });
+    };
+
+    // names of the open elements
+    let mut path: Vec<String> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+
+    loop {
+        // skip the whitespace before the element, which is trimmed away
+        let rest = &xml[reader.buffer_position() as usize..];
+        let position = xml.len() - rest.trim_start().len();
+        let (element, empty) = match reader.read_event() {
+            Ok(Event::Start(e)) => (e, false),
+            Ok(Event::Empty(e)) => (e, true),
+            Ok(Event::End(_)) => {
+                path.pop();
+                continue;
+            },
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            Err(err) => {
+                report(reader.error_position() as usize, format!("malformed XML: {}", err));
+                return violations;
+            },
+        };
+
+        let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+        let parent = path.last().map(String::as_str);
+        match parent {
+            None if name != "workout_file" => report(position, format!("root element must be <workout_file>, not <{}>", name)),
+            None => {},
+            Some("workout_file") => {
+                if !HEADER.contains(&name.as_str()) {
+                    report(position, format!("unknown element <{}>", name));
+                }
+                seen.push(name.clone());
+            },
+            Some("tags") if name != "tag" => report(position, format!("<tags> may only contain <tag>, not <{}>", name)),
+            Some("tags") => check_attributes(&element, &["name"], &[], &[], position, &mut report),
+            Some("workout") => match BLOCKS.iter().find(|(block, _, _)| *block == name) {
+                Some((_, required, optional)) => check_attributes(&element, required, optional, INTEGERS, position, &mut report),
+                None => report(position, format!("unknown block <{}>", name)),
+            },
+            Some(block) if BLOCKS.iter().any(|(known, _, _)| *known == block) => {
+                if name == "textevent" {
+                    check_attributes(&element, &[], &["timeoffset", "distoffset", "duration"], &[], position, &mut report);
+                    if !has_attribute(&element, "timeoffset") && !has_attribute(&element, "distoffset") {
+                        report(position, "<textevent> lacks attribute timeoffset".to_string());
+                    }
+                    if !has_attribute(&element, "message") {
+                        report(position, "<textevent> lacks attribute message".to_string());
+                    }
+                } else {
+                    report(position, format!("<{}> may only contain <textevent>, not <{}>", block, name));
+                }
+            },
+            Some(parent) => report(position, format!("unexpected <{}> inside <{}>", name, parent)),
+        }
+
+        if !empty {
+            path.push(name);
+        }
+    }
+
+    for required in ["name", "workout"] {
+        if !seen.iter().any(|name| name == required) {
+            report(xml.len(), format!("missing required element <{}>", required));
+        }
+    }
+
+    violations
+}
+
+fn has_attribute(element: &BytesStart, key: &str) -> bool {
+    element.attributes().flatten().any(|attr| attr.key.as_ref() == key.as_bytes())
+}
+
+/// Checks that all required attributes exist and numeric attributes hold non-negative numbers
+fn check_attributes<F>(element: &BytesStart, required: &[&str], optional: &[&str], integers: &[&str], position: usize, report: &mut F)
+where
+    F: FnMut(usize, String),
+{
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+
+    for key in required {
+        if !has_attribute(element, key) {
+            report(position, format!("<{}> lacks attribute {}", name, key));
+        }
+    }
+
+    for attr in element.attributes() {
+        let Ok(attr) = attr else {
+            report(position, format!("<{}> has a malformed attribute", name));
+            continue;
+        };
+
+        let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+        if !required.contains(&key.as_str()) && !optional.contains(&key.as_str()) || key == "name" {
+            continue;
+        }
+
+        let value = attr.unescape_value().map(|value| value.into_owned()).unwrap_or_default();
+        let valid = if integers.contains(&key.as_str()) {
+            value.parse::<u32>().is_ok()
+        } else {
+            value.parse::<f64>().is_ok_and(|number| number.is_finite() && number >= 0.0)
+        };
+        if !valid {
+            report(position, format!("<{}> has invalid {} \"{}\"", name, key, value));
+        }
+    }
+}
+
+#[test]
+fn test_validate() {
+    let zwo = r#"<workout_file>
+    <name>Test</name>
+    <colour>red</colour>
+    <workout>
+        <SteadyState Duration="60" Power="0.5"/>
+        <Ramp Duration="60" PowerLow="0.5"/>
+        <IntervalsT Repeat="2.5" OnDuration="30" OffDuration="30" OnPower="1.2" OffPower="0.5"/>
+        <SteadyState Duration="abc" Power="1.0">
+            <textevent timeoffset="10"/>
+        </SteadyState>
+        <Sprint Duration="10"/>
+    </workout>
+</workout_file>
+"#;
+
+    let violations = validate(zwo);
+    let lines: Vec<(usize, &str)> = violations.iter().map(|v| (v.line, v.message.as_str())).collect();
+    assert_eq!(vec![
+        (3, "unknown element <colour>"),
+        (6, "<Ramp> lacks attribute PowerHigh"),
+        (7, "<IntervalsT> has invalid Repeat \"2.5\""),
+        (8, "<SteadyState> has invalid Duration \"abc\""),
+        (9, "<textevent> lacks attribute message"),
+        (11, "unknown block <Sprint>"),
+    ], lines);
+
+    assert_eq!("<Sprint Duration=\"10\"/>", violations[5].context);
+    assert!(validate("<workout_file><name>X</name><workout/></workout_file>").is_empty());
+}
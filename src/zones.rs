@@ -0,0 +1,245 @@
+//! Training zones relative to FTP
+
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::Error;
+
+/// A power range relative to FTP
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Zone {
+    /// label like "Z2"
+    pub name: String,
+    /// further labels accepted as input, like "Endurance"
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// lower bound relative to FTP
+    pub low: f64,
+    /// upper bound relative to FTP
+    pub high: f64,
+    /// power zone labels are turned into, relative to FTP [default: the midpoint]
+    pub target: Option<f64>,
+}
+
+impl Zone {
+    fn new(name: &str, aliases: &[&str], low: f64, high: f64) -> Self {
+        let aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+        Zone { name: name.to_string(), aliases, low, high, target: None }
+    }
+
+    /// Power in the middle of the zone, relative to FTP
+    pub fn midpoint(&self) -> f64 {
+        (self.low + self.high) / 2.0
+    }
+
+    /// Power to aim for in this zone, relative to FTP
+    pub fn target(&self) -> f64 {
+        self.target.unwrap_or_else(|| self.midpoint())
+    }
+}
+
+/// A zone given in percent of FTP, like "Z2=56-75" or "Z2=56-75@65" with a target
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid zone \"{}\", expected e.g. \"Z2=56-75@65\"", s);
+        let percent = |text: &str| text.trim().trim_end_matches('%').parse::<f64>().map(|value| value / 100.0).map_err(|_| invalid());
+
+        let (name, range) = s.split_once('=').ok_or_else(invalid)?;
+        let (range, target) = match range.split_once('@') {
+            Some((range, target)) => (range, Some(percent(target)?)),
+            None => (range, None),
+        };
+        let (low, high) = range.split_once('-').ok_or_else(invalid)?;
+        Ok(Zone { target, ..Zone::new(name.trim(), &[], percent(low)?, percent(high)?) })
+    }
+}
+
+/// A set of zones, the first matching one wins when looking up power
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ZoneModel {
+    pub name: String,
+    pub zones: Vec<Zone>,
+}
+
+impl ZoneModel {
+    /// Andrew Coggan's seven power zones plus sweet spot
+    pub fn coggan() -> Self {
+        ZoneModel {
+            name: "coggan".to_string(),
+            zones: vec![
+                Zone::new("Z1", &["Recovery"], 0.40, 0.55),
+                Zone::new("Z2", &["Endurance"], 0.56, 0.75),
+                Zone::new("Z3", &["Tempo"], 0.76, 0.90),
+                Zone::new("Z4", &["Threshold", "FTP"], 0.91, 1.05),
+                Zone::new("Z5", &["VO2", "VO2max"], 1.06, 1.20),
+                Zone::new("Z6", &["Anaerobic"], 1.21, 1.50),
+                Zone::new("Z7", &["Neuromuscular", "Sprint"], 1.51, 2.00),
+                Zone::new("SS", &["SweetSpot"], 0.88, 0.94),
+            ],
+        }
+    }
+
+    /// Stephen Seiler's three zones for polarized training
+    pub fn seiler() -> Self {
+        ZoneModel {
+            name: "seiler".to_string(),
+            zones: vec![
+                Zone::new("Z1", &["Easy"], 0.50, 0.80),
+                Zone::new("Z2", &["Moderate"], 0.81, 0.95),
+                Zone::new("Z3", &["Hard"], 0.96, 1.20),
+            ],
+        }
+    }
+
+    /// Heart rate zones relative to LTHR, named like the Coggan power zones they correspond to
+    pub fn coggan_heart_rate() -> Self {
+        ZoneModel {
+            name: "coggan-hr".to_string(),
+            zones: vec![
+                Zone::new("Z1", &[], 0.50, 0.68),
+                Zone::new("Z2", &[], 0.69, 0.83),
+                Zone::new("Z3", &[], 0.84, 0.94),
+                Zone::new("Z4", &[], 0.95, 1.05),
+                Zone::new("Z5", &[], 1.06, 1.20),
+            ],
+        }
+    }
+
+    /// Maps a value of this model onto the zone of the same name in another model, keeping its
+    /// relative position within the zone, e.g. heart rate relative to LTHR into power relative to FTP.
+    /// Values between two zones belong to the lower one.
+    pub fn translate(&self, value: f64, target: &ZoneModel) -> Option<f64> {
+        let zone = self.zones.iter().rev().find(|zone| zone.low <= value).or_else(|| self.zones.first())?;
+        let equivalent = target.find(&zone.name)?;
+        let position = ((value - zone.low) / (zone.high - zone.low)).clamp(0.0, 1.0);
+        Some(equivalent.low + position * (equivalent.high - equivalent.low))
+    }
+
+    /// Loads a custom model from a YAML or JSON file
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(|err| std::io::Error::new(err.kind(), format!("reading zones {}: {}", path.display(), err)))?;
+        let model: ZoneModel = serde_yaml::from_str(&text).map_err(|err| Error::Parse(format!("{}: {}", path.display(), err)))?;
+        Ok(model)
+    }
+
+    /// Replaces the zone with the same name, or adds it
+    pub fn set(&mut self, zone: Zone) {
+        match self.zones.iter_mut().find(|known| known.name.eq_ignore_ascii_case(&zone.name)) {
+            Some(known) => *known = Zone { aliases: std::mem::take(&mut known.aliases), ..zone },
+            None => self.zones.push(zone),
+        }
+    }
+
+    /// Finds the first zone containing the given power relative to FTP
+    pub fn zone_of(&self, power: f64) -> Option<&Zone> {
+        self.zones.iter().find(|zone| zone.low <= power && power <= zone.high)
+    }
+
+    /// Zones dividing the power range, without those overlapping an earlier one like sweet spot
+    pub fn distinct(&self) -> Vec<&Zone> {
+        let mut zones: Vec<&Zone> = Vec::new();
+        for zone in &self.zones {
+            if !zones.iter().any(|known| zone.low < known.high && known.low < zone.high) {
+                zones.push(zone);
+            }
+        }
+        zones
+    }
+
+    /// Finds the distinct zone containing the power relative to FTP, or the closest one
+    pub fn classify(&self, power: f64) -> Option<&Zone> {
+        let distance = |zone: &Zone| (zone.low - power).max(power - zone.high).max(0.0);
+        self.distinct().into_iter().min_by(|a, b| distance(a).total_cmp(&distance(b)))
+    }
+
+    /// Finds a zone by its name or alias, ignoring case
+    pub fn find(&self, label: &str) -> Option<&Zone> {
+        let label = label.trim();
+        self.zones.iter().find(|zone| {
+            zone.name.eq_ignore_ascii_case(label) || zone.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(label))
+        })
+    }
+}
+
+impl Default for ZoneModel {
+    fn default() -> Self {
+        ZoneModel::coggan()
+    }
+}
+
+impl FromStr for ZoneModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coggan" => Ok(ZoneModel::coggan()),
+            "seiler" => Ok(ZoneModel::seiler()),
+            _ => Err(format!("unknown zone model \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for ZoneModel {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[test]
+fn test_find() {
+    let zones = ZoneModel::coggan();
+    assert_eq!(Some(0.655), zones.find("z2").map(Zone::midpoint));
+    assert!(zones.find("SweetSpot").is_some_and(|zone| (zone.midpoint() - 0.91).abs() < 1e-9));
+    assert_eq!(Some("Z5"), zones.find("vo2").map(|zone| zone.name.as_str()));
+    assert_eq!(None, zones.find("Z8"));
+}
+
+#[test]
+fn test_custom() {
+    let mut zones: ZoneModel = serde_yaml::from_str("
+name: custom
+zones:
+  - name: Easy
+    low: 0.5
+    high: 0.7
+    target: 0.6
+  - name: Hard
+    aliases: [Work]
+    low: 0.95
+    high: 1.1
+").unwrap();
+    assert_eq!(Some(0.6), zones.find("easy").map(Zone::target));
+    assert_eq!(Some("Hard"), zones.zone_of(1.0).map(|zone| zone.name.as_str()));
+
+    zones.set("hard=100-120@105".parse().unwrap());
+    assert_eq!(Some(1.05), zones.find("work").map(Zone::target));
+    assert_eq!(None, zones.zone_of(0.8));
+}
+
+#[test]
+fn test_translate() {
+    let heart_rate = ZoneModel::coggan_heart_rate();
+    let power = ZoneModel::coggan();
+    assert_eq!(Some(0.76), heart_rate.translate(0.84, &power));
+    assert_eq!(Some(0.90), heart_rate.translate(0.94, &power));
+    assert_eq!(Some(1.20), heart_rate.translate(1.30, &power));
+    assert_eq!(Some(0.40), heart_rate.translate(0.30, &power));
+    assert_eq!(None, heart_rate.translate(1.0, &ZoneModel::seiler()));
+}
+
+#[test]
+fn test_classify() {
+    let zones = ZoneModel::coggan();
+    assert_eq!(7, zones.distinct().len());
+    let name = |power| zones.classify(power).map(|zone| zone.name.as_str());
+    assert_eq!(Some("Z3"), name(0.88));
+    assert_eq!(Some("Z1"), name(0.2));
+    assert_eq!(Some("Z2"), name(0.557));
+    assert_eq!(Some("Z7"), name(2.5));
+}